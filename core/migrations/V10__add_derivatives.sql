@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS derivative (
+    id TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    underlying TEXT NOT NULL,
+    put_call TEXT,
+    strike NUMERIC,
+    expiry DATE,
+    multiplier NUMERIC NOT NULL,
+    currency TEXT NOT NULL,
+    date_added TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS option_exercise (
+    id TEXT PRIMARY KEY,
+    date TIMESTAMP WITH TIME ZONE NOT NULL,
+    broker TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    option_identifier TEXT NOT NULL,
+    contracts NUMERIC NOT NULL,
+    underlying_identifier TEXT NOT NULL,
+    date_added TIMESTAMP WITH TIME ZONE NOT NULL
+);
@@ -0,0 +1,17 @@
+-- funds are either reporting their taxable income to the OeKB ("Meldefonds") or taxed with the
+-- lump-sum rule for non-reporting funds, ISINs without a classification aren't funds
+CREATE TABLE IF NOT EXISTS fund_classification (
+    isin TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    date_added TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- the lump-sum income of a non-reporting fund is based on its first and last price of a year
+CREATE TABLE IF NOT EXISTS fund_price (
+    isin TEXT NOT NULL,
+    date DATE NOT NULL,
+    price NUMERIC NOT NULL,
+    currency TEXT NOT NULL,
+    date_added TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (isin, date)
+);
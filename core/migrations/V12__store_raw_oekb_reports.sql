@@ -0,0 +1,10 @@
+-- the report as published by the OeKB, so it can be interpreted again without fetching it
+ALTER TABLE fund_report_oekb ADD COLUMN IF NOT EXISTS raw_report TEXT;
+
+-- every tax code ("Steuercode") of a report with its amount per unit for private investors
+CREATE TABLE IF NOT EXISTS fund_report_oekb_item (
+    report_id INTEGER NOT NULL,
+    tax_code INTEGER NOT NULL,
+    amount NUMERIC NOT NULL,
+    PRIMARY KEY (report_id, tax_code)
+);
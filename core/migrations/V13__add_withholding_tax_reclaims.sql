@@ -0,0 +1,15 @@
+-- the country withholding tax is paid to, if it isn't the one of the ISIN, e.g. for funds, or for
+-- cash interest, which is keyed by the broker paying it
+CREATE TABLE IF NOT EXISTS withholding_tax_domicile (
+    identifier TEXT PRIMARY KEY,
+    country TEXT NOT NULL,
+    date_added TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- overrides of the built-in treaty rates of Austria's double taxation agreements
+CREATE TABLE IF NOT EXISTS withholding_tax_treaty_rate (
+    country TEXT PRIMARY KEY,
+    dividend_rate NUMERIC,
+    interest_rate NUMERIC,
+    date_added TIMESTAMP WITH TIME ZONE NOT NULL
+);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS imported_file (
+    hash TEXT PRIMARY KEY,
+    path TEXT NOT NULL,
+    date_imported TIMESTAMP WITH TIME ZONE NOT NULL
+);
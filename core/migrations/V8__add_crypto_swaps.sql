@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS crypto_swap (
+    id TEXT PRIMARY KEY,
+    date TIMESTAMP WITH TIME ZONE NOT NULL,
+    broker TEXT NOT NULL,
+    from_identifier TEXT NOT NULL,
+    from_units NUMERIC NOT NULL,
+    to_identifier TEXT NOT NULL,
+    to_units NUMERIC NOT NULL,
+    date_added TIMESTAMP WITH TIME ZONE NOT NULL
+);
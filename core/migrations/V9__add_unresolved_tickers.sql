@@ -0,0 +1,16 @@
+-- a ticker maps to exactly one ISIN, so mappings can be overridden
+DELETE FROM ticker_conversion a USING ticker_conversion b WHERE a.ticker = b.ticker AND a.id > b.id;
+CREATE UNIQUE INDEX IF NOT EXISTS ticker_conversion_ticker ON ticker_conversion (ticker);
+ALTER TABLE ticker_conversion ADD COLUMN IF NOT EXISTS share_class_figi TEXT;
+
+CREATE TABLE IF NOT EXISTS unresolved_ticker (
+    ticker TEXT PRIMARY KEY,
+    broker TEXT NOT NULL,
+    path TEXT NOT NULL,
+    occurrences INTEGER NOT NULL,
+    share_class_figi TEXT,
+    name TEXT,
+    first_seen TIMESTAMP WITH TIME ZONE NOT NULL,
+    last_seen TIMESTAMP WITH TIME ZONE NOT NULL,
+    last_lookup TIMESTAMP WITH TIME ZONE
+);
@@ -4,27 +4,39 @@ use crate::{
         performance::get_performance_signals,
     },
     services::{
+        archives::bundle_entries,
         events::get_events,
-        parsers::parse_timestamp,
+        files::export_json,
+        importers::{context::ImportContext, report::ImportReport},
+        instruments::tickers::{
+            get_ticker_overview, resolve_unresolved_tickers, set_ticker_mapping,
+        },
+        parsers::{parse_file_for_import, parse_timestamp},
         portfolio::get_portfolio_overview,
         positions::get_positions_overview,
+        sale_simulation::{self, InvalidSale},
         shared::{
             constants::{OUT_DIR, SESSION_TOKEN_KEY},
             env::{get_env_variable, is_running_in_docker},
+            identity::ensure_trades_rekeyed,
         },
+        tax_harvesting::get_harvesting_report,
         taxation::{get_capital_gains_tax_report, get_detailed_capital_gains_tax_report},
+        withholding_tax::get_withholding_tax_reclaims,
     },
 };
 use axum::{
-    extract::{Json, Query, Request},
+    extract::{multipart::MultipartError, Json, Multipart, Query, Request},
     http::{HeaderMap, HeaderValue, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
 use chrono::{DateTime, Datelike, NaiveDate, Utc};
 use log;
+use rust_decimal::Decimal;
 use serde::Deserialize;
 
+use std::path::Path;
 use tokio::fs;
 use tower_sessions::Session;
 
@@ -329,6 +341,101 @@ pub async fn taxation(
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ReclaimsQuery {
+    pub year: Option<i32>,
+}
+
+pub async fn withholding_tax_reclaims(
+    Query(query): Query<ReclaimsQuery>,
+) -> anyhow::Result<impl IntoResponse, ErrorResponse> {
+    let reclaims = get_withholding_tax_reclaims(query.year)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to compute withholding tax reclaims: {}", e);
+            ErrorResponse::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "TaxationComputationError",
+                &format!("Failed to compute withholding tax reclaims: {}", e),
+                None,
+            )
+        })?;
+    json_response(&reclaims).map_err(|status| {
+        ErrorResponse::new(
+            status,
+            "SerializationError",
+            "Failed to serialize withholding tax reclaims",
+            None,
+        )
+    })
+}
+
+pub async fn tax_loss_harvesting() -> anyhow::Result<impl IntoResponse, ErrorResponse> {
+    let report = get_harvesting_report().await.map_err(|e| {
+        log::error!("Failed to compute tax-loss harvesting suggestions: {}", e);
+        ErrorResponse::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "TaxationComputationError",
+            &format!("Failed to compute tax-loss harvesting suggestions: {}", e),
+            None,
+        )
+    })?;
+    json_response(&report).map_err(|status| {
+        ErrorResponse::new(
+            status,
+            "SerializationError",
+            "Failed to serialize tax-loss harvesting suggestions",
+            None,
+        )
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SaleSimulationPayload {
+    pub isin: String,
+    pub units: Decimal,
+    pub broker: Option<String>,
+    pub price: Option<Decimal>,
+}
+
+pub async fn simulate_sale(
+    Json(payload): Json<SaleSimulationPayload>,
+) -> anyhow::Result<impl IntoResponse, ErrorResponse> {
+    // positions that aren't held and invalid units or prices are the expected failures
+    let simulation = sale_simulation::simulate_sale(
+        &payload.isin,
+        payload.units,
+        payload.broker.as_deref(),
+        payload.price,
+    )
+    .await
+    .map_err(|e| {
+        if e.downcast_ref::<InvalidSale>().is_some() {
+            return ErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidSaleSimulation",
+                &e.to_string(),
+                None,
+            );
+        }
+        log::error!("Failed to simulate sale: {}", e);
+        ErrorResponse::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "SaleSimulationError",
+            &format!("Failed to simulate sale: {}", e),
+            None,
+        )
+    })?;
+    json_response(&simulation).map_err(|status| {
+        ErrorResponse::new(
+            status,
+            "SerializationError",
+            "Failed to serialize sale simulation",
+            None,
+        )
+    })
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PositionsQuery {
     pub date: Option<String>,
@@ -400,3 +507,140 @@ pub async fn positions(
 pub async fn auth_state() -> impl IntoResponse {
     (StatusCode::OK, "authenticated")
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    pub dry_run: Option<bool>,
+}
+
+fn invalid_upload(e: MultipartError) -> ErrorResponse {
+    ErrorResponse::new(e.status(), "InvalidUpload", &e.body_text(), None)
+}
+
+pub async fn import_files(
+    Query(query): Query<ImportQuery>,
+    mut multipart: Multipart,
+) -> anyhow::Result<impl IntoResponse, ErrorResponse> {
+    let mut uploaded_files: Vec<(String, Vec<u8>)> = vec![];
+    // encrypted PDFs are tried with every `password` field of the form
+    let mut pdf_passwords: Vec<String> = vec![];
+    while let Some(field) = multipart.next_field().await.map_err(invalid_upload)? {
+        if let Some(file_name) = field.file_name().map(|name| name.to_string()) {
+            let content = field.bytes().await.map_err(invalid_upload)?;
+            uploaded_files.push((file_name, content.to_vec()));
+        } else if field.name() == Some("password") {
+            pdf_passwords.push(field.text().await.map_err(invalid_upload)?);
+        }
+    }
+
+    let mut report = ImportReport::new();
+    report.dry_run = query.dry_run.unwrap_or(false);
+    if !report.dry_run {
+        ensure_trades_rekeyed().await.map_err(|e| {
+            ErrorResponse::new(StatusCode::CONFLICT, "LegacyTradeIds", &e.to_string(), None)
+        })?;
+    }
+
+    for (file_name, content) in uploaded_files {
+        // archives are imported file by file, each with its own entry in the report
+        for bundle_entry in bundle_entries(&file_name, content) {
+            let mut ctx = ImportContext::for_bundle_entry(&bundle_entry);
+            ctx.interactive = false;
+            ctx.dry_run = report.dry_run;
+            ctx.pdf_passwords = pdf_passwords.clone();
+            let result = match bundle_entry.content {
+                Ok(content) => {
+                    parse_file_for_import(&content, Path::new(&bundle_entry.path), &mut ctx).await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                log::error!(
+                    "Failed to import uploaded file {}: {:?}",
+                    bundle_entry.path,
+                    e
+                );
+                ctx.record_error(&e);
+            }
+            report.files.push(ctx.report);
+        }
+    }
+
+    if !report.dry_run {
+        if let Err(e) = export_json(&report, "import_report") {
+            log::error!("Failed to write import report: {}", e);
+        }
+    }
+
+    json_response(&report).map_err(|status| {
+        ErrorResponse::new(
+            status,
+            "SerializationError",
+            "Failed to serialize import report",
+            None,
+        )
+    })
+}
+
+pub async fn tickers() -> anyhow::Result<impl IntoResponse, ErrorResponse> {
+    let overview = get_ticker_overview().await.map_err(|e| {
+        log::error!("Failed to get ticker mappings: {}", e);
+        ErrorResponse::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "TickerError",
+            &format!("Failed to get ticker mappings: {}", e),
+            None,
+        )
+    })?;
+    json_response(&overview).map_err(|status| {
+        ErrorResponse::new(
+            status,
+            "SerializationError",
+            "Failed to serialize ticker mappings",
+            None,
+        )
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TickerMappingPayload {
+    pub ticker: String,
+    pub isin: String,
+}
+
+pub async fn set_ticker(
+    Json(payload): Json<TickerMappingPayload>,
+) -> anyhow::Result<impl IntoResponse, ErrorResponse> {
+    // invalid ISINs are the only expected failure
+    set_ticker_mapping(&payload.ticker, &payload.isin)
+        .await
+        .map_err(|e| {
+            ErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidTickerMapping",
+                &e.to_string(),
+                None,
+            )
+        })?;
+    tickers().await
+}
+
+pub async fn resolve_tickers() -> anyhow::Result<impl IntoResponse, ErrorResponse> {
+    let resolved = resolve_unresolved_tickers().await.map_err(|e| {
+        log::error!("Failed to resolve tickers: {}", e);
+        ErrorResponse::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "TickerError",
+            &format!("Failed to resolve tickers: {}", e),
+            None,
+        )
+    })?;
+    json_response(&resolved).map_err(|status| {
+        ErrorResponse::new(
+            status,
+            "SerializationError",
+            "Failed to serialize resolved tickers",
+            None,
+        )
+    })
+}
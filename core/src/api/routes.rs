@@ -1,4 +1,5 @@
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, get_service, post},
     Router,
 };
@@ -12,10 +13,14 @@ use tower_http::{
 use tower_sessions::{Expiry, MemoryStore, SessionManagerLayer};
 
 use super::handlers::{
-    auth_state, check_auth, login, logout, past_performance, performance, portfolio, positions,
-    taxation, taxation_detailed, timeline,
+    auth_state, check_auth, import_files, login, logout, past_performance, performance, portfolio,
+    positions, resolve_tickers, set_ticker, simulate_sale, tax_loss_harvesting, taxation,
+    taxation_detailed, tickers, timeline, withholding_tax_reclaims,
 };
 
+// yearly exports of some brokers come as zip archives with hundreds of PDFs
+const MAX_UPLOAD_SIZE: usize = 256 * 1024 * 1024;
+
 pub fn create_router() -> anyhow::Result<Router> {
     let session_store = MemoryStore::default();
     let session_layer = SessionManagerLayer::new(session_store)
@@ -35,8 +40,17 @@ pub fn create_router() -> anyhow::Result<Router> {
         .route("/timeline", get(timeline))
         .route("/taxation", get(taxation))
         .route("/taxation/detailed", get(taxation_detailed))
+        .route("/taxation/reclaims", get(withholding_tax_reclaims))
+        .route("/taxation/harvesting", get(tax_loss_harvesting))
+        .route("/simulate/sale", post(simulate_sale))
         .route("/positions", get(positions))
         .route("/auth_state", get(auth_state))
+        .route("/tickers", get(tickers).post(set_ticker))
+        .route("/tickers/resolve", post(resolve_tickers))
+        .route(
+            "/import",
+            post(import_files).layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE)),
+        )
         .layer(axum::middleware::from_fn(check_auth));
 
     let cors_layer = CorsLayer::new()
@@ -0,0 +1,91 @@
+use chrono::NaiveDate;
+use clap::{Subcommand, ValueEnum};
+use rust_decimal::Decimal;
+use tabled::Table;
+
+use crate::{
+    database::queries::fund::{get_fund_classifications, get_fund_prices},
+    services::instruments::fund_data::{
+        export_oekb_fund_reports, reinterpret_oekb_fund_reports, set_fund_classification,
+        set_fund_price, NON_REPORTING_FUND, REPORTING_FUND,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum FundKind {
+    /// Reports its taxable income to the OeKB ("Meldefonds")
+    Reporting,
+    /// Taxed with the lump-sum rule ("Nicht-Meldefonds")
+    NonReporting,
+    /// Not a fund
+    None,
+}
+
+#[derive(Debug, Subcommand, PartialEq)]
+pub enum FundsCommand {
+    /// Lists the fund classifications and prices
+    List {},
+    /// Sets whether an ISIN is a reporting or a non-reporting fund
+    Classify { isin: String, kind: FundKind },
+    /// Adds the price of a fund on a date, the lump-sum income of non-reporting funds is based on
+    /// the first and last price of a year
+    Price {
+        isin: String,
+        date: NaiveDate,
+        price: Decimal,
+        #[arg(long, default_value = "EUR")]
+        currency: String,
+    },
+    /// Interprets the stored OeKB reports again without fetching them
+    Reinterpret {},
+    /// Exports the stored OeKB reports, they can be imported again like any other file
+    ExportReports {},
+}
+
+pub async fn funds(cmd: FundsCommand) -> anyhow::Result<()> {
+    match cmd {
+        FundsCommand::List {} => {
+            println!("Fund classifications:");
+            println!("{}", Table::new(get_fund_classifications().await?));
+            println!("Fund prices:");
+            println!("{}", Table::new(get_fund_prices().await?));
+        }
+        FundsCommand::Classify { isin, kind } => {
+            let kind = match kind {
+                FundKind::Reporting => Some(REPORTING_FUND),
+                FundKind::NonReporting => Some(NON_REPORTING_FUND),
+                FundKind::None => None,
+            };
+            set_fund_classification(&isin, kind).await?;
+            println!(
+                "🏷️ {} classified as {}",
+                isin.trim().to_uppercase(),
+                kind.unwrap_or("no fund")
+            );
+        }
+        FundsCommand::Price {
+            isin,
+            date,
+            price,
+            currency,
+        } => {
+            let fund_price = set_fund_price(&isin, date, price, &currency).await?;
+            println!(
+                "🔄 Price of {} on {}: {} {}",
+                fund_price.isin, fund_price.date, fund_price.price, fund_price.currency
+            );
+        }
+        FundsCommand::Reinterpret {} => {
+            let count = reinterpret_oekb_fund_reports().await?;
+            println!("{} OeKB reports interpreted again.", count);
+        }
+        FundsCommand::ExportReports {} => {
+            let count = export_oekb_fund_reports().await?;
+            println!(
+                "{} OeKB reports exported to output/oekb_reports.json",
+                count
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use tabled::Table;
+
+use crate::services::tax_harvesting::get_harvesting_report;
+
+pub async fn harvest() -> anyhow::Result<()> {
+    let report = get_harvesting_report().await?;
+
+    println!("Settlement of {} so far:", report.year);
+    println!("{}", Table::new([&report.settlement]));
+    if report.candidates.is_empty() {
+        println!("🌾 No positions are held at a loss.");
+        return Ok(());
+    }
+    println!("🌾 Positions held at a loss, with the tax saved by selling each of them this year:");
+    println!("{}", Table::new(&report.candidates));
+    println!(
+        "Losses beyond this year's gains and income are forfeited, they can't be carried forward."
+    );
+
+    Ok(())
+}
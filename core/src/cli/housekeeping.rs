@@ -1,5 +1,8 @@
 use crate::services::{
-    instruments::{fund_data::update_oekb_fund_reports, stock_splits::update_stock_splits},
+    instruments::{
+        crypto::update_crypto_prices, fund_data::update_oekb_fund_reports,
+        stock_splits::update_stock_splits,
+    },
     market_data::fx_rates::fetch_historic_ecb_rates,
 };
 
@@ -7,5 +10,6 @@ pub async fn housekeeping() -> anyhow::Result<()> {
     update_stock_splits().await?;
     update_oekb_fund_reports().await?;
     fetch_historic_ecb_rates(None).await?;
+    update_crypto_prices().await?;
     Ok(())
 }
@@ -2,12 +2,23 @@ use fancy_regex::Regex;
 use log::info;
 use std::fs;
 use std::io::{self, Write};
-use std::process;
+use std::path::Path;
 use walkdir::WalkDir;
 
-use crate::services::parsers::parse_file_for_import;
+use crate::services::{
+    archives::{bundle_entries, BundleEntry},
+    files::export_json,
+    importers::{context::ImportContext, errors::ImportError, report::ImportReport},
+    parsers::parse_file_for_import,
+    shared::identity::ensure_trades_rekeyed,
+};
 
 pub async fn import(directory_path: &str) -> anyhow::Result<()> {
+    ensure_trades_rekeyed().await?;
+    let mut report = ImportReport::new();
+    // passwords entered for encrypted PDFs are tried on the remaining files as well
+    let mut pdf_passwords = vec![];
+
     for entry in WalkDir::new(directory_path)
         .into_iter()
         .filter_map(|e| e.ok())
@@ -15,25 +26,89 @@ pub async fn import(directory_path: &str) -> anyhow::Result<()> {
     {
         let file_path = entry.path();
 
-        info!(target: "import", "Importing {:?}", file_path);
-
-        match fs::read(file_path) {
-            Ok(buffer) => {
-                if let Err(e) = parse_file_for_import(&buffer, file_path).await {
-                    eprintln!("Failed to process {}: {:?}", file_path.display(), e);
-                    continue;
-                }
-            }
+        let buffer = match fs::read(file_path) {
+            Ok(buffer) => buffer,
             Err(e) => {
                 eprintln!("Failed to read {}: {:?}", file_path.display(), e);
+                let mut ctx = ImportContext::new(&file_path.display().to_string());
+                ctx.record_error(&e.into());
+                report.files.push(ctx.report);
                 continue;
             }
+        };
+
+        // archives are imported file by file, each with its own entry in the report
+        for bundle_entry in bundle_entries(&file_path.display().to_string(), buffer) {
+            let mut ctx = ImportContext::for_bundle_entry(&bundle_entry);
+            ctx.pdf_passwords = pdf_passwords;
+            ctx.pdf_password_prompt = Some(prompt_pdf_password);
+            import_bundle_entry(bundle_entry, &mut ctx).await;
+            pdf_passwords = ctx.pdf_passwords;
+            report.files.push(ctx.report);
         }
     }
+
+    export_json(&report, "import_report")?;
+    println!("{}", summarize_import(&report));
+
     Ok(())
 }
 
-pub fn choose_match_from_regex(regex_pattern: &str, text: &str) -> anyhow::Result<String> {
+pub async fn import_file(buffer: &[u8], file_path: &Path, ctx: &mut ImportContext) {
+    info!(target: "import", "Importing {:?}", file_path);
+
+    if let Err(e) = parse_file_for_import(buffer, file_path, ctx).await {
+        eprintln!("Failed to process {}: {:?}", file_path.display(), e);
+        ctx.record_error(&e);
+    }
+}
+
+/// Imports a file on its own or unpacked from an archive, archives that can't be read are
+/// reported as errors.
+pub async fn import_bundle_entry(entry: BundleEntry, ctx: &mut ImportContext) {
+    match entry.content {
+        Ok(content) => import_file(&content, Path::new(&entry.path), ctx).await,
+        Err(e) => {
+            eprintln!("Failed to unpack {}: {:?}", entry.path, e);
+            ctx.record_error(&e);
+        }
+    }
+}
+
+/// Asks for the password of an encrypted PDF, `None` if the user skips the file.
+pub fn prompt_pdf_password(path: &str) -> anyhow::Result<Option<String>> {
+    print!(
+        "{} is an encrypted PDF. Enter its password (leave empty to skip the file): ",
+        path
+    );
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let password = input.trim_end_matches(['\r', '\n']);
+    Ok((!password.is_empty()).then(|| password.to_string()))
+}
+
+pub fn summarize_import(report: &ImportReport) -> String {
+    let created: u32 = report.files.iter().map(|file| file.total_created()).sum();
+    let failed = report
+        .files
+        .iter()
+        .filter(|file| !file.errors.is_empty())
+        .count();
+    format!(
+        "Imported {} records from {} files, {} files with errors. See output/import_report.json for details.",
+        created,
+        report.files.len(),
+        failed
+    )
+}
+
+pub fn choose_match_from_regex(
+    regex_pattern: &str,
+    text: &str,
+    interactive: bool,
+) -> anyhow::Result<String> {
     let regex = Regex::new(regex_pattern)?;
 
     let matches: Vec<String> = regex
@@ -42,14 +117,21 @@ pub fn choose_match_from_regex(regex_pattern: &str, text: &str) -> anyhow::Resul
         .collect();
 
     if matches.is_empty() {
-        eprintln!("No matches found for the given regex.");
-        process::exit(1);
+        return Err(ImportError::unparsable(
+            regex_pattern,
+            &text.chars().take(80).collect::<String>(),
+        )
+        .into());
     }
 
     if matches.len() == 1 {
         return Ok(matches[0].clone());
     }
 
+    if !interactive {
+        return Err(ImportError::unparsable(regex_pattern, &matches.join(", ")).into());
+    }
+
     println!("Found matches:");
     for (index, matched_text) in matches.iter().enumerate() {
         println!("{}: {}", index + 1, matched_text);
@@ -57,12 +139,13 @@ pub fn choose_match_from_regex(regex_pattern: &str, text: &str) -> anyhow::Resul
 
     loop {
         print!("Please choose a match (1-{}): ", matches.len());
-        io::stdout().flush().unwrap();
+        io::stdout().flush()?;
 
         let mut input = String::new();
-        io::stdin()
-            .read_line(&mut input)
-            .expect("Failed to read line");
+        // no choice can be made once stdin is closed
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Err(ImportError::unparsable(regex_pattern, &matches.join(", ")).into());
+        }
 
         match input.trim().parse::<usize>() {
             Ok(index) if index > 0 && index <= matches.len() => {
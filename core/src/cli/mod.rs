@@ -1,21 +1,35 @@
+pub mod funds;
+pub mod harvest;
 pub mod housekeeping;
 pub mod import;
 pub mod performance;
 pub mod portfolio;
+pub mod reclaims;
+pub mod rekey;
 pub mod shared;
+pub mod simulate;
 pub mod taxation;
+pub mod tickers;
+pub mod watch;
 
 use std::fs;
 
 use chrono::NaiveDate;
 use chrono::{Duration, Utc};
 use clap::{Parser, Subcommand};
+use funds::{funds, FundsCommand};
+use harvest::harvest;
 use housekeeping::housekeeping;
 use import::import;
 use performance::performance;
 use portfolio::portfolio;
+use reclaims::{reclaims, ReclaimsCommand};
+use rekey::rekey;
 use shared::confirm_action;
+use simulate::{simulate, SimulateCommand};
 use taxation::{calculate_taxes, calculate_taxes_detailed};
+use tickers::{tickers, TickersCommand};
+use watch::{watch, WatchArgs};
 
 use crate::{
     api,
@@ -24,7 +38,11 @@ use crate::{
         composite::{events_exist, EventFilter},
         fx_rate::get_most_recent_rate,
     },
-    services::{market_data::fx_rates::fetch_historic_ecb_rates, parsers::extract_pdf_text},
+    services::{
+        market_data::fx_rates::fetch_historic_ecb_rates,
+        parsers::{configured_pdf_passwords, extract_pdf_text},
+        shared::constants::IN_DIR,
+    },
 };
 
 #[derive(Parser, Debug)]
@@ -54,19 +72,56 @@ enum Command {
         #[arg(long)]
         with_events: bool,
     },
+    Harvest {},
+    Simulate {
+        #[clap(subcommand)]
+        cmd: SimulateCommand,
+    },
     DebugPdf {
         path: String,
     },
+    Rekey {
+        #[arg(long)]
+        dry_run: bool,
+    },
+    Tickers {
+        #[clap(subcommand)]
+        cmd: TickersCommand,
+    },
+    Funds {
+        #[clap(subcommand)]
+        cmd: FundsCommand,
+    },
+    Reclaims {
+        #[clap(subcommand)]
+        cmd: ReclaimsCommand,
+    },
+    Watch {
+        #[arg(long, default_value = IN_DIR)]
+        path: String,
+        #[arg(long, default_value_t = 60)]
+        interval: u64,
+        #[arg(short, long)]
+        notify: bool,
+    },
     Api {
         #[arg(short, long)]
         silent: bool,
+        #[arg(long)]
+        watch: bool,
+        #[arg(long)]
+        notify: bool,
     },
 }
 
 pub async fn cli() -> anyhow::Result<()> {
     let args = Args::parse();
     let args = args.cmd;
-    if (args != Command::Api { silent: true }) {
+    let is_unattended = matches!(
+        args,
+        Command::Api { silent: true, .. } | Command::Watch { .. }
+    );
+    if !is_unattended {
         // fetch new fx rates when older than 4 days
         let four_days_ago = Utc::now().naive_utc().date() - Duration::days(4);
         let most_recent_fx_rate = get_most_recent_rate().await?;
@@ -138,15 +193,70 @@ pub async fn cli() -> anyhow::Result<()> {
                 println!("\x1b[31mPlease import events (e.g. trades, dividends) first. Run with --help to learn how.\x1b[0m");
             }
         }
-        Command::Api { silent: _ } => {
+        Command::Harvest {} => {
+            if events_exist(EventFilter::TradesOnly).await? {
+                harvest().await?;
+            } else {
+                println!(
+                    "\x1b[31mPlease import trades first. Run with --help to learn more.\x1b[0m"
+                );
+            }
+        }
+        Command::Simulate { cmd } => {
+            simulate(cmd).await?;
+        }
+        Command::Watch {
+            path,
+            interval,
+            notify,
+        } => {
+            watch(WatchArgs {
+                path,
+                interval,
+                notify,
+            })
+            .await?;
+        }
+        Command::Api {
+            silent: _,
+            watch: watch_input,
+            notify,
+        } => {
             println!("Starting web server...");
-            api().await?;
+            if watch_input {
+                let args = WatchArgs {
+                    path: IN_DIR.to_string(),
+                    interval: 60,
+                    notify,
+                };
+                // the API keeps serving if the watcher fails
+                tokio::spawn(async move {
+                    if let Err(e) = watch(args).await {
+                        eprintln!("Watching the input folder stopped: {:?}", e);
+                    }
+                });
+                api().await?;
+            } else {
+                api().await?;
+            }
+        }
+        Command::Rekey { dry_run } => {
+            rekey(dry_run).await?;
+        }
+        Command::Tickers { cmd } => {
+            tickers(cmd).await?;
+        }
+        Command::Funds { cmd } => {
+            funds(cmd).await?;
+        }
+        Command::Reclaims { cmd } => {
+            reclaims(cmd).await?;
         }
         Command::DebugPdf { path } => match fs::read(path.clone()) {
-            Ok(buffer) => {
-                let text = extract_pdf_text(&buffer)?;
-                println!("{}", text);
-            }
+            Ok(buffer) => match extract_pdf_text(&buffer, &configured_pdf_passwords())? {
+                Some(text) => println!("{}", text),
+                None => eprintln!("{} is encrypted, set its password in the environment", path),
+            },
             Err(e) => {
                 eprintln!("Failed to read {}: {:?}", path, e);
             }
@@ -0,0 +1,94 @@
+use clap::Subcommand;
+use rust_decimal::Decimal;
+use tabled::Table;
+
+use crate::{
+    database::queries::withholding_tax::{get_treaty_rates, get_withholding_tax_domiciles},
+    services::withholding_tax::{
+        get_withholding_tax_reclaims, set_treaty_rate, set_withholding_tax_domicile,
+        WithholdingTaxKind,
+    },
+};
+
+#[derive(Debug, Subcommand, PartialEq)]
+pub enum ReclaimsCommand {
+    /// Shows the foreign tax withheld per year and country, and the payments to reclaim tax for
+    Report {
+        #[arg(long)]
+        year: Option<i32>,
+    },
+    /// Sets the country tax on an ISIN, or on cash interest of a broker, is withheld by
+    Domicile { identifier: String, country: String },
+    /// Overrides the treaty rate of a country in percent, for dividends unless `--interest` is set
+    TreatyRate {
+        country: String,
+        rate: Decimal,
+        #[arg(long)]
+        interest: bool,
+    },
+    /// Lists the configured domiciles and treaty rates
+    List {},
+}
+
+pub async fn reclaims(cmd: ReclaimsCommand) -> anyhow::Result<()> {
+    match cmd {
+        ReclaimsCommand::Report { year } => {
+            let reclaims = get_withholding_tax_reclaims(year).await?;
+            if reclaims.is_empty() {
+                println!("No foreign tax withheld.");
+                return Ok(());
+            }
+            println!("Foreign withholding tax:");
+            println!("{}", Table::new(&reclaims));
+            for reclaim in reclaims
+                .iter()
+                .filter(|reclaim| !reclaim.payments.is_empty())
+            {
+                println!(
+                    "{:?} payments to reclaim tax from {} for {}:",
+                    reclaim.kind, reclaim.country, reclaim.year
+                );
+                println!("{}", Table::new(&reclaim.payments));
+            }
+            if reclaims.iter().any(|reclaim| reclaim.treaty_rate.is_none()) {
+                println!("Set the treaty rate of countries without one with `reclaims treaty-rate <COUNTRY> <RATE>`, or the domicile of unknown ones with `reclaims domicile <ISIN|BROKER> <COUNTRY>`.");
+            }
+        }
+        ReclaimsCommand::Domicile {
+            identifier,
+            country,
+        } => {
+            set_withholding_tax_domicile(&identifier, &country).await?;
+            println!(
+                "🌍 Tax on {} is withheld by {}",
+                identifier.trim(),
+                country.trim().to_uppercase()
+            );
+        }
+        ReclaimsCommand::TreatyRate {
+            country,
+            rate,
+            interest,
+        } => {
+            let kind = if interest {
+                WithholdingTaxKind::Interest
+            } else {
+                WithholdingTaxKind::Dividend
+            };
+            set_treaty_rate(&country, rate, kind).await?;
+            println!(
+                "🌍 Treaty rate of {} for {:?} set to {}%",
+                country.trim().to_uppercase(),
+                kind,
+                rate
+            );
+        }
+        ReclaimsCommand::List {} => {
+            println!("Domiciles:");
+            println!("{}", Table::new(get_withholding_tax_domiciles().await?));
+            println!("Treaty rates (overriding the built-in ones):");
+            println!("{}", Table::new(get_treaty_rates().await?));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use anyhow::bail;
+
+use crate::{
+    database::{
+        models::trade::{Trade, TradeWithHash},
+        queries::trade::{delete_trade, get_all_trades_with_hash, update_trade_hash},
+    },
+    services::shared::identity::{legacy_trade_hash, trade_hash, trade_record_id, DateGranularity},
+};
+
+// Trades imported without transaction ID were hashed from ISIN, units, direction and price only,
+// so of identical trades only the first one of a file was kept. Re-keying moves them to the
+// date-aware record identity with the occurrence the importer gives the first identical trade of
+// a file. If that ID is taken by the same trade, the legacy row is a duplicate and removed,
+// if it's taken by another trade the next free occurrence is used.
+pub async fn rekey(dry_run: bool) -> anyhow::Result<()> {
+    let granularity = DateGranularity::from_env();
+    let trades = get_all_trades_with_hash().await?;
+    let mut stored_trades: HashMap<String, Trade> = trades
+        .iter()
+        .map(|stored_trade| (stored_trade.hash.clone(), stored_trade.to_trade()))
+        .collect();
+
+    let mut rekeyed = 0;
+    let mut removed = 0;
+
+    for TradeWithHash { hash, .. } in &trades {
+        let trade = stored_trades[hash].clone();
+        if *hash != legacy_trade_hash(&trade) {
+            continue;
+        }
+
+        let mut occurrence = 0;
+        let new_hash = loop {
+            let new_hash = trade_hash(
+                &trade.broker,
+                &trade_record_id(&trade, granularity, occurrence),
+            );
+            match stored_trades.get(&new_hash) {
+                None => break Some(new_hash),
+                Some(stored_trade) if is_same_trade(stored_trade, &trade) => break None,
+                Some(_) => occurrence += 1,
+            }
+        };
+
+        let Some(new_hash) = new_hash else {
+            if dry_run {
+                println!("Would remove duplicate of a re-keyed trade: {:?}", trade);
+            } else {
+                delete_trade(hash).await?;
+                println!("🗑️ Duplicate of a re-keyed trade removed: {:?}", trade);
+            }
+            stored_trades.remove(hash);
+            removed += 1;
+            continue;
+        };
+
+        if dry_run {
+            println!("Would re-key trade: {:?}", trade);
+        } else if update_trade_hash(hash, &new_hash).await? {
+            println!("🔑 Trade re-keyed: {:?}", trade);
+        } else {
+            bail!(
+                "Couldn't re-key trade, its new ID was taken in the meantime: {:?}",
+                trade
+            );
+        }
+        stored_trades.remove(hash);
+        stored_trades.insert(new_hash, trade);
+        rekeyed += 1;
+    }
+
+    println!(
+        "{} trades {}re-keyed, {} duplicates {}removed.",
+        rekeyed,
+        if dry_run { "would be " } else { "" },
+        removed,
+        if dry_run { "would be " } else { "" },
+    );
+    if rekeyed > 0 && !dry_run {
+        println!("Trades dropped as duplicates by earlier imports can be restored by importing their statements again.");
+    }
+
+    Ok(())
+}
+
+// like the check of the importer whether a record with the same ID was already imported
+fn is_same_trade(stored_trade: &Trade, trade: &Trade) -> bool {
+    stored_trade.isin == trade.isin
+        && stored_trade.date == trade.date
+        && stored_trade.units == trade.units
+        && stored_trade.direction == trade.direction
+}
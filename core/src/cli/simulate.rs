@@ -0,0 +1,61 @@
+use clap::Subcommand;
+use rust_decimal::Decimal;
+use tabled::Table;
+
+use crate::services::sale_simulation::simulate_sale;
+
+#[derive(Debug, Subcommand, PartialEq)]
+pub enum SimulateCommand {
+    /// Shows the tax impact of selling units of a position now, without storing the sale
+    Sell {
+        isin: String,
+        units: Decimal,
+        /// the broker of the depot to sell from, by default the one holding the most units
+        #[arg(long)]
+        broker: Option<String>,
+        /// the price per unit in the currency of the position, by default the current price
+        #[arg(long)]
+        price: Option<Decimal>,
+    },
+}
+
+pub async fn simulate(cmd: SimulateCommand) -> anyhow::Result<()> {
+    match cmd {
+        SimulateCommand::Sell {
+            isin,
+            units,
+            broker,
+            price,
+        } => {
+            let simulation = simulate_sale(&isin, units, broker.as_deref(), price).await?;
+
+            println!(
+                "🧮 Selling {} units of {} ({}) at {} at {} {} for {} EUR:",
+                simulation.units,
+                simulation.name,
+                simulation.isin,
+                simulation.broker,
+                simulation.price.round_dp(4),
+                simulation.currency,
+                simulation.proceeds_eur
+            );
+            println!("Realised gain: {} EUR", simulation.realised_gain_eur);
+            println!("FX appreciation: {} EUR", simulation.fx_appreciation_eur);
+            println!("Additional tax: {} EUR", simulation.additional_tax_eur);
+            println!("Settlement of this year after the sale:");
+            println!("{}", Table::new([&simulation.settlement]));
+            match (&simulation.new_wac, &simulation.new_depot_wac) {
+                (None, None) => println!("The position is closed."),
+                (new_wac, new_depot_wac) => {
+                    println!(
+                        "WAC after the sale, across all depots and at {}:",
+                        simulation.broker
+                    );
+                    println!("{}", Table::new(new_wac.iter().chain(new_depot_wac.iter())));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -17,13 +17,35 @@ pub async fn calculate_taxes(
     let taxable_amounts_table = Table::new(&taxation_report.taxable_amounts).to_string();
     let securities_wac_table = Table::new(&taxation_report.securities_wacs).to_string();
     let currency_wac_table = Table::new(&taxation_report.currency_wacs).to_string();
+    let crypto_amounts_table = Table::new(&taxation_report.crypto_amounts).to_string();
+    let crypto_wac_table = Table::new(&taxation_report.crypto_wacs).to_string();
+    let derivative_wac_table = Table::new(&taxation_report.derivative_wacs).to_string();
+    let non_reporting_fund_table =
+        Table::new(&taxation_report.non_reporting_fund_income).to_string();
+    let settlements_table = Table::new(&taxation_report.settlements).to_string();
 
     println!("Taxable amounts:");
     println!("{}", taxable_amounts_table);
+    println!("Expected settlement after loss offsetting (positive to pay, negative refunded):");
+    println!("{}", settlements_table);
     println!("Securities WAC:");
     println!("{}", securities_wac_table);
     println!("Currency WAC:");
     println!("{}", currency_wac_table);
+    if !taxation_report.crypto_wacs.is_empty() || !taxation_report.crypto_amounts.is_empty() {
+        println!("Crypto gains (special tax rate since March 2022):");
+        println!("{}", crypto_amounts_table);
+        println!("Crypto WAC:");
+        println!("{}", crypto_wac_table);
+    }
+    if !taxation_report.derivative_wacs.is_empty() {
+        println!("Open options and futures:");
+        println!("{}", derivative_wac_table);
+    }
+    if !taxation_report.non_reporting_fund_income.is_empty() {
+        println!("Lump-sum income of funds without OeKB report:");
+        println!("{}", non_reporting_fund_table);
+    }
 
     Ok(())
 }
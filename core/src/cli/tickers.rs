@@ -0,0 +1,62 @@
+use clap::Subcommand;
+use tabled::Table;
+
+use crate::services::instruments::tickers::{
+    get_ticker_overview, resolve_unresolved_tickers, set_ticker_mapping,
+};
+
+#[derive(Debug, Subcommand, PartialEq)]
+pub enum TickersCommand {
+    /// Lists the ticker mappings and the tickers waiting for review
+    List {
+        #[arg(long)]
+        unresolved: bool,
+    },
+    /// Adds or overrides the ISIN a ticker maps to
+    Set { ticker: String, isin: String },
+    /// Matches the tickers waiting for review against the known ISINs via OpenFIGI again
+    Resolve {},
+}
+
+pub async fn tickers(cmd: TickersCommand) -> anyhow::Result<()> {
+    match cmd {
+        TickersCommand::List { unresolved } => {
+            let overview = get_ticker_overview().await?;
+            if !unresolved {
+                println!("{}", Table::new(&overview.conversions));
+            }
+            if overview.unresolved.is_empty() {
+                println!("No unresolved tickers 🎉");
+            } else {
+                println!("Unresolved tickers, add a mapping with `tickers set <TICKER> <ISIN>`:");
+                println!("{}", Table::new(&overview.unresolved));
+            }
+        }
+        TickersCommand::Set { ticker, isin } => {
+            let unresolved = set_ticker_mapping(&ticker, &isin).await?;
+            println!(
+                "🔗 {} mapped to {}",
+                ticker.trim().to_uppercase(),
+                isin.trim().to_uppercase()
+            );
+            if let Some(unresolved) = unresolved {
+                print_reimport_hint(&unresolved.path);
+            }
+        }
+        TickersCommand::Resolve {} => {
+            let resolved = resolve_unresolved_tickers().await?;
+            println!("{} tickers resolved.", resolved.len());
+            for unresolved in resolved {
+                println!("🔗 {} resolved", unresolved.ticker);
+                print_reimport_hint(&unresolved.path);
+            }
+        }
+    }
+    Ok(())
+}
+
+// rows of a ticker were left out of the import, importing the file again adds them while
+// everything else is recognised as duplicate
+fn print_reimport_hint(path: &str) {
+    println!("Import {} again to add the records of this ticker.", path);
+}
@@ -0,0 +1,186 @@
+use std::{collections::HashSet, fs, path::Path, time::Duration};
+
+use log::{error, info};
+use walkdir::WalkDir;
+
+use crate::{
+    cli::{
+        housekeeping::housekeeping,
+        import::{import_bundle_entry, summarize_import},
+        performance::performance,
+        portfolio::{portfolio, PortfolioArgs},
+        taxation::calculate_taxes,
+    },
+    database::queries::{
+        composite::{events_exist, EventFilter},
+        imported_file::{add_imported_file_to_db, imported_file_exists},
+    },
+    services::{
+        archives::bundle_entries,
+        files::export_json,
+        importers::{context::ImportContext, report::ImportReport},
+        notifications::Notification,
+        shared::{identity::ensure_trades_rekeyed, util::hash_bytes},
+    },
+};
+
+// files modified more recently are assumed to still be written to and are picked up next round
+const SETTLE_TIME: Duration = Duration::from_secs(5);
+
+pub struct WatchArgs {
+    pub path: String,
+    pub interval: u64,
+    pub notify: bool,
+}
+
+pub async fn watch(args: WatchArgs) -> anyhow::Result<()> {
+    ensure_trades_rekeyed().await?;
+    println!("Watching {} for new statements...", args.path);
+    let mut interval = tokio::time::interval(Duration::from_secs(args.interval));
+    // hashes of files imported with errors, they're retried silently until records get added
+    let mut retried_files = HashSet::new();
+    loop {
+        interval.tick().await;
+        if let Err(e) = import_new_files(&args, &mut retried_files).await {
+            error!("Failed to import new files: {:?}", e);
+        }
+    }
+}
+
+async fn import_new_files(
+    args: &WatchArgs,
+    retried_files: &mut HashSet<String>,
+) -> anyhow::Result<()> {
+    let mut report = ImportReport::new();
+
+    for entry in WalkDir::new(&args.path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        let file_path = entry.path();
+
+        // e.g. a file removed since it was listed
+        let modified = match fs::metadata(file_path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(e) => {
+                error!(
+                    "Failed to read the metadata of {}: {:?}",
+                    file_path.display(),
+                    e
+                );
+                continue;
+            }
+        };
+        let is_settled = modified
+            .elapsed()
+            .is_ok_and(|elapsed| elapsed >= SETTLE_TIME);
+        if !is_settled {
+            continue;
+        }
+
+        let buffer = match fs::read(file_path) {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                error!("Failed to read {}: {:?}", file_path.display(), e);
+                continue;
+            }
+        };
+
+        // files are tracked by content, so changed files are imported again
+        let hash = hash_bytes(&buffer);
+        if imported_file_exists(&hash).await? {
+            continue;
+        }
+
+        let mut complete = true;
+        let mut file_reports = vec![];
+        for bundle_entry in bundle_entries(&file_path.display().to_string(), buffer) {
+            let mut ctx = ImportContext::for_bundle_entry(&bundle_entry);
+            ctx.interactive = false;
+            import_bundle_entry(bundle_entry, &mut ctx).await;
+            complete &= ctx.report.complete;
+            file_reports.push(ctx.report);
+        }
+
+        // files with errors that might be resolved later (e.g. a failing request or a ticker
+        // mapping added in the meantime) aren't marked as imported and are retried next round
+        let created = file_reports.iter().any(|file| file.total_created() > 0);
+        let retried = !retried_files.insert(hash.clone());
+        if complete {
+            add_imported_file_to_db(&hash, &file_path.display().to_string()).await?;
+            retried_files.remove(&hash);
+        } else if !retried {
+            info!(
+                "Not all records of {} could be imported, retrying next round",
+                file_path.display()
+            );
+        }
+        if !retried || created {
+            report.files.extend(file_reports);
+        }
+    }
+
+    if report.files.is_empty() {
+        return Ok(());
+    }
+
+    export_json(&report, "import_report")?;
+    let summary = summarize_import(&report);
+    println!("{}", summary);
+
+    recalculate(args.notify).await?;
+
+    if args.notify {
+        let notification = Notification {
+            content: format!("<b>Import</b>\n\n{}", file_summary(&report)),
+        };
+        notification.send().await?;
+    }
+
+    Ok(())
+}
+
+// the steps `import` offers to run afterwards, without asking
+async fn recalculate(notify: bool) -> anyhow::Result<()> {
+    info!("Running housekeeping...");
+    housekeeping().await?;
+    if events_exist(EventFilter::TradesOnly).await? {
+        portfolio(PortfolioArgs {
+            notify: Some(notify),
+        })
+        .await?;
+        performance().await?;
+    }
+    if events_exist(EventFilter::All).await? {
+        calculate_taxes(None, None).await?;
+    }
+    Ok(())
+}
+
+fn file_summary(report: &ImportReport) -> String {
+    report
+        .files
+        .iter()
+        .map(|file| {
+            let file_name = Path::new(&file.path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| file.path.clone())
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+            if file.errors.is_empty() {
+                format!("• {}: {} records added", file_name, file.total_created())
+            } else {
+                format!(
+                    "• {}: {} records added, {} errors",
+                    file_name,
+                    file.total_created(),
+                    file.errors.len()
+                )
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -0,0 +1,15 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+
+// an exchange of one crypto asset for another, both legs are stored as trades as well so positions
+// and performance are tracked per asset
+#[derive(Debug, Clone)]
+pub struct CryptoSwap {
+    pub id: String,
+    pub date: DateTime<Utc>,
+    pub broker: String,
+    pub from_identifier: String,
+    pub from_units: Decimal,
+    pub to_identifier: String,
+    pub to_units: Decimal,
+}
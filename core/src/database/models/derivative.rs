@@ -0,0 +1,32 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+// the contract of an option or future, trades in it are stored per contract, i.e. the price of a
+// unit is the quoted price times the multiplier
+#[derive(Debug, Clone)]
+pub struct Derivative {
+    pub id: String,
+    // Option | Future
+    pub kind: String,
+    // the ISIN of the underlying if it has one, its symbol otherwise
+    pub underlying: String,
+    pub put_call: Option<String>,
+    pub strike: Option<Decimal>,
+    pub expiry: Option<NaiveDate>,
+    pub multiplier: Decimal,
+    pub currency: String,
+}
+
+// an option exercised by the holder or assigned to the writer, links the option leg to the trade
+// in the underlying, both are stored as trades as well
+#[derive(Debug, Clone)]
+pub struct OptionExercise {
+    pub id: String,
+    pub date: DateTime<Utc>,
+    pub broker: String,
+    // Exercise | Assignment
+    pub kind: String,
+    pub option_identifier: String,
+    pub contracts: Decimal,
+    pub underlying_identifier: String,
+}
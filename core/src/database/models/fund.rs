@@ -0,0 +1,19 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use tabled::Tabled;
+
+#[derive(Debug, Clone, Tabled)]
+pub struct FundClassification {
+    pub isin: String,
+    // ReportingFund | NonReportingFund
+    pub kind: String,
+}
+
+// a redemption price of a fund, or its exchange price if there's none
+#[derive(Debug, Clone, Tabled)]
+pub struct FundPrice {
+    pub isin: String,
+    pub date: NaiveDate,
+    pub price: Decimal,
+    pub currency: String,
+}
@@ -31,3 +31,11 @@ impl FundTaxReport {
         })
     }
 }
+
+// a tax code ("Steuercode") of an OeKB report with its amount per unit for private investors
+#[derive(Debug, Clone)]
+pub struct FundTaxReportItem {
+    pub report_id: i32,
+    pub tax_code: i32,
+    pub amount: Decimal,
+}
@@ -1,4 +1,7 @@
+pub mod crypto_swap;
+pub mod derivative;
 pub mod dividend;
+pub mod fund;
 pub mod fund_report;
 pub mod fx_conversion;
 pub mod instrument;
@@ -7,4 +10,6 @@ pub mod listing_change;
 pub mod performance;
 pub mod position;
 pub mod tax_optimization;
+pub mod ticker_conversion;
 pub mod trade;
+pub mod withholding_tax;
@@ -15,6 +15,7 @@ pub struct PositionWithName {
     pub isin: String,
     pub name: String,
     pub units: Decimal,
+    pub short: bool,
 }
 
 #[derive(Debug)]
@@ -31,5 +32,7 @@ pub struct PositionWithValueAndAllocation {
     pub name: String,
     pub value: Decimal,
     pub units: Decimal,
+    // in percent of the gross exposure, negative for short positions
     pub share: Decimal,
+    pub short: bool,
 }
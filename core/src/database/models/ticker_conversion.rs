@@ -0,0 +1,38 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tabled::Tabled;
+use typeshare::typeshare;
+
+#[typeshare]
+#[derive(Debug, Clone, Tabled, Serialize)]
+pub struct TickerConversion {
+    pub ticker: String,
+    pub isin: String,
+    #[tabled(display = "display_option")]
+    pub share_class_figi: Option<String>,
+}
+
+// a ticker no ISIN could be found for, rows with it are left out of the import until a mapping
+// is added
+#[typeshare]
+#[derive(Debug, Clone, Tabled, Serialize)]
+pub struct UnresolvedTicker {
+    pub ticker: String,
+    pub broker: String,
+    // the file it was last seen in, to be imported again once the ticker is mapped
+    pub path: String,
+    pub occurrences: i32,
+    #[tabled(display = "display_option")]
+    pub share_class_figi: Option<String>,
+    #[tabled(display = "display_option")]
+    pub name: Option<String>,
+    #[tabled(skip)]
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    #[tabled(skip)]
+    pub last_lookup: Option<DateTime<Utc>>,
+}
+
+fn display_option(value: &Option<String>) -> String {
+    value.clone().unwrap_or("-".to_string())
+}
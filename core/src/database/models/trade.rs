@@ -21,6 +21,7 @@ pub struct Trade {
 }
 
 #[allow(dead_code)]
+#[derive(Debug, Clone)]
 pub struct TradeWithHash {
     pub broker: String,
     pub date: DateTime<Utc>,
@@ -37,3 +38,23 @@ pub struct TradeWithHash {
     pub withholding_tax_currency: String,
     pub hash: String,
 }
+
+impl TradeWithHash {
+    pub fn to_trade(&self) -> Trade {
+        Trade {
+            broker: self.broker.clone(),
+            date: self.date,
+            units: self.units,
+            avg_price_per_unit: self.avg_price_per_unit,
+            eur_avg_price_per_unit: self.eur_avg_price_per_unit,
+            security_type: self.security_type.clone(),
+            direction: self.direction.clone(),
+            currency: self.currency.clone(),
+            isin: self.isin.clone(),
+            date_added: self.date_added,
+            fees: self.fees,
+            withholding_tax: self.withholding_tax,
+            withholding_tax_currency: self.withholding_tax_currency.clone(),
+        }
+    }
+}
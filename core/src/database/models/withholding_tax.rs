@@ -0,0 +1,27 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tabled::Tabled;
+use typeshare::typeshare;
+
+#[typeshare]
+#[derive(Debug, Clone, Tabled, Serialize)]
+pub struct WithholdingTaxDomicile {
+    // an ISIN, or a broker for cash interest
+    pub identifier: String,
+    pub country: String,
+}
+
+// rates are fractions, e.g. 0.15 for 15%, a missing rate falls back to the built-in one
+#[typeshare]
+#[derive(Debug, Clone, Tabled, Serialize)]
+pub struct TreatyRate {
+    pub country: String,
+    #[tabled(display = "display_rate")]
+    pub dividend_rate: Option<Decimal>,
+    #[tabled(display = "display_rate")]
+    pub interest_rate: Option<Decimal>,
+}
+
+fn display_rate(value: &Option<Decimal>) -> String {
+    value.map_or("-".to_string(), |rate| rate.to_string())
+}
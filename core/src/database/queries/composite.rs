@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
+use log::{debug, warn};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use std::collections::HashSet;
 
 use crate::{
     database::{
@@ -8,7 +10,10 @@ use crate::{
         models::{instrument::Instrument, trade::Trade},
         queries::instrument::{get_instrument_by_id, update_instrument_price},
     },
-    services::{instruments::identifiers::get_changed_identifier, shared::util::hash_string},
+    services::{
+        importers::errors::ImportError, instruments::identifiers::get_changed_identifier,
+        shared::identity::trade_hash,
+    },
 };
 
 use super::listing_change::get_listing_changes;
@@ -71,7 +76,10 @@ pub async fn events_exist(filter: EventFilter) -> anyhow::Result<bool> {
 pub async fn get_used_isins() -> anyhow::Result<Vec<String>> {
     let client = db_client().await?;
 
-    let statement: String = "select distinct(isin) from trade".to_string();
+    // crypto assets, options and futures are identified by their symbol and have no ISIN
+    let statement: String =
+        "select distinct(isin) from trade where security_type not in ('Crypto', 'Option', 'Future')"
+            .to_string();
 
     let listing_changes = get_listing_changes().await?;
 
@@ -87,6 +95,38 @@ pub async fn get_used_isins() -> anyhow::Result<Vec<String>> {
     Ok(isins)
 }
 
+pub async fn get_used_crypto_identifiers() -> anyhow::Result<Vec<String>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "select distinct(isin) from trade where security_type = 'Crypto'",
+            &[],
+        )
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get::<usize, String>(0)).collect())
+}
+
+// bonds have a later cutoff for Altbestand than shares and funds
+pub async fn get_bond_isins() -> anyhow::Result<HashSet<String>> {
+    let client = db_client().await?;
+
+    let listing_changes = get_listing_changes().await?;
+
+    let rows = client
+        .query(
+            "select distinct(isin) from trade where security_type = 'Bond'",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| get_changed_identifier(&row.get::<usize, String>(0), listing_changes.clone()))
+        .collect())
+}
+
 pub async fn get_all_trades(count: Option<i32>) -> anyhow::Result<Vec<Trade>> {
     let client = db_client().await?;
 
@@ -147,31 +187,44 @@ pub async fn get_all_trades(count: Option<i32>) -> anyhow::Result<Vec<Trade>> {
     Ok(trades)
 }
 
-pub async fn add_trade_to_db(trade: Trade, id: Option<String>) -> anyhow::Result<()> {
+// if the broker doesn't share the id of the trade, the id is a combination of trade properties
+// and their occurrence in the file, see `RecordIdentity`
+pub async fn add_trade_to_db(trade: Trade, id: String) -> anyhow::Result<bool> {
     let client = db_client().await?;
 
-    let hash = if let Some(id) = id {
-        hash_string(format!("{}{}", trade.broker, id).as_str())
-    } else {
-        // if the broker doesn't share the id of the trade, hash generation falls back to a
-        // combination of trade properties
-        // removed the trade.date because revolut apparently changed their timestamp handling in
-        // the later versions of their statements
-        hash_string(
-            format!(
-                "{}{}{}{}",
-                trade.isin, trade.units, trade.direction, trade.avg_price_per_unit
-            )
-            .as_str(),
-        )
-    };
+    let hash = trade_hash(&trade.broker, &id);
 
-    client.execute(
+    let inserted_rows = client.execute(
         "INSERT INTO trade (hash, date, units, avg_price_per_unit, eur_avg_price_per_unit, security_type, direction, currency, isin, broker, date_added, fees, withholding_tax, withholding_tax_currency) values ($1, $2, $3, $4, $5, $6,$7, $8, $9, $10, $11, $12, $13, $14) ON CONFLICT(hash) DO NOTHING",
         &[&hash, &trade.date, &trade.units, &trade.avg_price_per_unit, &trade.eur_avg_price_per_unit, &trade.security_type, &trade.direction, &trade.currency, &trade.isin, &trade.broker, &Utc::now(), &trade.fees, &trade.withholding_tax, &trade.withholding_tax_currency],
         )
     .await?;
 
+    if inserted_rows == 0 {
+        let existing = client
+            .query_one(
+                "SELECT isin, date, units, direction FROM trade WHERE hash = $1",
+                &[&hash],
+            )
+            .await?;
+        let is_same_trade = existing.get::<usize, String>(0) == trade.isin
+            && existing.get::<usize, DateTime<Utc>>(1) == trade.date
+            && existing.get::<usize, Decimal>(2) == trade.units
+            && existing.get::<usize, String>(3) == trade.direction;
+        if !is_same_trade {
+            warn!(
+                "Record identity collision: trade {:?} has the same ID ({}) as an existing trade, skipping it.",
+                trade, id
+            );
+            return Err(ImportError::Duplicate {
+                record: format!("{:?}", trade),
+            }
+            .into());
+        }
+        debug!("Trade {} already imported, skipping.", id);
+        return Ok(false);
+    }
+
     println!("✅ Trade added: {:?}", trade);
 
     let existing_instrument_entry = get_instrument_by_id(&trade.isin).await?;
@@ -180,8 +233,9 @@ pub async fn add_trade_to_db(trade: Trade, id: Option<String>) -> anyhow::Result
         Some(_) => {
             let last_price_update = existing_instrument_entry.unwrap().last_price_update;
 
-            // Update instrument price if the last update is older than the trade date
-            if last_price_update < trade.date {
+            // Update instrument price if the last update is older than the trade date, assets
+            // received for free (e.g. staking rewards) don't tell the price though
+            if last_price_update < trade.date && trade.eur_avg_price_per_unit > dec!(0) {
                 let instrument = Instrument {
                     id: trade.isin.clone(),
                     last_price_update: trade.date,
@@ -205,7 +259,7 @@ pub async fn add_trade_to_db(trade: Trade, id: Option<String>) -> anyhow::Result
         }
     }
 
-    Ok(())
+    Ok(true)
 }
 
 pub async fn get_active_years() -> anyhow::Result<Vec<i32>> {
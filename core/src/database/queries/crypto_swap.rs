@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+
+use crate::database::{db_client, models::crypto_swap::CryptoSwap};
+
+pub async fn get_crypto_swaps() -> anyhow::Result<Vec<CryptoSwap>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "select id, date, broker, from_identifier, from_units, to_identifier, to_units from crypto_swap",
+            &[],
+        )
+        .await?;
+
+    let mut crypto_swaps: Vec<CryptoSwap> = vec![];
+
+    for row in rows {
+        let crypto_swap = CryptoSwap {
+            id: row.get::<usize, String>(0),
+            date: row.get::<usize, DateTime<Utc>>(1),
+            broker: row.get::<usize, String>(2),
+            from_identifier: row.get::<usize, String>(3),
+            from_units: row.get::<usize, Decimal>(4),
+            to_identifier: row.get::<usize, String>(5),
+            to_units: row.get::<usize, Decimal>(6),
+        };
+
+        crypto_swaps.push(crypto_swap);
+    }
+
+    Ok(crypto_swaps)
+}
+
+pub async fn add_crypto_swap_to_db(crypto_swap: CryptoSwap) -> anyhow::Result<bool> {
+    let client = db_client().await?;
+
+    let inserted_rows = client.execute(
+            "INSERT INTO crypto_swap (id, date, broker, from_identifier, from_units, to_identifier, to_units, date_added) values ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT(id) DO NOTHING",
+            &[&crypto_swap.id, &crypto_swap.date, &crypto_swap.broker, &crypto_swap.from_identifier, &crypto_swap.from_units, &crypto_swap.to_identifier, &crypto_swap.to_units, &Utc::now()],
+        )
+    .await?;
+
+    Ok(inserted_rows > 0)
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+use crate::database::{
+    db_client,
+    models::derivative::{Derivative, OptionExercise},
+};
+
+pub async fn get_derivatives() -> anyhow::Result<Vec<Derivative>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "select id, kind, underlying, put_call, strike, expiry, multiplier, currency from derivative",
+            &[],
+        )
+        .await?;
+
+    let mut derivatives: Vec<Derivative> = vec![];
+
+    for row in rows {
+        let derivative = Derivative {
+            id: row.get::<usize, String>(0),
+            kind: row.get::<usize, String>(1),
+            underlying: row.get::<usize, String>(2),
+            put_call: row.get::<usize, Option<String>>(3),
+            strike: row.get::<usize, Option<Decimal>>(4),
+            expiry: row.get::<usize, Option<NaiveDate>>(5),
+            multiplier: row.get::<usize, Decimal>(6),
+            currency: row.get::<usize, String>(7),
+        };
+
+        derivatives.push(derivative);
+    }
+
+    Ok(derivatives)
+}
+
+pub async fn add_derivative_to_db(derivative: Derivative) -> anyhow::Result<bool> {
+    let client = db_client().await?;
+
+    let inserted_rows = client.execute(
+            "INSERT INTO derivative (id, kind, underlying, put_call, strike, expiry, multiplier, currency, date_added) values ($1, $2, $3, $4, $5, $6, $7, $8, $9) ON CONFLICT(id) DO NOTHING",
+            &[&derivative.id, &derivative.kind, &derivative.underlying, &derivative.put_call, &derivative.strike, &derivative.expiry, &derivative.multiplier, &derivative.currency, &Utc::now()],
+        )
+    .await?;
+
+    Ok(inserted_rows > 0)
+}
+
+pub async fn get_option_exercises() -> anyhow::Result<Vec<OptionExercise>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "select id, date, broker, kind, option_identifier, contracts, underlying_identifier from option_exercise",
+            &[],
+        )
+        .await?;
+
+    let mut option_exercises: Vec<OptionExercise> = vec![];
+
+    for row in rows {
+        let option_exercise = OptionExercise {
+            id: row.get::<usize, String>(0),
+            date: row.get::<usize, DateTime<Utc>>(1),
+            broker: row.get::<usize, String>(2),
+            kind: row.get::<usize, String>(3),
+            option_identifier: row.get::<usize, String>(4),
+            contracts: row.get::<usize, Decimal>(5),
+            underlying_identifier: row.get::<usize, String>(6),
+        };
+
+        option_exercises.push(option_exercise);
+    }
+
+    Ok(option_exercises)
+}
+
+pub async fn add_option_exercise_to_db(option_exercise: OptionExercise) -> anyhow::Result<bool> {
+    let client = db_client().await?;
+
+    let inserted_rows = client.execute(
+            "INSERT INTO option_exercise (id, date, broker, kind, option_identifier, contracts, underlying_identifier, date_added) values ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT(id) DO NOTHING",
+            &[&option_exercise.id, &option_exercise.date, &option_exercise.broker, &option_exercise.kind, &option_exercise.option_identifier, &option_exercise.contracts, &option_exercise.underlying_identifier, &Utc::now()],
+        )
+    .await?;
+
+    Ok(inserted_rows > 0)
+}
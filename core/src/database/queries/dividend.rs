@@ -49,3 +49,29 @@ pub async fn add_dividend_to_db(dividend: Dividend, transaction_id: Option<&str>
     // Return true if a row was actually inserted
     Ok(result == 1)
 }
+
+/// Dividends foreign tax was withheld from
+pub async fn get_dividends_with_withholding_tax() -> anyhow::Result<Vec<Dividend>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "SELECT isin, date, amount, broker, currency, amount_eur, withholding_tax, withholding_tax_currency FROM dividend WHERE withholding_tax > 0 ORDER BY date",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Dividend {
+            isin: row.get(0),
+            date: row.get(1),
+            amount: row.get(2),
+            broker: row.get(3),
+            currency: row.get(4),
+            amount_eur: row.get(5),
+            withholding_tax: row.get(6),
+            withholding_tax_currency: row.get(7),
+        })
+        .collect())
+}
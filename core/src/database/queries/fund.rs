@@ -0,0 +1,97 @@
+use chrono::{NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+use crate::database::{
+    db_client,
+    models::fund::{FundClassification, FundPrice},
+};
+
+pub async fn get_fund_classifications() -> anyhow::Result<Vec<FundClassification>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "select isin, kind from fund_classification order by isin",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| FundClassification {
+            isin: row.get::<usize, String>(0),
+            kind: row.get::<usize, String>(1),
+        })
+        .collect())
+}
+
+// classifications found automatically never replace the ones set manually
+pub async fn add_fund_classification(isin: &str, kind: &str) -> anyhow::Result<bool> {
+    let client = db_client().await?;
+
+    let inserted_rows = client
+        .execute(
+            "INSERT INTO fund_classification (isin, kind, date_added) values ($1, $2, $3) ON CONFLICT(isin) DO NOTHING",
+            &[&isin, &kind, &Utc::now()],
+        )
+        .await?;
+
+    Ok(inserted_rows > 0)
+}
+
+pub async fn upsert_fund_classification(isin: &str, kind: &str) -> anyhow::Result<()> {
+    let client = db_client().await?;
+
+    client
+        .execute(
+            "INSERT INTO fund_classification (isin, kind, date_added) values ($1, $2, $3) ON CONFLICT(isin) DO UPDATE SET kind = EXCLUDED.kind",
+            &[&isin, &kind, &Utc::now()],
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn remove_fund_classification(isin: &str) -> anyhow::Result<()> {
+    let client = db_client().await?;
+
+    client
+        .execute("DELETE FROM fund_classification WHERE isin = $1", &[&isin])
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_fund_prices() -> anyhow::Result<Vec<FundPrice>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "select isin, date, price, currency from fund_price order by isin, date",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| FundPrice {
+            isin: row.get::<usize, String>(0),
+            date: row.get::<usize, NaiveDate>(1),
+            price: row.get::<usize, Decimal>(2),
+            currency: row.get::<usize, String>(3),
+        })
+        .collect())
+}
+
+pub async fn upsert_fund_price(fund_price: &FundPrice) -> anyhow::Result<()> {
+    let client = db_client().await?;
+
+    client
+        .execute(
+            "INSERT INTO fund_price (isin, date, price, currency, date_added) values ($1, $2, $3, $4, $5) ON CONFLICT(isin, date) DO UPDATE SET price = EXCLUDED.price, currency = EXCLUDED.currency",
+            &[&fund_price.isin, &fund_price.date, &fund_price.price, &fund_price.currency, &Utc::now()],
+        )
+        .await?;
+
+    Ok(())
+}
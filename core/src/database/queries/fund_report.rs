@@ -1,4 +1,9 @@
-use crate::database::{db_client, models::fund_report::FundTaxReport};
+use std::collections::HashSet;
+
+use crate::database::{
+    db_client,
+    models::fund_report::{FundTaxReport, FundTaxReportItem},
+};
 
 pub async fn get_oekb_fund_report_by_id(id: i32) -> anyhow::Result<FundTaxReport> {
     let client = db_client().await?;
@@ -10,13 +15,80 @@ pub async fn get_oekb_fund_report_by_id(id: i32) -> anyhow::Result<FundTaxReport
     FundTaxReport::from_row(&row)
 }
 
-pub async fn add_oekb_fund_report_to_db(report: FundTaxReport) -> anyhow::Result<()> {
-    let client = db_client().await?;
+// reports are interpreted again when they're stored again, so the raw report, the selected
+// amounts and the tax codes are replaced, returns whether the report is new
+pub async fn add_oekb_fund_report_to_db(
+    report: FundTaxReport,
+    items: &[FundTaxReportItem],
+    raw_report: &str,
+) -> anyhow::Result<bool> {
+    let mut client = db_client().await?;
+    let transaction = client.transaction().await?;
 
-    client.execute(
-        "INSERT INTO fund_report_oekb (id, date, isin, currency, dividend, dividend_aequivalent, intermittent_dividend, withheld_dividend, wac_adjustment) values ($1, $2, $3, $4, $5, $6,$7, $8, $9) ON CONFLICT(id) DO NOTHING",
-        &[&report.id, &report.date, &report.isin, &report.currency, &report.dividend, &report.dividend_aequivalent, &report.intermittent_dividends, &report.withheld_dividend, &report.wac_adjustment])
+    let row = transaction.query_one(
+        "INSERT INTO fund_report_oekb (id, date, isin, currency, dividend, dividend_aequivalent, intermittent_dividend, withheld_dividend, wac_adjustment, raw_report) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) ON CONFLICT(id) DO UPDATE SET date = EXCLUDED.date, isin = EXCLUDED.isin, currency = EXCLUDED.currency, dividend = EXCLUDED.dividend, dividend_aequivalent = EXCLUDED.dividend_aequivalent, intermittent_dividend = EXCLUDED.intermittent_dividend, withheld_dividend = EXCLUDED.withheld_dividend, wac_adjustment = EXCLUDED.wac_adjustment, raw_report = EXCLUDED.raw_report RETURNING (xmax = 0)",
+        &[&report.id, &report.date, &report.isin, &report.currency, &report.dividend, &report.dividend_aequivalent, &report.intermittent_dividends, &report.withheld_dividend, &report.wac_adjustment, &raw_report])
     .await?;
 
-    Ok(())
+    transaction
+        .execute(
+            "DELETE FROM fund_report_oekb_item WHERE report_id = $1",
+            &[&report.id],
+        )
+        .await?;
+    for item in items {
+        transaction
+            .execute(
+                "INSERT INTO fund_report_oekb_item (report_id, tax_code, amount) values ($1, $2, $3) ON CONFLICT(report_id, tax_code) DO UPDATE SET amount = EXCLUDED.amount",
+                &[&item.report_id, &item.tax_code, &item.amount],
+            )
+            .await?;
+    }
+    transaction.commit().await?;
+
+    Ok(row.get::<usize, bool>(0))
+}
+
+// reports stored before raw reports were kept are fetched again
+pub async fn get_cached_oekb_report_ids() -> anyhow::Result<HashSet<i32>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "SELECT id FROM fund_report_oekb WHERE raw_report IS NOT NULL",
+            &[],
+        )
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get::<usize, i32>(0)).collect())
+}
+
+pub async fn get_raw_oekb_fund_reports() -> anyhow::Result<Vec<String>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "SELECT raw_report FROM fund_report_oekb WHERE raw_report IS NOT NULL ORDER BY id",
+            &[],
+        )
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get::<usize, String>(0)).collect())
+}
+
+// the years each fund has an OeKB report for, by the date its income is deemed received
+pub async fn get_oekb_report_years() -> anyhow::Result<Vec<(String, i32)>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "SELECT DISTINCT isin, EXTRACT(YEAR FROM date)::INTEGER FROM fund_report_oekb",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| (row.get::<usize, String>(0), row.get::<usize, i32>(1)))
+        .collect())
 }
@@ -3,7 +3,8 @@ use crate::{
     services::shared::util::hash_string,
 };
 
-pub async fn add_fx_conversion_to_db(fx_conversion: FxConversion) -> anyhow::Result<()> {
+/// Add FX conversion to database, returns true if inserted, false if duplicate
+pub async fn add_fx_conversion_to_db(fx_conversion: FxConversion) -> anyhow::Result<bool> {
     let client = db_client().await?;
     let hash = hash_string(
         format!(
@@ -18,11 +19,11 @@ pub async fn add_fx_conversion_to_db(fx_conversion: FxConversion) -> anyhow::Res
         .as_str(),
     );
 
-    client.execute(
+    let inserted_rows = client.execute(
             "INSERT INTO fx_conversion (id, date, broker, from_amount, to_amount, from_currency, to_currency, date_added, fees) values ($1, $2, $3, $4, $5, $6, $7, $8, $9) ON CONFLICT(id) DO NOTHING",
             &[&hash, &fx_conversion.date, &fx_conversion.broker, &fx_conversion.from_amount, &fx_conversion.to_amount, &fx_conversion.from_currency, &fx_conversion.to_currency, &fx_conversion.date_added, &fx_conversion.fees],
         )
     .await?;
 
-    Ok(())
+    Ok(inserted_rows > 0)
 }
@@ -4,7 +4,10 @@ use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use tokio_postgres::Client;
 
-use crate::{database::db_client, services::market_data::fx_rates::fetch_historic_ecb_rates};
+use crate::{
+    database::db_client,
+    services::{importers::errors::ImportError, market_data::fx_rates::fetch_historic_ecb_rates},
+};
 
 pub async fn get_exchange_rate(
     mut currency_from: &str,
@@ -69,10 +72,12 @@ pub async fn get_exchange_rate(
     let rows = client.query(&stmt, &[&date]).await?;
 
     if rows.is_empty() {
-        return Err(anyhow!(format!(
-            "Exchange rate not found for the given currencies ({}{}) and date ({:?}).",
-            currency_from, currency_to, date
-        )));
+        return Err(ImportError::MissingFxRate {
+            currency_from: currency_from.to_string(),
+            currency_to: currency_to.to_string(),
+            date: *date,
+        }
+        .into());
     }
 
     let mut rate: Decimal = rows[0].get(0);
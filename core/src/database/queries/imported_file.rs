@@ -0,0 +1,24 @@
+use chrono::Utc;
+
+use crate::database::db_client;
+
+pub async fn imported_file_exists(hash: &str) -> anyhow::Result<bool> {
+    let client = db_client().await?;
+    let row = client
+        .query_opt("SELECT 1 FROM imported_file WHERE hash = $1", &[&hash])
+        .await?;
+    Ok(row.is_some())
+}
+
+pub async fn add_imported_file_to_db(hash: &str, path: &str) -> anyhow::Result<()> {
+    let client = db_client().await?;
+
+    client
+        .execute(
+            "INSERT INTO imported_file (hash, path, date_imported) values ($1, $2, $3) ON CONFLICT(hash) DO NOTHING",
+            &[&hash, &path, &Utc::now()],
+        )
+        .await?;
+
+    Ok(())
+}
@@ -1,6 +1,8 @@
+use rust_decimal::Decimal;
+
 use crate::{
     database::{db_client, models::interest::InterestPayment},
-    services::shared::util::hash_string,
+    services::{instruments::bonds::bond_isin_from_principal, shared::util::hash_string},
 };
 
 /// Check if an interest payment with the given hash already exists
@@ -56,3 +58,55 @@ pub async fn add_interest_to_db(interest_payment: InterestPayment, transaction_i
     // Return true if a row was actually inserted
     Ok(result == 1)
 }
+
+/// Sum of coupons and accrued interest in EUR per broker and bond ISIN
+pub async fn get_bond_interest_totals() -> anyhow::Result<Vec<(String, String, Decimal)>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "SELECT broker, principal, SUM(amount_eur) FROM interest GROUP BY broker, principal",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            (
+                row.get::<usize, String>(0),
+                row.get::<usize, String>(1),
+                row.get::<usize, Decimal>(2),
+            )
+        })
+        .filter_map(|(broker, principal, amount)| {
+            bond_isin_from_principal(&principal).map(|isin| (broker, isin.to_string(), amount))
+        })
+        .collect())
+}
+
+/// Interest payments foreign tax was withheld from
+pub async fn get_interest_with_withholding_tax() -> anyhow::Result<Vec<InterestPayment>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "SELECT date, amount, broker, principal, currency, amount_eur, withholding_tax, withholding_tax_currency FROM interest WHERE withholding_tax > 0 ORDER BY date",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| InterestPayment {
+            date: row.get(0),
+            amount: row.get(1),
+            broker: row.get(2),
+            principal: row.get(3),
+            currency: row.get(4),
+            amount_eur: row.get(5),
+            withholding_tax: row.get(6),
+            withholding_tax_currency: row.get(7),
+        })
+        .collect())
+}
@@ -25,3 +25,15 @@ pub async fn get_listing_changes() -> anyhow::Result<Vec<ListingChange>> {
 
     Ok(listing_changes)
 }
+
+pub async fn add_listing_change_to_db(listing_change: ListingChange) -> anyhow::Result<bool> {
+    let client = db_client().await?;
+
+    let inserted_rows = client.execute(
+            "INSERT INTO listing_change (id, ex_date, from_factor, to_factor, from_identifier, to_identifier) values ($1, $2, $3, $4, $5, $6) ON CONFLICT(id) DO NOTHING",
+            &[&listing_change.id, &listing_change.ex_date, &listing_change.from_factor, &listing_change.to_factor, &listing_change.from_identifier, &listing_change.to_identifier],
+        )
+    .await?;
+
+    Ok(inserted_rows > 0)
+}
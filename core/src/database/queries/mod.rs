@@ -1,8 +1,12 @@
 pub mod composite;
+pub mod crypto_swap;
+pub mod derivative;
 pub mod dividend;
+pub mod fund;
 pub mod fund_report;
 pub mod fx_conversion;
 pub mod fx_rate;
+pub mod imported_file;
 pub mod instrument;
 pub mod interest;
 pub mod listing_change;
@@ -12,4 +16,5 @@ pub mod stock_split;
 pub mod tax_optimization;
 pub mod ticker_conversion;
 pub mod trade;
+pub mod withholding_tax;
 
@@ -69,17 +69,18 @@ pub async fn get_positions(
             get_split_adjusted_units(&isin, units, trade_date, &mut stock_split_information);
         if direction == "Buy" {
             *entry += split_adjusted_units;
-        } else if direction == "Sell" {
+        } else if direction == "Sell" || direction == "TransferOut" {
             *entry -= split_adjusted_units;
         }
     }
 
     let mut active_units: Vec<Position> = vec![];
 
+    // short positions have negative units
     for (isin, units) in units_map {
         let position = Position { isin, units };
 
-        if position.units > dec!(0) {
+        if position.units != dec!(0) {
             active_units.push(position);
         }
     }
@@ -3,16 +3,16 @@ use rust_decimal::Decimal;
 
 use crate::{database::db_client, services::instruments::stock_splits::StockSplit};
 
-pub async fn add_stock_split_to_db(stock_split: StockSplit) -> anyhow::Result<()> {
+pub async fn add_stock_split_to_db(stock_split: StockSplit) -> anyhow::Result<bool> {
     let client = db_client().await?;
 
-    client.execute(
+    let inserted_rows = client.execute(
             "INSERT INTO stock_split (id, ex_date, from_factor, to_factor, isin, date_added) values ($1, $2, $3, $4, $5, $6) ON CONFLICT(id) DO NOTHING",
             &[&stock_split.id, &stock_split.ex_date, &stock_split.from_factor, &stock_split.to_factor, &stock_split.isin, &Utc::now()],
         )
     .await?;
 
-    Ok(())
+    Ok(inserted_rows > 0)
 }
 
 pub async fn get_stock_splits() -> anyhow::Result<Vec<StockSplit>> {
@@ -1,26 +1,43 @@
-use crate::database::db_client;
+use chrono::{DateTime, Utc};
 
-pub async fn query_isin_from_symbol(symbol: &str) -> anyhow::Result<String> {
+use crate::database::{
+    db_client,
+    models::ticker_conversion::{TickerConversion, UnresolvedTicker},
+};
+
+pub async fn query_isin_from_symbol(symbol: &str) -> anyhow::Result<Option<String>> {
     println!("Getting ISIN for symbol {}...", &symbol);
 
     let client = db_client().await?;
 
-    let statement = format!(
-        "SELECT isin from ticker_conversion where ticker = '{}'",
-        symbol
-    );
+    let result = client
+        .query_opt(
+            "SELECT isin FROM ticker_conversion WHERE ticker = $1",
+            &[&symbol],
+        )
+        .await?;
+
+    Ok(result.map(|row| row.get::<usize, String>(0)))
+}
+
+pub async fn query_isin_from_figi(share_class_figi: &str) -> anyhow::Result<Option<String>> {
+    let client = db_client().await?;
 
-    let result = client.query_one(&statement, &[]).await?;
+    let result = client
+        .query_opt(
+            "SELECT isin FROM ticker_conversion WHERE share_class_figi = $1 LIMIT 1",
+            &[&share_class_figi],
+        )
+        .await?;
 
-    Ok(result
-        .try_get::<usize, String>(0)
-        .unwrap_or("Unidentified".to_string()))
+    Ok(result.map(|row| row.get::<usize, String>(0)))
 }
 
 pub async fn query_symbol_from_isin(isin: &str) -> anyhow::Result<String> {
     let client = db_client().await?;
 
-    let statement = "SELECT ticker FROM ticker_conversion WHERE isin = $1";
+    // an ISIN can be listed under several tickers, any of them will do
+    let statement = "SELECT ticker FROM ticker_conversion WHERE isin = $1 LIMIT 1";
 
     let result = client.query_one(statement, &[&isin]).await;
 
@@ -37,13 +54,167 @@ pub async fn query_symbol_from_isin(isin: &str) -> anyhow::Result<String> {
     Ok(ticker)
 }
 
-pub async fn insert_ticker_conversion(isin: &str, symbol: &str) -> anyhow::Result<()> {
+pub async fn get_isins_without_ticker_conversion(isins: &[String]) -> anyhow::Result<Vec<String>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "SELECT isin FROM unnest($1::text[]) AS isin WHERE isin NOT IN (SELECT isin FROM ticker_conversion)",
+            &[&isins],
+        )
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get::<usize, String>(0)).collect())
+}
+
+// mappings found automatically never replace existing ones, they only complete their FIGI
+pub async fn insert_ticker_conversion(
+    isin: &str,
+    symbol: &str,
+    share_class_figi: Option<&str>,
+) -> anyhow::Result<()> {
+    let client = db_client().await?;
+
+    client.execute(
+        "INSERT INTO ticker_conversion (isin, ticker, share_class_figi) values ($1, $2, $3) ON CONFLICT(ticker) DO UPDATE SET share_class_figi = COALESCE(ticker_conversion.share_class_figi, EXCLUDED.share_class_figi) WHERE ticker_conversion.isin = EXCLUDED.isin",
+        &[&isin, &symbol, &share_class_figi])
+    .await?;
+
+    Ok(())
+}
+
+pub async fn upsert_ticker_conversion(symbol: &str, isin: &str) -> anyhow::Result<()> {
     let client = db_client().await?;
 
+    // the FIGI belongs to the previous ISIN if the mapping changes
     client.execute(
-        "INSERT INTO ticker_conversion (isin, ticker) values ($1, $2) ON CONFLICT(id) DO NOTHING",
+        "INSERT INTO ticker_conversion (isin, ticker) values ($1, $2) ON CONFLICT(ticker) DO UPDATE SET isin = EXCLUDED.isin, share_class_figi = CASE WHEN ticker_conversion.isin = EXCLUDED.isin THEN ticker_conversion.share_class_figi END",
         &[&isin, &symbol])
     .await?;
 
     Ok(())
 }
+
+pub async fn get_ticker_conversions() -> anyhow::Result<Vec<TickerConversion>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "SELECT ticker, isin, share_class_figi FROM ticker_conversion ORDER BY ticker",
+            &[],
+        )
+        .await?;
+
+    let mut ticker_conversions: Vec<TickerConversion> = vec![];
+
+    for row in rows {
+        let ticker_conversion = TickerConversion {
+            ticker: row.get::<usize, String>(0),
+            isin: row.get::<usize, String>(1),
+            share_class_figi: row.get::<usize, Option<String>>(2),
+        };
+
+        ticker_conversions.push(ticker_conversion);
+    }
+
+    Ok(ticker_conversions)
+}
+
+const UNRESOLVED_TICKER_COLUMNS: &str =
+    "ticker, broker, path, occurrences, share_class_figi, name, first_seen, last_seen, last_lookup";
+
+fn unresolved_ticker_from_row(row: &tokio_postgres::Row) -> UnresolvedTicker {
+    UnresolvedTicker {
+        ticker: row.get::<usize, String>(0),
+        broker: row.get::<usize, String>(1),
+        path: row.get::<usize, String>(2),
+        occurrences: row.get::<usize, i32>(3),
+        share_class_figi: row.get::<usize, Option<String>>(4),
+        name: row.get::<usize, Option<String>>(5),
+        first_seen: row.get::<usize, DateTime<Utc>>(6),
+        last_seen: row.get::<usize, DateTime<Utc>>(7),
+        last_lookup: row.get::<usize, Option<DateTime<Utc>>>(8),
+    }
+}
+
+pub async fn get_unresolved_tickers() -> anyhow::Result<Vec<UnresolvedTicker>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            &format!(
+                "SELECT {} FROM unresolved_ticker ORDER BY last_seen DESC",
+                UNRESOLVED_TICKER_COLUMNS
+            ),
+            &[],
+        )
+        .await?;
+
+    Ok(rows.iter().map(unresolved_ticker_from_row).collect())
+}
+
+pub async fn get_unresolved_ticker(ticker: &str) -> anyhow::Result<Option<UnresolvedTicker>> {
+    let client = db_client().await?;
+
+    let row = client
+        .query_opt(
+            &format!(
+                "SELECT {} FROM unresolved_ticker WHERE ticker = $1",
+                UNRESOLVED_TICKER_COLUMNS
+            ),
+            &[&ticker],
+        )
+        .await?;
+
+    Ok(row.as_ref().map(unresolved_ticker_from_row))
+}
+
+// every occurrence is counted, lookup results are only replaced by newer ones
+pub async fn add_unresolved_ticker(
+    ticker: &str,
+    broker: &str,
+    path: &str,
+    share_class_figi: Option<&str>,
+    name: Option<&str>,
+    last_lookup: Option<DateTime<Utc>>,
+) -> anyhow::Result<()> {
+    let client = db_client().await?;
+
+    client.execute(
+        "INSERT INTO unresolved_ticker (ticker, broker, path, occurrences, share_class_figi, name, first_seen, last_seen, last_lookup) values ($1, $2, $3, 1, $4, $5, $6, $6, $7) ON CONFLICT(ticker) DO UPDATE SET broker = EXCLUDED.broker, path = EXCLUDED.path, occurrences = unresolved_ticker.occurrences + 1, share_class_figi = COALESCE(EXCLUDED.share_class_figi, unresolved_ticker.share_class_figi), name = COALESCE(EXCLUDED.name, unresolved_ticker.name), last_seen = EXCLUDED.last_seen, last_lookup = COALESCE(EXCLUDED.last_lookup, unresolved_ticker.last_lookup)",
+        &[&ticker, &broker, &path, &share_class_figi, &name, &Utc::now(), &last_lookup])
+    .await?;
+
+    Ok(())
+}
+
+pub async fn update_unresolved_ticker_lookup(
+    ticker: &str,
+    share_class_figi: Option<&str>,
+    name: Option<&str>,
+) -> anyhow::Result<()> {
+    let client = db_client().await?;
+
+    client.execute(
+        "UPDATE unresolved_ticker SET share_class_figi = COALESCE($2, share_class_figi), name = COALESCE($3, name), last_lookup = $4 WHERE ticker = $1",
+        &[&ticker, &share_class_figi, &name, &Utc::now()])
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_unresolved_ticker(ticker: &str) -> anyhow::Result<Option<UnresolvedTicker>> {
+    let client = db_client().await?;
+
+    let row = client
+        .query_opt(
+            &format!(
+                "DELETE FROM unresolved_ticker WHERE ticker = $1 RETURNING {}",
+                UNRESOLVED_TICKER_COLUMNS
+            ),
+            &[&ticker],
+        )
+        .await?;
+
+    Ok(row.as_ref().map(unresolved_ticker_from_row))
+}
@@ -67,15 +67,90 @@ pub async fn find_similar_trade(trade: &Trade) -> anyhow::Result<Option<TradeWit
     Ok(None)
 }
 
+// transfers in are booked as buys at their original cost, the transfers out they come from are
+// netted at their average cost, so a position moved between depots is only invested once
 pub async fn get_total_invested_value() -> anyhow::Result<Decimal> {
     let client = db_client().await?;
 
     let result = client
         .query_one(
-            "select SUM(t.eur_avg_price_per_unit * t.units) from trade t where direction = 'Buy'",
+            "select SUM(case when direction = 'TransferOut' then -1 else 1 end * t.eur_avg_price_per_unit * t.units) from trade t where direction in ('Buy', 'TransferOut')",
             &[],
         )
         .await?;
 
     Ok(result.try_get::<usize, Decimal>(0).unwrap_or(dec!(0.0)))
 }
+
+pub async fn get_all_trades_with_hash() -> anyhow::Result<Vec<TradeWithHash>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            r#"
+        SELECT broker, date, isin, avg_price_per_unit, eur_avg_price_per_unit, units,
+               direction, security_type, currency, date_added, fees,
+               withholding_tax, withholding_tax_currency, hash
+        FROM trade
+        ORDER BY date ASC
+    "#,
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| TradeWithHash {
+            broker: row.get("broker"),
+            date: row.get("date"),
+            isin: row.get("isin"),
+            avg_price_per_unit: row.get("avg_price_per_unit"),
+            eur_avg_price_per_unit: row.get("eur_avg_price_per_unit"),
+            units: row.get("units"),
+            direction: row.get("direction"),
+            security_type: row.get("security_type"),
+            currency: row.get("currency"),
+            date_added: row.get("date_added"),
+            fees: row.get("fees"),
+            withholding_tax: row
+                .get::<&str, Option<Decimal>>("withholding_tax")
+                .unwrap_or(dec!(0.0)),
+            withholding_tax_currency: row
+                .get::<&str, Option<String>>("withholding_tax_currency")
+                .unwrap_or("EUR".to_string()),
+            hash: row.get("hash"),
+        })
+        .collect())
+}
+
+/// Returns false if another trade already uses the new hash
+pub async fn update_trade_hash(old_hash: &str, new_hash: &str) -> anyhow::Result<bool> {
+    let client = db_client().await?;
+
+    let exists = client
+        .query_opt("SELECT 1 FROM trade WHERE hash = $1", &[&new_hash])
+        .await?
+        .is_some();
+    if exists {
+        return Ok(false);
+    }
+
+    client
+        .execute(
+            "UPDATE trade SET hash = $1 WHERE hash = $2",
+            &[&new_hash, &old_hash],
+        )
+        .await?;
+
+    Ok(true)
+}
+
+pub async fn delete_trade(hash: &str) -> anyhow::Result<()> {
+    let client = db_client().await?;
+
+    client
+        .execute("DELETE FROM trade WHERE hash = $1", &[&hash])
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use chrono::Utc;
+use rust_decimal::Decimal;
+
+use crate::database::{
+    db_client,
+    models::withholding_tax::{TreatyRate, WithholdingTaxDomicile},
+};
+
+pub async fn get_withholding_tax_domiciles() -> anyhow::Result<Vec<WithholdingTaxDomicile>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "SELECT identifier, country FROM withholding_tax_domicile ORDER BY identifier",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| WithholdingTaxDomicile {
+            identifier: row.get(0),
+            country: row.get(1),
+        })
+        .collect())
+}
+
+pub async fn upsert_withholding_tax_domicile(
+    identifier: &str,
+    country: &str,
+) -> anyhow::Result<()> {
+    let client = db_client().await?;
+
+    client
+        .execute(
+            "INSERT INTO withholding_tax_domicile (identifier, country, date_added) VALUES ($1, $2, $3) ON CONFLICT(identifier) DO UPDATE SET country = EXCLUDED.country",
+            &[&identifier, &country, &Utc::now()],
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_treaty_rates() -> anyhow::Result<Vec<TreatyRate>> {
+    let client = db_client().await?;
+
+    let rows = client
+        .query(
+            "SELECT country, dividend_rate, interest_rate FROM withholding_tax_treaty_rate ORDER BY country",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| TreatyRate {
+            country: row.get(0),
+            dividend_rate: row.get(1),
+            interest_rate: row.get(2),
+        })
+        .collect())
+}
+
+// only the given rate is replaced, the other one is kept
+pub async fn upsert_treaty_rate(
+    country: &str,
+    dividend_rate: Option<Decimal>,
+    interest_rate: Option<Decimal>,
+) -> anyhow::Result<()> {
+    let client = db_client().await?;
+
+    client
+        .execute(
+            "INSERT INTO withholding_tax_treaty_rate (country, dividend_rate, interest_rate, date_added) VALUES ($1, $2, $3, $4) ON CONFLICT(country) DO UPDATE SET dividend_rate = COALESCE(EXCLUDED.dividend_rate, withholding_tax_treaty_rate.dividend_rate), interest_rate = COALESCE(EXCLUDED.interest_rate, withholding_tax_treaty_rate.interest_rate)",
+            &[&country, &dividend_rate, &interest_rate, &Utc::now()],
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,347 @@
+use std::{
+    collections::VecDeque,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail};
+use flate2::read::MultiGzDecoder;
+use zip::ZipArchive;
+
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+// limits of what's unpacked from a file including the archives nested in it, guarding against
+// zip bombs
+const MAX_NESTING_DEPTH: usize = 4;
+const MAX_ENTRY_SIZE: u64 = 100 * 1024 * 1024;
+const MAX_TOTAL_SIZE: u64 = 1024 * 1024 * 1024;
+const MAX_ENTRIES: usize = 10_000;
+
+pub struct ArchiveEntry {
+    pub path: String,
+    // an error if only this entry can't be unpacked, e.g. as it's too large
+    pub content: anyhow::Result<Vec<u8>>,
+}
+
+/// A file to import, either on its own or unpacked from a (possibly nested) archive.
+pub struct BundleEntry {
+    // shown in the import report, e.g. `input/export.zip/2023/statement.pdf`
+    pub path: String,
+    // the outermost archive and the path within it, e.g. `2023/statement.pdf`, or
+    // `inner.tar.gz/statement.pdf` for nested archives
+    pub archive: Option<String>,
+    pub inner_path: Option<String>,
+    pub content: anyhow::Result<Vec<u8>>,
+}
+
+enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+fn detect_archive_format(file: &[u8], file_path: &Path) -> Option<ArchiveFormat> {
+    let file_name = file_path.file_name()?.to_string_lossy().to_lowercase();
+    if file_name.ends_with(".zip") && file.starts_with(&ZIP_MAGIC) {
+        return Some(ArchiveFormat::Zip);
+    }
+    if (file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz"))
+        && file.starts_with(&GZIP_MAGIC)
+    {
+        return Some(ArchiveFormat::TarGz);
+    }
+    None
+}
+
+fn is_resource_fork(path: &str) -> bool {
+    path.starts_with("__MACOSX/") || path.contains("/__MACOSX/")
+}
+
+/// What may still be unpacked from a file, shared by all archives nested in it.
+struct UnpackLimits {
+    remaining_bytes: u64,
+    remaining_entries: usize,
+}
+
+impl UnpackLimits {
+    fn new() -> UnpackLimits {
+        UnpackLimits {
+            remaining_bytes: MAX_TOTAL_SIZE,
+            remaining_entries: MAX_ENTRIES,
+        }
+    }
+
+    // counts an entry, the rest of the archive isn't unpacked once there are too many
+    fn count_entry(&mut self) -> anyhow::Result<()> {
+        if self.remaining_entries == 0 {
+            bail!("More than {} files in the archive", MAX_ENTRIES);
+        }
+        self.remaining_entries -= 1;
+        Ok(())
+    }
+
+    // Reads an entry of the size declared by the archive, which is checked before anything is
+    // allocated. The declared size can't be trusted, reading stops once it exceeds the limits.
+    // The outer result fails once the archive exceeds the total size, the inner one if only the
+    // entry is too large.
+    fn read(
+        &mut self,
+        path: &str,
+        declared_size: u64,
+        content: impl Read,
+    ) -> anyhow::Result<anyhow::Result<Vec<u8>>> {
+        let too_large = || {
+            anyhow!(
+                "{} is larger than {} MiB, it's not unpacked",
+                path,
+                MAX_ENTRY_SIZE / 1024 / 1024
+            )
+        };
+        let exhausted = || {
+            anyhow!(
+                "More than {} MiB to unpack from the archive",
+                MAX_TOTAL_SIZE / 1024 / 1024
+            )
+        };
+        if declared_size > MAX_ENTRY_SIZE {
+            return Ok(Err(too_large()));
+        }
+        if declared_size > self.remaining_bytes {
+            return Err(exhausted());
+        }
+
+        let limit = MAX_ENTRY_SIZE.min(self.remaining_bytes);
+        let mut buffer = Vec::with_capacity(declared_size as usize);
+        if let Err(e) = content.take(limit + 1).read_to_end(&mut buffer) {
+            return Ok(Err(e.into()));
+        }
+        let size = buffer.len() as u64;
+        if size > limit {
+            if size > MAX_ENTRY_SIZE {
+                return Ok(Err(too_large()));
+            }
+            return Err(exhausted());
+        }
+        self.remaining_bytes -= size;
+        Ok(Ok(buffer))
+    }
+}
+
+/// Reads the files of a zip archive one by one. Directories and macOS resource forks are left
+/// out.
+struct ZipReader {
+    archive: ZipArchive<Cursor<Vec<u8>>>,
+    index: usize,
+}
+
+impl ZipReader {
+    fn new(file: Vec<u8>) -> anyhow::Result<ZipReader> {
+        Ok(ZipReader {
+            archive: ZipArchive::new(Cursor::new(file))?,
+            index: 0,
+        })
+    }
+
+    fn next_entry(&mut self, limits: &mut UnpackLimits) -> anyhow::Result<Option<ArchiveEntry>> {
+        while self.index < self.archive.len() {
+            let mut file = self.archive.by_index(self.index)?;
+            self.index += 1;
+            let path = file.name().to_string();
+            if file.is_dir() || is_resource_fork(&path) {
+                continue;
+            }
+
+            limits.count_entry()?;
+            let declared_size = file.size();
+            let content = limits.read(&path, declared_size, &mut file)?;
+            return Ok(Some(ArchiveEntry { path, content }));
+        }
+        Ok(None)
+    }
+}
+
+fn unpack_tar_entry(
+    entry: std::io::Result<tar::Entry<impl Read>>,
+    limits: &mut UnpackLimits,
+) -> anyhow::Result<Option<ArchiveEntry>> {
+    let mut entry = entry?;
+    // long names in GNU and pax format are resolved by the tar crate
+    let path = entry.path()?.to_string_lossy().to_string();
+    let path = path.trim_start_matches("./").to_string();
+    if !entry.header().entry_type().is_file() || is_resource_fork(&path) {
+        return Ok(None);
+    }
+
+    limits.count_entry()?;
+    let declared_size = entry.size();
+    let content = limits.read(&path, declared_size, &mut entry)?;
+    Ok(Some(ArchiveEntry { path, content }))
+}
+
+/// Reads the files of a gzip compressed tar archive. The entries of the tar crate borrow the
+/// archive, so they're all unpacked when it's opened, within the limits. Directories and links
+/// are left out.
+struct TarGzReader {
+    entries: VecDeque<ArchiveEntry>,
+    // the archive can't be read any further after the entries before it
+    error: Option<anyhow::Error>,
+}
+
+impl TarGzReader {
+    fn new(file: Vec<u8>, limits: &mut UnpackLimits) -> anyhow::Result<TarGzReader> {
+        let mut archive = tar::Archive::new(MultiGzDecoder::new(Cursor::new(file)));
+        let mut reader = TarGzReader {
+            entries: VecDeque::new(),
+            error: None,
+        };
+        for entry in archive.entries()? {
+            match unpack_tar_entry(entry, limits) {
+                Ok(Some(entry)) => reader.entries.push_back(entry),
+                Ok(None) => (),
+                Err(e) => {
+                    reader.error = Some(e);
+                    break;
+                }
+            }
+        }
+        Ok(reader)
+    }
+
+    fn next_entry(&mut self) -> anyhow::Result<Option<ArchiveEntry>> {
+        match self.entries.pop_front() {
+            Some(entry) => Ok(Some(entry)),
+            None => self.error.take().map_or(Ok(None), Err),
+        }
+    }
+}
+
+enum ArchiveReader {
+    Zip(ZipReader),
+    TarGz(TarGzReader),
+}
+
+impl ArchiveReader {
+    fn new(
+        format: ArchiveFormat,
+        file: Vec<u8>,
+        limits: &mut UnpackLimits,
+    ) -> anyhow::Result<ArchiveReader> {
+        Ok(match format {
+            ArchiveFormat::Zip => ArchiveReader::Zip(ZipReader::new(file)?),
+            ArchiveFormat::TarGz => ArchiveReader::TarGz(TarGzReader::new(file, limits)?),
+        })
+    }
+
+    fn next_entry(&mut self, limits: &mut UnpackLimits) -> anyhow::Result<Option<ArchiveEntry>> {
+        match self {
+            ArchiveReader::Zip(reader) => reader.next_entry(limits),
+            ArchiveReader::TarGz(reader) => reader.next_entry(),
+        }
+    }
+}
+
+struct OpenArchive {
+    // path of the archive within the outermost one, empty for the outermost archive itself
+    inner_path: String,
+    reader: ArchiveReader,
+}
+
+/// Iterates over the files to import from a file, which is either the file itself or, for zip
+/// and tar.gz archives, the files within them. Entries are unpacked one at a time.
+pub struct BundleEntries {
+    path: String,
+    file: Option<Vec<u8>>,
+    archives: Vec<OpenArchive>,
+    limits: UnpackLimits,
+}
+
+pub fn bundle_entries(path: &str, file: Vec<u8>) -> BundleEntries {
+    BundleEntries {
+        path: path.to_string(),
+        file: Some(file),
+        archives: vec![],
+        limits: UnpackLimits::new(),
+    }
+}
+
+impl BundleEntries {
+    fn entry(&self, inner_path: &str, content: anyhow::Result<Vec<u8>>) -> BundleEntry {
+        if inner_path.is_empty() {
+            return BundleEntry {
+                path: self.path.clone(),
+                archive: None,
+                inner_path: None,
+                content,
+            };
+        }
+        BundleEntry {
+            path: format!("{}/{}", self.path, inner_path),
+            archive: Some(self.path.clone()),
+            inner_path: Some(inner_path.to_string()),
+            content,
+        }
+    }
+
+    fn open(&mut self, inner_path: String, file: Vec<u8>) -> Option<BundleEntry> {
+        let path = if inner_path.is_empty() {
+            self.path.clone()
+        } else {
+            inner_path.clone()
+        };
+        let Some(format) = detect_archive_format(&file, Path::new(&path)) else {
+            return Some(self.entry(&inner_path, Ok(file)));
+        };
+        if self.archives.len() >= MAX_NESTING_DEPTH {
+            let error = anyhow!("Archives nested deeper than {} levels", MAX_NESTING_DEPTH);
+            return Some(self.entry(&inner_path, Err(error)));
+        }
+
+        match ArchiveReader::new(format, file, &mut self.limits) {
+            Ok(reader) => {
+                self.archives.push(OpenArchive { inner_path, reader });
+                None
+            }
+            Err(e) => Some(self.entry(&inner_path, Err(e))),
+        }
+    }
+}
+
+impl Iterator for BundleEntries {
+    type Item = BundleEntry;
+
+    fn next(&mut self) -> Option<BundleEntry> {
+        if let Some(file) = self.file.take() {
+            if let Some(entry) = self.open(String::new(), file) {
+                return Some(entry);
+            }
+        }
+
+        while let Some(archive) = self.archives.last_mut() {
+            let prefix = archive.inner_path.clone();
+            match archive.reader.next_entry(&mut self.limits) {
+                Ok(Some(archive_entry)) => {
+                    let inner_path = if prefix.is_empty() {
+                        archive_entry.path
+                    } else {
+                        format!("{}/{}", prefix, archive_entry.path)
+                    };
+                    let content = match archive_entry.content {
+                        Ok(content) => content,
+                        Err(e) => return Some(self.entry(&inner_path, Err(e))),
+                    };
+                    if let Some(entry) = self.open(inner_path, content) {
+                        return Some(entry);
+                    }
+                }
+                Ok(None) => {
+                    self.archives.pop();
+                }
+                // the rest of a corrupt archive, or one exceeding the limits, isn't read
+                Err(e) => {
+                    self.archives.pop();
+                    return Some(self.entry(&prefix, Err(e)));
+                }
+            }
+        }
+        None
+    }
+}
@@ -18,6 +18,7 @@ use crate::{
         },
     },
     services::instruments::{
+        bonds::bond_isin_from_principal,
         identifiers::get_changed_identifier,
         stock_splits::{get_split_adjusted_price_per_unit, get_split_adjusted_units},
     },
@@ -28,6 +29,8 @@ use crate::{
 pub enum TradeDirection {
     Buy,
     Sell,
+    // units moved to a depot at another broker, which books them as a buy at their original cost
+    TransferOut,
 }
 
 #[typeshare]
@@ -35,6 +38,8 @@ pub enum TradeDirection {
 pub enum EventType {
     CashInterest,
     ShareInterest,
+    // coupons and accrued interest of bonds
+    BondInterest,
     Dividend,
     Trade,
     FxConversion,
@@ -223,14 +228,20 @@ async fn process_interest_rows(rows: Vec<Row>) -> anyhow::Result<Vec<PortfolioEv
             None
         };
 
+        let principal: String = row.get(3);
+        let bond_isin = bond_isin_from_principal(&principal).map(str::to_string);
+        let event_type = if principal == "Cash" {
+            EventType::CashInterest
+        } else if bond_isin.is_some() {
+            EventType::BondInterest
+        } else {
+            EventType::ShareInterest
+        };
+
         let event = PortfolioEvent {
             date,
-            event_type: if row.get::<usize, String>(3) == "Cash" {
-                EventType::CashInterest
-            } else {
-                EventType::ShareInterest
-            },
-            identifier: None,
+            identifier: bond_isin,
+            event_type,
             name: None,
             units: amount,
             price_unit: dec!(1.00),
@@ -512,10 +523,10 @@ async fn process_trade_rows(rows: Vec<Row>) -> anyhow::Result<Vec<PortfolioEvent
             units: split_adjusted_units,
             price_unit: split_adjusted_price_per_unit,
             currency: event_currency,
-            direction: Some(if row.get::<usize, String>(5) == *"Buy" {
-                TradeDirection::Buy
-            } else {
-                TradeDirection::Sell
+            direction: Some(match row.get::<usize, &str>(5) {
+                "Buy" => TradeDirection::Buy,
+                "TransferOut" => TradeDirection::TransferOut,
+                _ => TradeDirection::Sell,
             }),
             applied_fx_rate: Some(applied_fx_rate),
             withholding_tax_percent,
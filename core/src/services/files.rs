@@ -67,5 +67,13 @@ pub fn detect_file_format(file: &[u8], file_path: &Path) -> ImportFileFormat {
         return ImportFileFormat::Csv;
     }
 
+    if file_path.extension() == Some(std::ffi::OsStr::new("xml")) {
+        return ImportFileFormat::Xml;
+    }
+
+    if file_path.extension() == Some(std::ffi::OsStr::new("json")) {
+        return ImportFileFormat::Json;
+    }
+
     ImportFileFormat::Unsupported
 }
@@ -0,0 +1,255 @@
+use chrono::prelude::*;
+use log::info;
+use logos::Logos;
+use rust_decimal_macros::dec;
+
+use crate::{
+    database::models::{dividend::Dividend, trade::Trade},
+    services::{
+        importers::{context::ImportContext, errors::ImportError},
+        market_data::fx_rates::convert_amount,
+        parsers::{does_match_exist, find_token_sequence, parse_german_decimal, parse_timestamp},
+    },
+};
+
+#[derive(Debug)]
+enum RecordType {
+    EquityTrade,
+    Dividend,
+    Vorabpauschale,
+    Unmatched,
+}
+
+fn detect_record_type(text: &str) -> RecordType {
+    if does_match_exist(r"Vorabpauschale", text) {
+        RecordType::Vorabpauschale
+    } else if does_match_exist(r"Dividendenabrechnung|Ertragsabrechnung|Ausschuttung", text) {
+        RecordType::Dividend
+    } else if does_match_exist(r"Wertpapierabrechnung", text) {
+        RecordType::EquityTrade
+    } else {
+        RecordType::Unmatched
+    }
+}
+
+/// Whether a Scalable Capital statement uses the layout issued by Baader Bank, as opposed to the
+/// older layout handled in `scalable.rs`.
+pub fn is_baader_statement(text: &str) -> bool {
+    does_match_exist(r"Baader Bank", text)
+        && does_match_exist(r"Handelstag|Zahltag|Vorabpauschale", text)
+}
+
+// labels are matched after deunicode, e.g. "Stück" becomes "Stuck"
+#[derive(Logos, Debug, PartialEq, Clone, Copy)]
+enum BaaderToken {
+    #[regex(r"Wertpapierabrechnung:? Kauf", priority = 3)]
+    Buy,
+    #[regex(r"Wertpapierabrechnung:? Verkauf", priority = 3)]
+    Sell,
+    #[token("Handelstag", priority = 3)]
+    TradeDateKeyword,
+    #[token("Handelszeit", priority = 3)]
+    TradeTimeKeyword,
+    #[regex(r"Zahltag|Valuta", priority = 3)]
+    PayDateKeyword,
+    #[token("ISIN", priority = 3)]
+    IsinKeyword,
+    #[token("STK", priority = 3)]
+    UnitsKeyword,
+    #[token("Kurswert", priority = 3)]
+    MarketValueKeyword,
+    #[regex(
+        r"Provision|Fremde Spesen|Ordergebuhr|Handelsplatzgebuhr",
+        priority = 3
+    )]
+    FeeKeyword,
+    #[token("Bruttobetrag", priority = 3)]
+    GrossKeyword,
+    #[regex(r"([A-Z]{2}-)?Quellensteuer", priority = 3)]
+    WithholdingTaxKeyword,
+    #[token("Vorgangs-Nr.", priority = 3)]
+    IdKeyword,
+    #[regex(r"[A-Z]{2}[A-Z0-9]{9}\d", priority = 2)]
+    Isin,
+    #[regex(r"\d{2}\.\d{2}\.\d{4}", priority = 2)]
+    Date,
+    #[regex(r"\d{2}:\d{2}:\d{2}(:\d{2})?", priority = 2)]
+    Time,
+    #[regex(r"-?\d+(\.\d{3})*(,\d+)?", priority = 1)]
+    Amount,
+    #[regex(r"[A-Z]{3}", priority = 1)]
+    Currency,
+    #[regex(r"[:\s]+", logos::skip)]
+    Separator,
+}
+
+type Tokens<'a> = Vec<(BaaderToken, &'a str)>;
+
+fn tokenize(text: &str) -> Tokens<'_> {
+    BaaderToken::lexer(text)
+        .spanned()
+        .filter_map(|(token, span)| token.ok().map(|token| (token, &text[span])))
+        .collect()
+}
+
+fn missing(field: &str) -> anyhow::Error {
+    ImportError::unparsable(field, "Baader Bank statement").into()
+}
+
+fn find_isin(tokens: &Tokens) -> anyhow::Result<String> {
+    find_token_sequence(tokens, BaaderToken::IsinKeyword, &[BaaderToken::Isin], 0)
+        .map(|isin| isin[0].to_string())
+        .ok_or_else(|| missing("ISIN"))
+}
+
+async fn extract_trade(
+    tokens: &Tokens<'_>,
+    broker: String,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let direction = tokens
+        .iter()
+        .find_map(|(token, _)| match token {
+            BaaderToken::Buy => Some("Buy"),
+            BaaderToken::Sell => Some("Sell"),
+            _ => None,
+        })
+        .ok_or_else(|| missing("direction"))?;
+    let date = find_token_sequence(
+        tokens,
+        BaaderToken::TradeDateKeyword,
+        &[BaaderToken::Date],
+        0,
+    )
+    .ok_or_else(|| missing("date"))?;
+    // the time is stated with hundredths of a second, e.g. 09:05:12:00
+    let time = find_token_sequence(
+        tokens,
+        BaaderToken::TradeTimeKeyword,
+        &[BaaderToken::Time],
+        0,
+    )
+    .map(|time| time[0].chars().take(8).collect::<String>())
+    .unwrap_or("00:00:00".to_string());
+    let date = parse_timestamp(&format!("{} {}", date[0], time))?;
+
+    let units = find_token_sequence(tokens, BaaderToken::UnitsKeyword, &[BaaderToken::Amount], 0)
+        .ok_or_else(|| missing("units"))?;
+    let units = parse_german_decimal(units[0])?;
+    // the price column is interleaved with the instrument name, the market value isn't
+    let market_value = find_token_sequence(
+        tokens,
+        BaaderToken::MarketValueKeyword,
+        &[BaaderToken::Currency, BaaderToken::Amount],
+        0,
+    )
+    .ok_or_else(|| missing("market value"))?;
+    if units == dec!(0) {
+        return Err(missing("units"));
+    }
+    let currency = market_value[0].to_string();
+    let price = parse_german_decimal(market_value[1])?.abs() / units;
+
+    let mut fees = dec!(0.0);
+    for (position, (token, _)) in tokens.iter().enumerate() {
+        if *token == BaaderToken::FeeKeyword {
+            if let (Some((BaaderToken::Currency, _)), Some((BaaderToken::Amount, amount))) =
+                (tokens.get(position + 1), tokens.get(position + 2))
+            {
+                fees += parse_german_decimal(amount)?.abs();
+            }
+        }
+    }
+
+    let id = find_token_sequence(tokens, BaaderToken::IdKeyword, &[BaaderToken::Amount], 0)
+        .map(|id| id[0].to_string());
+
+    let trade = Trade {
+        broker,
+        date,
+        isin: find_isin(tokens)?,
+        avg_price_per_unit: price,
+        eur_avg_price_per_unit: convert_amount(price, &date.date_naive(), &currency, "EUR").await?,
+        units,
+        direction: direction.to_string(),
+        security_type: "Equity".to_string(),
+        currency: currency.clone(),
+        date_added: Utc::now(),
+        fees,
+        withholding_tax: dec!(0.0),
+        withholding_tax_currency: currency,
+    };
+    let id = match id {
+        Some(id) => id,
+        None => ctx.identity.trade_id(&trade),
+    };
+    ctx.add_trade(trade, Some(id)).await?;
+    Ok(())
+}
+
+async fn extract_dividend(
+    tokens: &Tokens<'_>,
+    broker: String,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let date = find_token_sequence(tokens, BaaderToken::PayDateKeyword, &[BaaderToken::Date], 0)
+        .ok_or_else(|| missing("date"))?;
+    let date = parse_timestamp(&format!("{} 16:00:00", date[0]))?;
+    let gross = find_token_sequence(
+        tokens,
+        BaaderToken::GrossKeyword,
+        &[BaaderToken::Currency, BaaderToken::Amount],
+        0,
+    )
+    .ok_or_else(|| missing("amount"))?;
+    let amount = parse_german_decimal(gross[1])?;
+    let currency = gross[0].to_string();
+    let withholding_tax = find_token_sequence(
+        tokens,
+        BaaderToken::WithholdingTaxKeyword,
+        &[BaaderToken::Currency, BaaderToken::Amount],
+        1,
+    );
+
+    let dividend = Dividend {
+        isin: find_isin(tokens)?,
+        date,
+        amount,
+        broker,
+        currency: currency.clone(),
+        amount_eur: convert_amount(amount, &date.date_naive(), &currency, "EUR").await?,
+        withholding_tax: match &withholding_tax {
+            Some(withholding_tax) => parse_german_decimal(withholding_tax[1])?.abs(),
+            None => dec!(0.0),
+        },
+        withholding_tax_currency: match &withholding_tax {
+            Some(withholding_tax) => withholding_tax[0].to_string(),
+            None => currency,
+        },
+    };
+    if ctx.add_dividend(dividend.clone(), None).await? {
+        println!("💵 Dividend added: {:?}", dividend);
+    }
+    Ok(())
+}
+
+pub async fn extract_baader_record(text: &str, ctx: &mut ImportContext) -> anyhow::Result<()> {
+    // the same broker as the older layout, so positions continue across both
+    let broker = "Scalable".to_string();
+    let tokens = tokenize(text);
+
+    match detect_record_type(text) {
+        RecordType::EquityTrade => extract_trade(&tokens, broker, ctx).await?,
+        RecordType::Dividend => extract_dividend(&tokens, broker, ctx).await?,
+        RecordType::Vorabpauschale => {
+            // only applies to German tax residents, see the flatex importer
+            info!(
+                "Skipping Baader Bank Vorabpauschale notice for {}",
+                find_isin(&tokens).unwrap_or_default()
+            );
+            ctx.skip();
+        }
+        RecordType::Unmatched => ctx.skip(),
+    }
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use std::{collections::BTreeMap, io::Cursor};
+
+use chrono::{DateTime, Utc};
+use csv::{ReaderBuilder, StringRecord};
+use log::info;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+
+use crate::{
+    database::models::{crypto_swap::CryptoSwap, trade::Trade},
+    services::{
+        importers::{context::ImportContext, errors::ImportError},
+        instruments::crypto::crypto_identifier,
+        market_data::fx_rates::convert_amount,
+        shared::util::hash_string,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+struct BitpandaRecord {
+    #[serde(rename = "Transaction ID")]
+    transaction_id: String,
+    #[serde(rename = "Timestamp")]
+    timestamp: String,
+    #[serde(rename = "Transaction Type")]
+    transaction_type: String,
+    #[serde(rename = "In/Out")]
+    in_out: String,
+    #[serde(rename = "Amount Fiat")]
+    amount_fiat: String,
+    #[serde(rename = "Fiat")]
+    fiat: String,
+    #[serde(rename = "Amount Asset")]
+    amount_asset: String,
+    #[serde(rename = "Asset")]
+    asset: String,
+    #[serde(rename = "Asset class")]
+    asset_class: String,
+    #[serde(rename = "Fee", default)]
+    fee: String,
+    #[serde(rename = "Fee asset", default)]
+    fee_asset: String,
+}
+
+enum RecordType {
+    Buy,
+    Sell,
+    Reward,
+    Unmatched,
+}
+
+fn detect_record_type(record: &BitpandaRecord) -> RecordType {
+    // stocks and ETFs are derivatives without an ISIN in the export, and precious metals aren't
+    // taxed as capital income
+    if record.asset_class != "Cryptocurrency" {
+        return RecordType::Unmatched;
+    }
+    match (record.transaction_type.as_str(), record.in_out.as_str()) {
+        ("buy", _) => RecordType::Buy,
+        ("sell", _) => RecordType::Sell,
+        (transaction_type, "incoming") if transaction_type.contains("reward") => RecordType::Reward,
+        _ => RecordType::Unmatched,
+    }
+}
+
+/// Whether a CSV is a Bitpanda transaction history, which starts with a disclaimer before the
+/// header.
+pub fn is_bitpanda_csv(record: &StringRecord) -> bool {
+    record
+        .get(0)
+        .is_some_and(|column| column.contains("errors and changes are reserved"))
+        || (record.get(0) == Some("Transaction ID") && record.get(2) == Some("Transaction Type"))
+}
+
+// missing values are exported as "-"
+fn parse_amount(field: &str, value: &str) -> anyhow::Result<Decimal> {
+    if value.is_empty() || value == "-" {
+        return Ok(dec!(0.0));
+    }
+    value
+        .parse::<Decimal>()
+        .map_err(|_| ImportError::unparsable(field, value).into())
+}
+
+fn parse_date(value: &str) -> anyhow::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|date| date.with_timezone(&Utc))
+        .map_err(|_| ImportError::unparsable("Timestamp", value).into())
+}
+
+async fn to_trade(record: &BitpandaRecord, direction: &str, broker: &str) -> anyhow::Result<Trade> {
+    let date = parse_date(&record.timestamp)?;
+    let units = parse_amount("Amount Asset", &record.amount_asset)?.abs();
+    if units == dec!(0) {
+        return Err(ImportError::unparsable("Amount Asset", &record.amount_asset).into());
+    }
+    // the effective price includes Bitpanda's spread, which is part of the acquisition cost
+    let price = match direction {
+        "Reward" => dec!(0.0),
+        _ => parse_amount("Amount Fiat", &record.amount_fiat)?.abs() / units,
+    };
+    // fees are mostly paid in BEST or the traded asset itself, only fiat fees are deductible
+    let fees = if record.fee_asset == record.fiat {
+        parse_amount("Fee", &record.fee)?.abs()
+    } else {
+        dec!(0.0)
+    };
+
+    Ok(Trade {
+        broker: broker.to_string(),
+        date,
+        isin: crypto_identifier(&record.asset),
+        avg_price_per_unit: price,
+        eur_avg_price_per_unit: convert_amount(price, &date.date_naive(), &record.fiat, "EUR")
+            .await?,
+        units,
+        // rewards are acquired at no cost, so they're taxed in full once sold
+        direction: match direction {
+            "Sell" => "Sell".to_string(),
+            _ => "Buy".to_string(),
+        },
+        security_type: "Crypto".to_string(),
+        currency: record.fiat.clone(),
+        date_added: Utc::now(),
+        fees,
+        withholding_tax: dec!(0.0),
+        withholding_tax_currency: record.fiat.clone(),
+    })
+}
+
+// a swap of one crypto asset for another is exported as a sale and a purchase at the same time
+async fn add_swap(
+    sell: &BitpandaRecord,
+    buy: &BitpandaRecord,
+    broker: &str,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let sell_trade = to_trade(sell, "Sell", broker).await?;
+    let buy_trade = to_trade(buy, "Buy", broker).await?;
+
+    let crypto_swap = CryptoSwap {
+        id: hash_string(format!("{}{}", sell.transaction_id, buy.transaction_id).as_str()),
+        date: sell_trade.date,
+        broker: broker.to_string(),
+        from_identifier: sell_trade.isin.clone(),
+        from_units: sell_trade.units,
+        to_identifier: buy_trade.isin.clone(),
+        to_units: buy_trade.units,
+    };
+    if ctx.add_crypto_swap(crypto_swap).await? {
+        ctx.add_trade(sell_trade, Some(sell.transaction_id.clone()))
+            .await?;
+        ctx.add_trade(buy_trade, Some(buy.transaction_id.clone()))
+            .await?;
+    }
+    Ok(())
+}
+
+async fn extract_record(
+    record: &BitpandaRecord,
+    broker: &str,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let direction = match detect_record_type(record) {
+        RecordType::Buy => "Buy",
+        RecordType::Sell => "Sell",
+        RecordType::Reward => "Reward",
+        RecordType::Unmatched => {
+            info!(
+                "Skipping Bitpanda {} of {} ({})",
+                record.transaction_type, record.asset, record.asset_class
+            );
+            ctx.skip();
+            return Ok(());
+        }
+    };
+    let trade = to_trade(record, direction, broker).await?;
+    ctx.add_trade(trade, Some(record.transaction_id.clone()))
+        .await?;
+    Ok(())
+}
+
+pub async fn extract_bitpanda_record(
+    file_content: &[u8],
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let broker = "Bitpanda";
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(Cursor::new(file_content));
+
+    let mut headers: Option<StringRecord> = None;
+    // rows are grouped by timestamp to tell swaps apart from separate purchases and sales
+    let mut records: BTreeMap<String, Vec<(u64, BitpandaRecord)>> = BTreeMap::new();
+    for (index, result) in rdr.records().enumerate() {
+        let line = index as u64 + 1;
+        ctx.set_line(line);
+        let row = result?;
+
+        // the header follows a disclaimer and the account details
+        let Some(headers) = &headers else {
+            if row.get(0) == Some("Transaction ID") {
+                headers = Some(row);
+            }
+            continue;
+        };
+        match row.deserialize::<BitpandaRecord>(Some(headers)) {
+            Ok(record) => records
+                .entry(record.timestamp.clone())
+                .or_default()
+                .push((line, record)),
+            Err(e) => ctx.record_error(&e.into()),
+        }
+    }
+
+    for group in records.values() {
+        let crypto_trades: Vec<&BitpandaRecord> = group
+            .iter()
+            .map(|(_, record)| record)
+            .filter(|record| {
+                matches!(
+                    detect_record_type(record),
+                    RecordType::Buy | RecordType::Sell
+                )
+            })
+            .collect();
+        let swap = match crypto_trades.as_slice() {
+            [first, second] if first.transaction_type != second.transaction_type => {
+                if first.transaction_type == "sell" {
+                    Some((*first, *second))
+                } else {
+                    Some((*second, *first))
+                }
+            }
+            _ => None,
+        };
+
+        for (line, record) in group {
+            ctx.set_line(*line);
+            let result = match swap {
+                Some((sell, buy)) if record.transaction_id == sell.transaction_id => {
+                    add_swap(sell, buy, broker, ctx).await
+                }
+                Some((_, buy)) if record.transaction_id == buy.transaction_id => Ok(()),
+                _ => extract_record(record, broker, ctx).await,
+            };
+            // a faulty row is reported and doesn't keep the remaining rows from being imported
+            if let Err(e) = result {
+                ctx.record_error(&e);
+            }
+        }
+    }
+    Ok(())
+}
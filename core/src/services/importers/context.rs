@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use log::{error, warn};
+
+use crate::{
+    database::{
+        models::{
+            crypto_swap::CryptoSwap,
+            derivative::{Derivative, OptionExercise},
+            dividend::Dividend,
+            fx_conversion::FxConversion,
+            interest::InterestPayment,
+            listing_change::ListingChange,
+            tax_optimization::TaxOptimization,
+            trade::Trade,
+        },
+        queries::{
+            composite::add_trade_to_db,
+            crypto_swap::add_crypto_swap_to_db,
+            derivative::{add_derivative_to_db, add_option_exercise_to_db},
+            dividend::add_dividend_to_db,
+            fund::add_fund_classification,
+            fund_report::add_oekb_fund_report_to_db,
+            fx_conversion::add_fx_conversion_to_db,
+            interest::add_interest_to_db,
+            listing_change::add_listing_change_to_db,
+            stock_split::{add_stock_split_to_db, get_stock_splits},
+            tax_optimization::add_tax_optimization_to_db,
+            ticker_conversion::add_unresolved_ticker,
+        },
+    },
+    services::{
+        archives::BundleEntry,
+        instruments::{
+            fund_data::REPORTING_FUND,
+            stock_splits::StockSplit,
+            tickers::{resolve_ticker, TickerResolution},
+        },
+        market_data::oekb::{interpret_oekb_report, OekbRawReport},
+        shared::identity::RecordIdentity,
+    },
+};
+
+use super::{
+    errors::ImportError,
+    report::{FileImportReport, RecordKind},
+    validation::{
+        validate_crypto_swap, validate_derivative, validate_dividend, validate_fund_report,
+        validate_fx_conversion, validate_interest, validate_listing_change,
+        validate_option_exercise, validate_stock_split, validate_tax_optimization, validate_trade,
+    },
+};
+
+// asks for the password of an encrypted PDF, `None` gives up on the file
+pub type PdfPasswordPrompt = fn(&str) -> anyhow::Result<Option<String>>;
+
+/// State of importing a single file. Importers hand their records to the context instead of
+/// writing to the database directly, so every record ends up in the import report.
+pub struct ImportContext {
+    pub report: FileImportReport,
+    pub identity: RecordIdentity,
+    // whether the user can be asked to resolve ambiguous records, false when running unattended
+    pub interactive: bool,
+    // records are validated and counted, but not stored
+    pub dry_run: bool,
+    // passwords for encrypted PDFs supplied at the prompt or via the API, in addition to the
+    // configured ones
+    pub pdf_passwords: Vec<String>,
+    // asked for passwords no other one works for, if the user can be asked
+    pub pdf_password_prompt: Option<PdfPasswordPrompt>,
+    line: Option<u64>,
+    // tickers already resolved in this file, so each is looked up once
+    tickers: HashMap<String, TickerResolution>,
+    // for the price check of trades, loaded with the first trade of the file
+    stock_splits: Option<Vec<StockSplit>>,
+}
+
+impl ImportContext {
+    pub fn new(path: &str) -> ImportContext {
+        ImportContext {
+            report: FileImportReport::new(path),
+            identity: RecordIdentity::new(),
+            interactive: true,
+            dry_run: false,
+            pdf_passwords: vec![],
+            pdf_password_prompt: None,
+            line: None,
+            tickers: HashMap::new(),
+            stock_splits: None,
+        }
+    }
+
+    pub fn for_bundle_entry(entry: &BundleEntry) -> ImportContext {
+        let mut ctx = ImportContext::new(&entry.path);
+        ctx.report.archive = entry.archive.clone();
+        ctx.report.inner_path = entry.inner_path.clone();
+        ctx
+    }
+
+    pub fn set_broker(&mut self, broker: &str) {
+        self.report.broker = Some(broker.to_string());
+    }
+
+    pub fn set_line(&mut self, line: u64) {
+        self.line = Some(line);
+    }
+
+    pub fn skip(&mut self) {
+        self.report.skipped += 1;
+    }
+
+    pub fn record_error(&mut self, error: &anyhow::Error) {
+        self.report.add_error(error, self.line);
+    }
+
+    pub fn record_field_error(&mut self, field: &str, value: &str, cause: &anyhow::Error) {
+        error!(
+            "Failed to parse {} '{}' at line {:?}: {:?}",
+            field, value, self.line, cause
+        );
+        self.record_error(&ImportError::unparsable(field, value).into());
+    }
+
+    // rows of tickers no ISIN is found for are reported and queued for review instead of failing
+    // the whole file, importers skip them
+    pub async fn resolve_ticker(&mut self, ticker: &str) -> anyhow::Result<Option<String>> {
+        let resolution = match self.tickers.get(ticker) {
+            Some(resolution) => resolution.clone(),
+            None => {
+                let resolution = resolve_ticker(ticker, false).await?;
+                self.tickers.insert(ticker.to_string(), resolution.clone());
+                resolution
+            }
+        };
+        if resolution.isin.is_some() {
+            return Ok(resolution.isin);
+        }
+
+        warn!(
+            "No ISIN found for ticker {} at line {:?}",
+            ticker, self.line
+        );
+        self.record_error(
+            &ImportError::UnresolvedTicker {
+                ticker: ticker.to_string(),
+            }
+            .into(),
+        );
+        if !self.dry_run {
+            let last_lookup = resolution.looked_up.then(Utc::now);
+            add_unresolved_ticker(
+                ticker,
+                self.report.broker.as_deref().unwrap_or("Unknown"),
+                &self.report.path,
+                resolution.share_class_figi.as_deref(),
+                resolution.name.as_deref(),
+                last_lookup,
+            )
+            .await?;
+        }
+        Ok(None)
+    }
+
+    // records failing validation are rejected into the report instead of failing the whole file
+    fn accept(&mut self, validation: anyhow::Result<()>) -> anyhow::Result<bool> {
+        match validation {
+            Ok(()) => Ok(true),
+            Err(e) if matches!(e.downcast_ref(), Some(ImportError::Invalid { .. })) => {
+                warn!("{}", e);
+                self.record_error(&e);
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn count(&mut self, kind: RecordKind, inserted: bool) {
+        if inserted {
+            self.report.count_created(kind);
+        } else {
+            self.report.count_duplicate(kind);
+        }
+    }
+
+    /// Adds a trade under the broker's transaction ID, trades without one are identified by their
+    /// properties and occurrence in the file.
+    pub async fn add_trade(&mut self, trade: Trade, id: Option<String>) -> anyhow::Result<bool> {
+        if self.stock_splits.is_none() {
+            self.stock_splits = Some(get_stock_splits().await?);
+        }
+        let validation = validate_trade(&trade, self.stock_splits.as_deref().unwrap_or_default());
+        if !self.accept(validation.await)? {
+            return Ok(false);
+        }
+        if self.dry_run {
+            self.count(RecordKind::Trade, true);
+            return Ok(true);
+        }
+        let id = id.unwrap_or_else(|| self.identity.trade_id(&trade));
+        let inserted = match add_trade_to_db(trade, id).await {
+            Ok(inserted) => inserted,
+            Err(e) if matches!(e.downcast_ref(), Some(ImportError::Duplicate { .. })) => {
+                self.record_error(&e);
+                return Ok(false);
+            }
+            Err(e) => return Err(e),
+        };
+        self.count(RecordKind::Trade, inserted);
+        Ok(inserted)
+    }
+
+    pub async fn add_dividend(
+        &mut self,
+        dividend: Dividend,
+        transaction_id: Option<&str>,
+    ) -> anyhow::Result<bool> {
+        if !self.accept(validate_dividend(&dividend))? {
+            return Ok(false);
+        }
+        if self.dry_run {
+            self.count(RecordKind::Dividend, true);
+            return Ok(true);
+        }
+        let inserted = add_dividend_to_db(dividend, transaction_id).await?;
+        self.count(RecordKind::Dividend, inserted);
+        Ok(inserted)
+    }
+
+    pub async fn add_interest(
+        &mut self,
+        interest_payment: InterestPayment,
+        transaction_id: Option<&str>,
+    ) -> anyhow::Result<bool> {
+        if !self.accept(validate_interest(&interest_payment))? {
+            return Ok(false);
+        }
+        if self.dry_run {
+            self.count(RecordKind::Interest, true);
+            return Ok(true);
+        }
+        let inserted = add_interest_to_db(interest_payment, transaction_id).await?;
+        self.count(RecordKind::Interest, inserted);
+        Ok(inserted)
+    }
+
+    pub async fn add_fx_conversion(&mut self, fx_conversion: FxConversion) -> anyhow::Result<bool> {
+        if !self.accept(validate_fx_conversion(&fx_conversion))? {
+            return Ok(false);
+        }
+        if self.dry_run {
+            self.count(RecordKind::FxConversion, true);
+            return Ok(true);
+        }
+        let inserted = add_fx_conversion_to_db(fx_conversion).await?;
+        self.count(RecordKind::FxConversion, inserted);
+        Ok(inserted)
+    }
+
+    pub async fn add_tax_optimization(
+        &mut self,
+        tax_optimization: TaxOptimization,
+    ) -> anyhow::Result<bool> {
+        if !self.accept(validate_tax_optimization(&tax_optimization))? {
+            return Ok(false);
+        }
+        if self.dry_run {
+            self.count(RecordKind::TaxOptimization, true);
+            return Ok(true);
+        }
+        let inserted = add_tax_optimization_to_db(tax_optimization).await?;
+        self.count(RecordKind::TaxOptimization, inserted);
+        Ok(inserted)
+    }
+
+    pub async fn add_stock_split(&mut self, stock_split: StockSplit) -> anyhow::Result<bool> {
+        if !self.accept(validate_stock_split(&stock_split))? {
+            return Ok(false);
+        }
+        if self.dry_run {
+            self.count(RecordKind::StockSplit, true);
+            return Ok(true);
+        }
+        if let Some(stock_splits) = &mut self.stock_splits {
+            stock_splits.push(stock_split.clone());
+        }
+        let inserted = add_stock_split_to_db(stock_split).await?;
+        self.count(RecordKind::StockSplit, inserted);
+        Ok(inserted)
+    }
+
+    pub async fn add_listing_change(
+        &mut self,
+        listing_change: ListingChange,
+    ) -> anyhow::Result<bool> {
+        if !self.accept(validate_listing_change(&listing_change))? {
+            return Ok(false);
+        }
+        if self.dry_run {
+            self.count(RecordKind::ListingChange, true);
+            return Ok(true);
+        }
+        let inserted = add_listing_change_to_db(listing_change).await?;
+        self.count(RecordKind::ListingChange, inserted);
+        Ok(inserted)
+    }
+
+    pub async fn add_crypto_swap(&mut self, crypto_swap: CryptoSwap) -> anyhow::Result<bool> {
+        if !self.accept(validate_crypto_swap(&crypto_swap))? {
+            return Ok(false);
+        }
+        if self.dry_run {
+            self.count(RecordKind::CryptoSwap, true);
+            return Ok(true);
+        }
+        let inserted = add_crypto_swap_to_db(crypto_swap).await?;
+        self.count(RecordKind::CryptoSwap, inserted);
+        Ok(inserted)
+    }
+
+    pub async fn add_derivative(&mut self, derivative: Derivative) -> anyhow::Result<bool> {
+        if !self.accept(validate_derivative(&derivative))? {
+            return Ok(false);
+        }
+        if self.dry_run {
+            self.count(RecordKind::Derivative, true);
+            return Ok(true);
+        }
+        let inserted = add_derivative_to_db(derivative).await?;
+        self.count(RecordKind::Derivative, inserted);
+        Ok(inserted)
+    }
+
+    pub async fn add_option_exercise(
+        &mut self,
+        option_exercise: OptionExercise,
+    ) -> anyhow::Result<bool> {
+        if !self.accept(validate_option_exercise(&option_exercise))? {
+            return Ok(false);
+        }
+        if self.dry_run {
+            self.count(RecordKind::OptionExercise, true);
+            return Ok(true);
+        }
+        let inserted = add_option_exercise_to_db(option_exercise).await?;
+        self.count(RecordKind::OptionExercise, inserted);
+        Ok(inserted)
+    }
+
+    // a report that can't be interpreted is reported, the remaining ones of the file are imported
+    pub async fn add_fund_report(&mut self, raw_report: OekbRawReport) -> anyhow::Result<bool> {
+        let (report, items) = match interpret_oekb_report(&raw_report) {
+            Ok(interpreted) => interpreted,
+            Err(e) => {
+                warn!("Failed to interpret OeKB report: {:?}", e);
+                self.record_error(&e);
+                return Ok(false);
+            }
+        };
+        if !self.accept(validate_fund_report(&report))? {
+            return Ok(false);
+        }
+        if self.dry_run {
+            self.count(RecordKind::FundReport, true);
+            return Ok(true);
+        }
+        let isin = report.isin.clone();
+        let inserted =
+            add_oekb_fund_report_to_db(report, &items, &serde_json::to_string(&raw_report)?)
+                .await?;
+        add_fund_classification(&isin, REPORTING_FUND).await?;
+        self.count(RecordKind::FundReport, inserted);
+        Ok(inserted)
+    }
+}
@@ -0,0 +1,414 @@
+use std::{collections::BTreeMap, io::Cursor, str::FromStr};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use csv::{ReaderBuilder, StringRecord};
+use log::info;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::{
+    database::models::{
+        dividend::Dividend, fx_conversion::FxConversion, interest::InterestPayment, trade::Trade,
+    },
+    services::{
+        importers::{context::ImportContext, errors::ImportError},
+        market_data::fx_rates::convert_amount,
+        parsers::parse_timestamp,
+    },
+};
+
+const BROKER: &str = "DEGIRO";
+
+// DEGIRO localises the exports, the column order is the same in all languages though
+const QUANTITY_HEADERS: &[&str] = &["Quantity", "Aantal", "Anzahl"];
+const PRICE_HEADERS: &[&str] = &["Price", "Koers", "Kurs"];
+const VALUE_DATE_HEADERS: &[&str] = &["Value date", "Valutadatum"];
+const FEES_HEADERS: &[&str] = &["Transaction", "Transactiekosten", "Transaktionskosten"];
+const ORDER_ID_HEADERS: &[&str] = &["Order ID", "Order Id", "Order-ID"];
+
+enum CsvType {
+    Transactions,
+    Account,
+}
+
+enum AccountRecordType {
+    Dividend,
+    DividendTax,
+    Interest,
+    FxDebit,
+    FxCredit,
+    ConnectionFee,
+    Unmatched,
+}
+
+fn detect_csv_type(headers: &StringRecord) -> Option<CsvType> {
+    let header = |index: usize| headers.get(index).unwrap_or_default().trim();
+
+    if header(3) == "ISIN"
+        && QUANTITY_HEADERS.contains(&header(6))
+        && PRICE_HEADERS.contains(&header(7))
+    {
+        return Some(CsvType::Transactions);
+    }
+    if header(4) == "ISIN" && header(6) == "FX" && VALUE_DATE_HEADERS.contains(&header(2)) {
+        return Some(CsvType::Account);
+    }
+    None
+}
+
+pub fn is_degiro_csv(headers: &StringRecord) -> bool {
+    detect_csv_type(headers).is_some()
+}
+
+fn detect_account_record_type(description: &str) -> AccountRecordType {
+    match description {
+        "Dividend" | "Dividende" => AccountRecordType::Dividend,
+        "Dividend Tax" | "Dividendbelasting" | "Dividendensteuer" => AccountRecordType::DividendTax,
+        "Interest" | "Rente" | "Zinsen" | "Flatex Interest" | "Flatex Interest Income" => {
+            AccountRecordType::Interest
+        }
+        "FX Debit" | "Valuta Debitering" | "Währungswechsel (Ausbuchung)" => {
+            AccountRecordType::FxDebit
+        }
+        "FX Credit" | "Valuta Creditering" | "Währungswechsel (Einbuchung)" => {
+            AccountRecordType::FxCredit
+        }
+        _ if description.contains("Connection Fee")
+            || description.contains("Aansluitingskosten")
+            || description.contains("Börsenanbindung") =>
+        {
+            AccountRecordType::ConnectionFee
+        }
+        _ => AccountRecordType::Unmatched,
+    }
+}
+
+fn find_column_index(headers: &StringRecord, prefixes: &[&str]) -> Option<usize> {
+    headers
+        .iter()
+        .position(|header| prefixes.iter().any(|prefix| header.starts_with(prefix)))
+}
+
+fn field(record: &StringRecord, index: usize) -> &str {
+    record.get(index).unwrap_or_default().trim()
+}
+
+// the English export uses a decimal point, the Dutch and German ones a decimal comma
+fn parse_amount(name: &str, value: &str) -> anyhow::Result<Decimal> {
+    let normalized = match (value.rfind(','), value.rfind('.')) {
+        (Some(comma), Some(point)) if comma > point => value.replace('.', "").replace(',', "."),
+        (Some(_), Some(_)) => value.replace(',', ""),
+        (Some(_), None) => value.replace(',', "."),
+        _ => value.to_string(),
+    };
+    Decimal::from_str(&normalized).map_err(|_| ImportError::unparsable(name, value).into())
+}
+
+fn parse_optional_amount(name: &str, value: &str) -> anyhow::Result<Decimal> {
+    if value.is_empty() {
+        return Ok(dec!(0.0));
+    }
+    parse_amount(name, value)
+}
+
+fn parse_date(date: &str, time: &str) -> anyhow::Result<DateTime<Utc>> {
+    let timestamp = format!("{} {}:00", date, time);
+    parse_timestamp(&timestamp).map_err(|_| ImportError::unparsable("date", &timestamp).into())
+}
+
+struct TransactionColumns {
+    fees: Option<usize>,
+    auto_fx_fee: Option<usize>,
+    order_id: Option<usize>,
+}
+
+async fn extract_trade(
+    record: &StringRecord,
+    columns: &TransactionColumns,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let isin = field(record, 3);
+    let quantity = parse_amount("quantity", field(record, 6))?;
+    // e.g. non-tradable rights or cash funds without ISIN
+    if isin.is_empty() || quantity == dec!(0) {
+        ctx.skip();
+        return Ok(());
+    }
+
+    let date = parse_date(field(record, 0), field(record, 1))?;
+    let price = parse_amount("price", field(record, 7))?;
+    let currency = field(record, 8).to_string();
+
+    // DEGIRO charges transaction and AutoFX fees in the account's base currency
+    let mut fees = dec!(0.0);
+    for index in [columns.fees, columns.auto_fx_fee].into_iter().flatten() {
+        fees += parse_optional_amount("fees", field(record, index))?.abs();
+    }
+
+    let trade = Trade {
+        broker: BROKER.to_string(),
+        date,
+        isin: isin.to_string(),
+        avg_price_per_unit: price,
+        eur_avg_price_per_unit: convert_amount(price, &date.date_naive(), &currency, "EUR").await?,
+        units: quantity.abs(),
+        direction: if quantity < dec!(0) {
+            "Sell".to_string()
+        } else {
+            "Buy".to_string()
+        },
+        security_type: "Equity".to_string(),
+        currency: currency.clone(),
+        date_added: Utc::now(),
+        fees,
+        withholding_tax: dec!(0.0),
+        withholding_tax_currency: currency,
+    };
+
+    // partial fills of an order share its ID, so it's combined with the fill's characteristics
+    let trade_id = ctx.identity.trade_id(&trade);
+    let id = match columns.order_id.map(|index| field(record, index)) {
+        Some(order_id) if !order_id.is_empty() => format!("{}|{}", order_id, trade_id),
+        _ => trade_id,
+    };
+    ctx.add_trade(trade, Some(id)).await?;
+    Ok(())
+}
+
+async fn extract_transactions(
+    rdr: &mut csv::Reader<Cursor<&[u8]>>,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let headers = rdr.headers()?.clone();
+    let columns = TransactionColumns {
+        fees: find_column_index(&headers, FEES_HEADERS),
+        auto_fx_fee: find_column_index(&headers, &["AutoFX"]),
+        order_id: find_column_index(&headers, ORDER_ID_HEADERS),
+    };
+
+    for (index, result) in rdr.records().enumerate() {
+        // the header is line 1
+        ctx.set_line(index as u64 + 2);
+        let record = result?;
+        if let Err(e) = extract_trade(&record, &columns, ctx).await {
+            ctx.record_error(&e);
+        }
+    }
+    Ok(())
+}
+
+struct DividendEntry {
+    line: u64,
+    date: DateTime<Utc>,
+    amount: Decimal,
+    withholding_tax: Decimal,
+}
+
+struct FxLeg {
+    line: u64,
+    date: DateTime<Utc>,
+    amount: Decimal,
+    currency: String,
+}
+
+#[derive(Default)]
+struct FxEntry {
+    debit: Option<FxLeg>,
+    credit: Option<FxLeg>,
+}
+
+async fn add_dividend(
+    isin: String,
+    currency: String,
+    entry: DividendEntry,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    ctx.set_line(entry.line);
+    // fully reversed dividends net out to zero
+    if entry.amount <= dec!(0) {
+        ctx.skip();
+        return Ok(());
+    }
+    let dividend = Dividend {
+        isin,
+        date: entry.date,
+        amount: entry.amount,
+        broker: BROKER.to_string(),
+        currency: currency.clone(),
+        amount_eur: convert_amount(entry.amount, &entry.date.date_naive(), &currency, "EUR")
+            .await?,
+        // refunds of withheld tax are booked as positive dividend tax
+        withholding_tax: (-entry.withholding_tax).max(dec!(0)),
+        withholding_tax_currency: currency,
+    };
+    if ctx.add_dividend(dividend.clone(), None).await? {
+        println!("💵 Dividend added: {:?}", dividend);
+    }
+    Ok(())
+}
+
+async fn add_interest(
+    date: DateTime<Utc>,
+    amount: Decimal,
+    currency: String,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    // negative interest on cash balances is a cost, not income
+    if amount <= dec!(0) {
+        ctx.skip();
+        return Ok(());
+    }
+    let interest_payment = InterestPayment {
+        date,
+        amount,
+        broker: BROKER.to_string(),
+        principal: "Cash".to_string(),
+        currency: currency.clone(),
+        amount_eur: convert_amount(amount, &date.date_naive(), &currency, "EUR").await?,
+        withholding_tax: dec!(0.0),
+        withholding_tax_currency: currency,
+    };
+    if ctx.add_interest(interest_payment.clone(), None).await? {
+        println!("💵 Interest payment added: {:?}", interest_payment);
+    }
+    Ok(())
+}
+
+async fn add_fx_conversion(
+    key: &str,
+    entry: FxEntry,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let (Some(debit), Some(credit)) = (entry.debit, entry.credit) else {
+        return Err(ImportError::unparsable("currency exchange", key).into());
+    };
+    ctx.set_line(debit.line.max(credit.line));
+    let fx_conversion = FxConversion {
+        date: debit.date,
+        broker: BROKER.to_string(),
+        from_amount: debit.amount.abs(),
+        to_amount: credit.amount.abs(),
+        from_currency: debit.currency,
+        to_currency: credit.currency,
+        date_added: Utc::now(),
+        fees: dec!(0.0),
+    };
+    ctx.add_fx_conversion(fx_conversion).await?;
+    Ok(())
+}
+
+async fn extract_account(
+    rdr: &mut csv::Reader<Cursor<&[u8]>>,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    // dividends and their withheld tax as well as both legs of a currency exchange are booked as
+    // separate rows, they're collected first and combined once the whole file is read
+    let mut dividends: BTreeMap<(NaiveDate, String, String), DividendEntry> = BTreeMap::new();
+    let mut fx_conversions: BTreeMap<String, FxEntry> = BTreeMap::new();
+
+    for (index, result) in rdr.records().enumerate() {
+        let line = index as u64 + 2;
+        ctx.set_line(line);
+        let record = result?;
+
+        let record_type = detect_account_record_type(field(&record, 5));
+        if matches!(record_type, AccountRecordType::Unmatched) {
+            ctx.skip();
+            continue;
+        }
+
+        let parsed = parse_date(field(&record, 0), field(&record, 1))
+            .and_then(|date| Ok((date, parse_amount("amount", field(&record, 8))?)));
+        let (date, amount) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                ctx.record_error(&e);
+                continue;
+            }
+        };
+        let isin = field(&record, 4).to_string();
+        let currency = field(&record, 7).to_string();
+
+        match record_type {
+            AccountRecordType::Dividend | AccountRecordType::DividendTax => {
+                let entry = dividends
+                    .entry((date.date_naive(), isin, currency))
+                    .or_insert(DividendEntry {
+                        line,
+                        date,
+                        amount: dec!(0.0),
+                        withholding_tax: dec!(0.0),
+                    });
+                if matches!(record_type, AccountRecordType::Dividend) {
+                    entry.amount += amount;
+                } else {
+                    entry.withholding_tax += amount;
+                }
+            }
+            AccountRecordType::Interest => {
+                if let Err(e) = add_interest(date, amount, currency, ctx).await {
+                    ctx.record_error(&e);
+                }
+            }
+            AccountRecordType::FxDebit | AccountRecordType::FxCredit => {
+                // both legs share the timestamp and, for AutoFX, the order ID
+                let key = format!(
+                    "{} {} {}",
+                    field(&record, 0),
+                    field(&record, 1),
+                    field(&record, 11)
+                );
+                let leg = FxLeg {
+                    line,
+                    date,
+                    amount,
+                    currency,
+                };
+                let entry = fx_conversions.entry(key).or_default();
+                if matches!(record_type, AccountRecordType::FxDebit) {
+                    entry.debit = Some(leg);
+                } else {
+                    entry.credit = Some(leg);
+                }
+            }
+            AccountRecordType::ConnectionFee => {
+                // exchange connection fees can't be deducted from Austrian capital gains and
+                // there's no ledger for account fees
+                info!("Skipping DEGIRO connection fee of {} {}", amount, currency);
+                ctx.skip();
+            }
+            AccountRecordType::Unmatched => ctx.skip(),
+        }
+    }
+
+    for ((_, isin, currency), entry) in dividends {
+        if let Err(e) = add_dividend(isin, currency, entry, ctx).await {
+            ctx.record_error(&e);
+        }
+    }
+    for (key, entry) in fx_conversions {
+        if let Err(e) = add_fx_conversion(&key, entry, ctx).await {
+            ctx.record_error(&e);
+        }
+    }
+    Ok(())
+}
+
+pub async fn extract_degiro_record(
+    file_content: &[u8],
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(Cursor::new(file_content));
+
+    match detect_csv_type(rdr.headers()?) {
+        Some(CsvType::Transactions) => extract_transactions(&mut rdr, ctx).await,
+        Some(CsvType::Account) => extract_account(&mut rdr, ctx).await,
+        None => Err(ImportError::UnknownBroker {
+            path: ctx.report.path.clone(),
+        }
+        .into()),
+    }
+}
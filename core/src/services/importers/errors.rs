@@ -0,0 +1,124 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+
+#[derive(Debug)]
+pub enum ImportError {
+    UnsupportedFile {
+        path: String,
+    },
+    UnknownBroker {
+        path: String,
+    },
+    EncryptedPdf {
+        path: String,
+    },
+    UnparsableField {
+        field: String,
+        value: String,
+        line: Option<u64>,
+    },
+    Duplicate {
+        record: String,
+    },
+    Invalid {
+        record: String,
+        reason: String,
+    },
+    UnresolvedTicker {
+        ticker: String,
+    },
+    MissingFxRate {
+        currency_from: String,
+        currency_to: String,
+        date: NaiveDate,
+    },
+}
+
+impl ImportError {
+    pub fn unparsable(field: &str, value: &str) -> ImportError {
+        ImportError::UnparsableField {
+            field: field.to_string(),
+            value: value.to_string(),
+            line: None,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ImportError::UnsupportedFile { .. } => "UnsupportedFile",
+            ImportError::UnknownBroker { .. } => "UnknownBroker",
+            ImportError::EncryptedPdf { .. } => "EncryptedPdf",
+            ImportError::UnparsableField { .. } => "UnparsableField",
+            ImportError::Duplicate { .. } => "Duplicate",
+            ImportError::Invalid { .. } => "Invalid",
+            ImportError::UnresolvedTicker { .. } => "UnresolvedTicker",
+            ImportError::MissingFxRate { .. } => "MissingFxRate",
+        }
+    }
+
+    // errors due to the content of the file, importing it again yields the same errors
+    pub fn is_data_error(&self) -> bool {
+        matches!(
+            self,
+            ImportError::UnsupportedFile { .. }
+                | ImportError::UnknownBroker { .. }
+                | ImportError::UnparsableField { .. }
+                | ImportError::Duplicate { .. }
+                | ImportError::Invalid { .. }
+        )
+    }
+
+    pub fn line(&self) -> Option<u64> {
+        match self {
+            ImportError::UnparsableField { line, .. } => *line,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::UnsupportedFile { path } => write!(f, "File {} is not supported", path),
+            ImportError::UnknownBroker { path } => {
+                write!(f, "No broker could be detected for {}", path)
+            }
+            ImportError::EncryptedPdf { path } => write!(
+                f,
+                "{} is an encrypted PDF and none of the passwords could decrypt it",
+                path
+            ),
+            ImportError::UnparsableField { field, value, line } => match line {
+                Some(line) => write!(
+                    f,
+                    "Couldn't parse {} from '{}' on line {}",
+                    field, value, line
+                ),
+                None => write!(f, "Couldn't parse {} from '{}'", field, value),
+            },
+            ImportError::Duplicate { record } => {
+                write!(f, "A different record with the same ID exists: {}", record)
+            }
+            ImportError::Invalid { record, reason } => {
+                write!(f, "Record rejected, {}: {}", reason, record)
+            }
+            ImportError::UnresolvedTicker { ticker } => write!(
+                f,
+                "No known ISIN found for ticker {}, it's queued for review (see `doughbox tickers`)",
+                ticker
+            ),
+            ImportError::MissingFxRate {
+                currency_from,
+                currency_to,
+                date,
+            } => write!(
+                f,
+                "Exchange rate not found for the given currencies ({}{}) and date ({:?}).",
+                currency_from, currency_to, date
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
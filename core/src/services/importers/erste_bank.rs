@@ -2,11 +2,11 @@ use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
 use crate::{
-    database::{
-        models::{dividend::Dividend, trade::Trade},
-        queries::{composite::add_trade_to_db, dividend::add_dividend_to_db},
+    database::models::{dividend::Dividend, trade::Trade},
+    services::{
+        importers::context::ImportContext,
+        parsers::{does_match_exist, parse_timestamp, return_first_match},
     },
-    services::parsers::{does_match_exist, parse_timestamp, return_first_match},
 };
 use chrono::prelude::*;
 
@@ -28,7 +28,7 @@ fn detect_record_type(text: &str) -> anyhow::Result<RecordType> {
     Ok(RecordType::Unmatched)
 }
 
-pub async fn extract_erste_bank_record(text: &str) -> anyhow::Result<()> {
+pub async fn extract_erste_bank_record(text: &str, ctx: &mut ImportContext) -> anyhow::Result<()> {
     let broker = "Erste Bank".to_string();
     let record_type = detect_record_type(text)?;
 
@@ -118,7 +118,7 @@ pub async fn extract_erste_bank_record(text: &str) -> anyhow::Result<()> {
                 withholding_tax: dec!(0.0),
                 withholding_tax_currency: "EUR".to_string(),
             };
-            add_trade_to_db(trade, Some(id)).await?;
+            ctx.add_trade(trade, Some(id)).await?;
         }
         RecordType::Dividend => {
             let date_match = return_first_match(r", am \d{2}\.\d{2}\.\d{4}", text)?
@@ -157,11 +157,11 @@ pub async fn extract_erste_bank_record(text: &str) -> anyhow::Result<()> {
                 withholding_tax,
                 withholding_tax_currency: "EUR".to_string(),
             };
-            if add_dividend_to_db(dividend.clone(), None).await? {
+            if ctx.add_dividend(dividend.clone(), None).await? {
                 println!("💵 Dividend added: {:?}", dividend);
             }
         }
-        RecordType::Unmatched => (),
+        RecordType::Unmatched => ctx.skip(),
     }
     Ok(())
 }
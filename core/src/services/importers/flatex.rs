@@ -0,0 +1,323 @@
+use chrono::prelude::*;
+use log::info;
+use logos::Logos;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::{
+    database::models::{dividend::Dividend, trade::Trade},
+    services::{
+        importers::{context::ImportContext, errors::ImportError},
+        market_data::fx_rates::convert_amount,
+        parsers::{does_match_exist, find_token_sequence, parse_german_decimal, parse_timestamp},
+    },
+};
+
+#[derive(Debug)]
+enum RecordType {
+    EquityTrade,
+    Dividend,
+    Vorabpauschale,
+    Unmatched,
+}
+
+// a Vorabpauschale notice also mentions the fund's distributions, hence it's checked first
+fn detect_record_type(text: &str) -> RecordType {
+    if does_match_exist(r"Vorabpauschale", text) {
+        RecordType::Vorabpauschale
+    } else if does_match_exist(r"Dividendengutschrift|Ertragsmitteilung", text) {
+        RecordType::Dividend
+    } else if does_match_exist(r"Sammelabrechnung|Wertpapierabrechnung", text) {
+        RecordType::EquityTrade
+    } else {
+        RecordType::Unmatched
+    }
+}
+
+// labels are matched after deunicode, e.g. "Ausgeführt" becomes "Ausgefuhrt"
+#[derive(Logos, Debug, PartialEq, Clone, Copy)]
+enum FlatexToken {
+    #[regex(r"Nr\.\d+/\d+", priority = 3)]
+    OrderNumber,
+    #[token("Kauf", priority = 3)]
+    Buy,
+    #[token("Verkauf", priority = 3)]
+    Sell,
+    #[regex(r"\([A-Z]{2}[A-Z0-9]{9}\d/", priority = 3)]
+    Isin,
+    #[token("Ausgefuhrt", priority = 3)]
+    UnitsKeyword,
+    #[token("Kurs", priority = 3)]
+    PriceKeyword,
+    #[token("Kurswert", priority = 3)]
+    MarketValueKeyword,
+    #[regex(
+        r"Provision|Fremde Spesen|Eigene Spesen|Handelsplatzentgelt",
+        priority = 3
+    )]
+    FeeKeyword,
+    #[token("Schlusstag", priority = 3)]
+    TradeDateKeyword,
+    #[regex(r"Zahltag|Valuta", priority = 3)]
+    PayDateKeyword,
+    #[regex(r"Bruttodividende pro Stuck|Ausschuttung pro Stuck", priority = 3)]
+    PerUnitKeyword,
+    #[regex(r"Bruttodividende|Bruttoausschuttung|Ausschuttung", priority = 3)]
+    GrossKeyword,
+    #[token("Quellensteuer", priority = 3)]
+    WithholdingTaxKeyword,
+    #[regex(r"\d{2}\.\d{2}\.\d{4}", priority = 2)]
+    Date,
+    #[regex(r"\d{2}:\d{2}:\d{2}", priority = 2)]
+    Time,
+    #[regex(r"-?\d+(\.\d{3})*(,\d+)?", priority = 1)]
+    Amount,
+    #[regex(r"[A-Z]{3}", priority = 1)]
+    Currency,
+    #[regex(r"[:\s]+", logos::skip)]
+    Separator,
+}
+
+type Tokens<'a> = Vec<(FlatexToken, &'a str)>;
+
+fn tokenize(text: &str) -> Tokens<'_> {
+    FlatexToken::lexer(text)
+        .spanned()
+        .filter_map(|(token, span)| token.ok().map(|token| (token, &text[span])))
+        .collect()
+}
+
+fn isin_from_token(slice: &str) -> String {
+    slice
+        .trim_start_matches('(')
+        .trim_end_matches('/')
+        .to_string()
+}
+
+struct TradeDetails {
+    id: String,
+    direction: String,
+    isin: String,
+    date: DateTime<Utc>,
+    units: Decimal,
+    price: Decimal,
+    currency: String,
+    fees: Decimal,
+}
+
+// a collective statement (Sammelabrechnung) contains one block per order, each starting with the
+// order number
+fn split_orders<'a>(tokens: &'a [(FlatexToken, &'a str)]) -> Vec<&'a [(FlatexToken, &'a str)]> {
+    let starts: Vec<usize> = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, (token, _))| *token == FlatexToken::OrderNumber)
+        .map(|(index, _)| index)
+        .collect();
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(position, start)| {
+            let end = starts.get(position + 1).copied().unwrap_or(tokens.len());
+            &tokens[*start..end]
+        })
+        .collect()
+}
+
+fn extract_trade_details(order: &[(FlatexToken, &str)]) -> anyhow::Result<TradeDetails> {
+    let id = order[0].1.trim_start_matches("Nr.").to_string();
+    let missing = |field: &str| -> anyhow::Error { ImportError::unparsable(field, &id).into() };
+
+    let direction = order
+        .iter()
+        .find_map(|(token, _)| match token {
+            FlatexToken::Buy => Some("Buy"),
+            FlatexToken::Sell => Some("Sell"),
+            _ => None,
+        })
+        .ok_or_else(|| missing("direction"))?;
+    let isin = order
+        .iter()
+        .find(|(token, _)| *token == FlatexToken::Isin)
+        .map(|(_, slice)| isin_from_token(slice))
+        .ok_or_else(|| missing("ISIN"))?;
+
+    let date = match find_token_sequence(
+        order,
+        FlatexToken::TradeDateKeyword,
+        &[FlatexToken::Date, FlatexToken::Time],
+        2,
+    ) {
+        Some(date) => parse_timestamp(&format!("{} {}", date[0], date[1]))?,
+        None => {
+            let date = find_token_sequence(
+                order,
+                FlatexToken::TradeDateKeyword,
+                &[FlatexToken::Date],
+                2,
+            )
+            .ok_or_else(|| missing("date"))?;
+            parse_timestamp(&format!("{} 00:00:00", date[0]))?
+        }
+    };
+
+    let units = find_token_sequence(order, FlatexToken::UnitsKeyword, &[FlatexToken::Amount], 1)
+        .ok_or_else(|| missing("units"))?;
+    let price = find_token_sequence(
+        order,
+        FlatexToken::PriceKeyword,
+        &[FlatexToken::Amount, FlatexToken::Currency],
+        1,
+    )
+    .ok_or_else(|| missing("price"))?;
+
+    // fees are charged in EUR
+    let mut fees = dec!(0.0);
+    for (position, (token, _)) in order.iter().enumerate() {
+        if *token == FlatexToken::FeeKeyword {
+            if let Some((FlatexToken::Amount, amount)) = order.get(position + 1) {
+                fees += parse_german_decimal(amount)?.abs();
+            }
+        }
+    }
+
+    Ok(TradeDetails {
+        id,
+        direction: direction.to_string(),
+        isin,
+        date,
+        units: parse_german_decimal(units[0])?,
+        price: parse_german_decimal(price[0])?,
+        currency: price[1].to_string(),
+        fees,
+    })
+}
+
+struct DividendDetails {
+    isin: String,
+    date: DateTime<Utc>,
+    amount: Decimal,
+    currency: String,
+    withholding_tax: Decimal,
+    withholding_tax_currency: String,
+}
+
+fn extract_dividend_details(tokens: &[(FlatexToken, &str)]) -> anyhow::Result<DividendDetails> {
+    let missing = |field: &str| -> anyhow::Error {
+        ImportError::unparsable(field, "flatex dividend statement").into()
+    };
+
+    let isin = tokens
+        .iter()
+        .find(|(token, _)| *token == FlatexToken::Isin)
+        .map(|(_, slice)| isin_from_token(slice))
+        .ok_or_else(|| missing("ISIN"))?;
+    let date = find_token_sequence(tokens, FlatexToken::PayDateKeyword, &[FlatexToken::Date], 1)
+        .ok_or_else(|| missing("date"))?;
+    // the gross amount is stated in the original currency first and converted to EUR afterwards
+    let gross = find_token_sequence(
+        tokens,
+        FlatexToken::GrossKeyword,
+        &[FlatexToken::Amount, FlatexToken::Currency],
+        1,
+    )
+    .ok_or_else(|| missing("amount"))?;
+    let withholding_tax = find_token_sequence(
+        tokens,
+        FlatexToken::WithholdingTaxKeyword,
+        &[FlatexToken::Amount, FlatexToken::Currency],
+        2,
+    );
+
+    Ok(DividendDetails {
+        isin,
+        date: parse_timestamp(&format!("{} 16:00:00", date[0]))?,
+        amount: parse_german_decimal(gross[0])?,
+        currency: gross[1].to_string(),
+        withholding_tax: match &withholding_tax {
+            Some(withholding_tax) => parse_german_decimal(withholding_tax[0])?.abs(),
+            None => dec!(0.0),
+        },
+        withholding_tax_currency: match &withholding_tax {
+            Some(withholding_tax) => withholding_tax[1].to_string(),
+            None => gross[1].to_string(),
+        },
+    })
+}
+
+pub async fn extract_flatex_record(text: &str, ctx: &mut ImportContext) -> anyhow::Result<()> {
+    let broker = "flatex".to_string();
+    let tokens = tokenize(text);
+
+    match detect_record_type(text) {
+        RecordType::EquityTrade => {
+            for order in split_orders(&tokens) {
+                let details = match extract_trade_details(order) {
+                    Ok(details) => details,
+                    Err(e) => {
+                        ctx.record_error(&e);
+                        continue;
+                    }
+                };
+                let trade = Trade {
+                    broker: broker.clone(),
+                    date: details.date,
+                    isin: details.isin,
+                    avg_price_per_unit: details.price,
+                    eur_avg_price_per_unit: convert_amount(
+                        details.price,
+                        &details.date.date_naive(),
+                        &details.currency,
+                        "EUR",
+                    )
+                    .await?,
+                    units: details.units,
+                    direction: details.direction,
+                    security_type: "Equity".to_string(),
+                    currency: details.currency.clone(),
+                    date_added: Utc::now(),
+                    fees: details.fees,
+                    withholding_tax: dec!(0.0),
+                    withholding_tax_currency: details.currency,
+                };
+                ctx.add_trade(trade, Some(details.id)).await?;
+            }
+        }
+        RecordType::Dividend => {
+            let details = extract_dividend_details(&tokens)?;
+            let dividend = Dividend {
+                isin: details.isin,
+                date: details.date,
+                amount: details.amount,
+                broker,
+                currency: details.currency.clone(),
+                amount_eur: convert_amount(
+                    details.amount,
+                    &details.date.date_naive(),
+                    &details.currency,
+                    "EUR",
+                )
+                .await?,
+                withholding_tax: details.withholding_tax,
+                withholding_tax_currency: details.withholding_tax_currency,
+            };
+            if ctx.add_dividend(dividend.clone(), None).await? {
+                println!("💵 Dividend added: {:?}", dividend);
+            }
+        }
+        RecordType::Vorabpauschale => {
+            // the German Vorabpauschale isn't relevant for Austrian taxes, deemed distributions
+            // are taken from the OeKB fund reports instead
+            let isin = tokens
+                .iter()
+                .find(|(token, _)| *token == FlatexToken::Isin)
+                .map(|(_, slice)| isin_from_token(slice))
+                .unwrap_or_default();
+            info!("Skipping flatex Vorabpauschale notice for {}", isin);
+            ctx.skip();
+        }
+        RecordType::Unmatched => ctx.skip(),
+    }
+    Ok(())
+}
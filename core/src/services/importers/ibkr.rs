@@ -3,205 +3,314 @@ use rust_decimal_macros::dec;
 use std::io::Cursor;
 
 use crate::{
-    database::{
-        models::{fx_conversion::FxConversion, trade::Trade},
-        queries::{composite::add_trade_to_db, fx_conversion::add_fx_conversion_to_db},
+    database::models::{
+        derivative::{Derivative, OptionExercise},
+        fx_conversion::FxConversion,
+        trade::Trade,
+    },
+    services::{
+        importers::{context::ImportContext, errors::ImportError, validation::is_valid_isin},
+        instruments::derivatives::{future_identifier, option_identifier},
+        parsers::parse_timestamp,
+        shared::util::hash_string,
     },
-    services::parsers::parse_timestamp,
 };
 use chrono::prelude::*;
 use itertools::Itertools;
 use rust_decimal::Decimal;
 use serde::Deserialize;
 
+// fields are matched by the column names of the Flex Query CSV, the aliases are the attribute
+// names of a `<Trade>` element in the Flex Query XML
 #[derive(Debug, Deserialize)]
 pub struct IBRKRecord {
-    _client_account_id: String,
-    _account_alias: String,
-    _model: String,
+    #[serde(rename = "CurrencyPrimary", alias = "currency")]
     currency_primary: String,
+    #[serde(rename = "FXRateToBase", alias = "fxRateToBase")]
     fx_rate_to_base: String,
+    #[serde(rename = "AssetClass", alias = "assetCategory")]
     asset_class: String,
-    _symbol: String,
-    _description: String,
-    _conid: String,
-    _security_id: String,
-    _security_id_type: String,
-    _cusip: String,
+    #[serde(rename = "Symbol", alias = "symbol")]
+    symbol: String,
+    #[serde(rename = "ISIN", alias = "isin", default)]
     isin: String,
-    _listing_exchange: String,
-    _underlying_conid: String,
-    _underlying_symbol: String,
-    _underlying_security_id: String,
-    _underlying_listing_exchange: String,
-    _issuer: String,
-    _multiplier: String,
-    _strike: String,
-    _expiry: String,
+    #[serde(rename = "TradeID", alias = "tradeID")]
     trade_id: String,
-    _put_v_call: String,
-    _related_trade_id: String,
-    _principal_adjust_factor: String,
-    _report_date: String,
+    #[serde(rename = "DateTime", alias = "dateTime")]
     date_time: String,
-    _trade_date: String,
-    _settle_date_target: String,
-    _transaction_type: String,
-    _exchange: String,
+    #[serde(rename = "Quantity", alias = "quantity")]
     quantity: String,
+    #[serde(rename = "TradePrice", alias = "tradePrice")]
     trade_price: String,
-    _trade_money: String,
+    #[serde(rename = "Proceeds", alias = "proceeds")]
     proceeds: String,
+    #[serde(rename = "Taxes", alias = "taxes", default)]
     taxes: String,
+    #[serde(rename = "IBCommission", alias = "ibCommission", default)]
     ib_commission: String,
-    _ib_commission_currency: String,
-    _net_cash: String,
-    _close_price: String,
-    _open_close_indicator: String,
-    _notes_codes: String,
-    _cost_basis: String,
-    _fifo_pnl_realized: String,
-    _fx_pnl: String,
-    _mtm_pnl: String,
-    _orig_trade_price: String,
-    _orig_trade_date: String,
-    _orig_trade_id: String,
-    _orig_order_id: String,
-    _clearing_firm_id: String,
+    #[serde(rename = "Buy/Sell", alias = "buySell")]
     buy_sell: String,
-    _transaction_id: String,
-    _ib_order_id: String,
-    _related_transaction_id: String,
-    _ib_exec_id: String,
-    _brokerage_order_id: String,
-    _order_reference: String,
-    _volatility_order_link: String,
-    _exch_order_id: String,
-    _ext_exec_id: String,
-    _order_time: String,
-    _open_date_time: String,
-    _holding_period_date_time: String,
-    _when_realized: String,
-    _when_reopened: String,
-    _level_of_detail: String,
-    _change_in_price: String,
-    _change_in_quantity: String,
-    _order_type: String,
-    _trader_id: String,
-    _is_api_order: String,
-    _accrued_interest: String,
-    _serial_number: String,
-    _delivery_type: String,
-    _commodity_type: String,
-    _fineness: String,
-    _weight: String,
+    // contract details of options and futures, older queries may not include them
+    #[serde(rename = "Multiplier", alias = "multiplier", default)]
+    multiplier: String,
+    #[serde(rename = "Strike", alias = "strike", default)]
+    strike: String,
+    #[serde(rename = "Expiry", alias = "expiry", default)]
+    expiry: String,
+    #[serde(rename = "Put/Call", alias = "putCall", default)]
+    put_call: String,
+    #[serde(rename = "UnderlyingSymbol", alias = "underlyingSymbol", default)]
+    underlying_symbol: String,
+    #[serde(
+        rename = "UnderlyingSecurityID",
+        alias = "underlyingSecurityID",
+        default
+    )]
+    underlying_security_id: String,
+    // e.g. "A;C" for the closing leg of an assigned option
+    #[serde(rename = "Notes/Codes", alias = "notes", default)]
+    notes_codes: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum DerivativeKind {
+    Option,
+    Future,
 }
 
 enum RecordType {
     EquityTrade,
+    DerivativeTrade(DerivativeKind),
     FxConversion,
     Unmatched,
 }
 
 fn detect_record_type(record: &IBRKRecord) -> RecordType {
-    if record.asset_class == "CASH" {
-        return RecordType::FxConversion;
+    match record.asset_class.as_str() {
+        "CASH" => RecordType::FxConversion,
+        "STK" => RecordType::EquityTrade,
+        // options on futures are treated like any other option
+        "OPT" | "FOP" => RecordType::DerivativeTrade(DerivativeKind::Option),
+        "FUT" => RecordType::DerivativeTrade(DerivativeKind::Future),
+        _ => RecordType::Unmatched,
+    }
+}
+
+// empty amounts, e.g. no taxes on a trade, are exported as an empty column
+fn parse_amount(value: &str) -> anyhow::Result<Decimal> {
+    if value.is_empty() {
+        return Ok(dec!(0.0));
     }
-    if record.asset_class == "STK" {
-        return RecordType::EquityTrade;
+    Ok(value.parse::<Decimal>()?)
+}
+
+fn parse_expiry(value: &str) -> anyhow::Result<Option<NaiveDate>> {
+    if value.is_empty() {
+        return Ok(None);
     }
-    RecordType::Unmatched
+    let expiry = NaiveDate::parse_from_str(value, "%Y%m%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d"))
+        .map_err(|_| ImportError::unparsable("expiry", value))?;
+    Ok(Some(expiry))
 }
 
-pub async fn extract_ibkr_record(file_content: &[u8]) -> anyhow::Result<()> {
+fn has_note_code(record: &IBRKRecord, code: &str) -> bool {
+    record
+        .notes_codes
+        .split(';')
+        .any(|note| note.trim() == code)
+}
+
+// options and futures are held in contracts, so the price of a unit is the quoted price times the
+// multiplier
+async fn add_ibkr_derivative_trade(
+    record: IBRKRecord,
+    kind: DerivativeKind,
+    broker: &str,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let multiplier = if record.multiplier.is_empty() {
+        dec!(1)
+    } else {
+        record.multiplier.parse::<Decimal>()?
+    };
+    let identifier = match kind {
+        DerivativeKind::Option => option_identifier(&record.symbol),
+        DerivativeKind::Future => future_identifier(&record.symbol),
+    };
+    let underlying_isin = is_valid_isin(&record.underlying_security_id)
+        .then(|| record.underlying_security_id.clone());
+
+    let derivative = Derivative {
+        id: identifier.clone(),
+        kind: format!("{:?}", kind),
+        underlying: underlying_isin
+            .clone()
+            .unwrap_or(record.underlying_symbol.clone()),
+        put_call: match record.put_call.as_str() {
+            "C" => Some("Call".to_string()),
+            "P" => Some("Put".to_string()),
+            _ => None,
+        },
+        strike: if record.strike.is_empty() {
+            None
+        } else {
+            Some(record.strike.parse::<Decimal>()?)
+        },
+        expiry: parse_expiry(&record.expiry)?,
+        multiplier,
+        currency: record.currency_primary.clone(),
+    };
+    ctx.add_derivative(derivative).await?;
+
+    let date = parse_timestamp(&record.date_time)?;
+    let fx_rate_to_base = record.fx_rate_to_base.parse::<Decimal>()?;
+    let price_per_contract = record.trade_price.parse::<Decimal>()? * multiplier;
+    let contracts = record.quantity.parse::<Decimal>()?.abs();
+
+    let trade = Trade {
+        broker: broker.to_string(),
+        date,
+        isin: identifier.clone(),
+        avg_price_per_unit: price_per_contract,
+        eur_avg_price_per_unit: price_per_contract * fx_rate_to_base,
+        units: contracts,
+        direction: if record.buy_sell.contains("BUY") {
+            "Buy".to_string()
+        } else {
+            "Sell".to_string()
+        },
+        security_type: format!("{:?}", kind),
+        currency: record.currency_primary.to_string(),
+        date_added: Utc::now(),
+        fees: parse_amount(&record.ib_commission)? * dec!(-1.0) * fx_rate_to_base,
+        withholding_tax: parse_amount(&record.taxes)?,
+        withholding_tax_currency: record.currency_primary.clone(),
+    };
+    ctx.add_trade(trade, Some(record.trade_id.clone())).await?;
+
+    // the option leg of an exercise or assignment is closed at a price of 0, the underlying is
+    // traded at the strike in a separate trade on the same day
+    let exercise_kind = if kind != DerivativeKind::Option {
+        None
+    } else if has_note_code(&record, "A") {
+        Some("Assignment")
+    } else if has_note_code(&record, "Ex") {
+        Some("Exercise")
+    } else {
+        None
+    };
+    if let (Some(exercise_kind), Some(underlying_isin)) = (exercise_kind, underlying_isin) {
+        let option_exercise = OptionExercise {
+            id: hash_string(format!("{}{}", broker, record.trade_id).as_str()),
+            date,
+            broker: broker.to_string(),
+            kind: exercise_kind.to_string(),
+            option_identifier: identifier,
+            contracts,
+            underlying_identifier: underlying_isin,
+        };
+        ctx.add_option_exercise(option_exercise).await?;
+    }
+    Ok(())
+}
+
+/// Adds a single trade of a Flex Query, shared by the CSV and the XML importer.
+pub async fn add_ibkr_trade(record: IBRKRecord, ctx: &mut ImportContext) -> anyhow::Result<()> {
     let broker = "Interactive Brokers".to_string();
 
+    match detect_record_type(&record) {
+        RecordType::EquityTrade => {
+            let trade = Trade {
+                broker: broker.clone(),
+                date: parse_timestamp(&record.date_time)?,
+                isin: record.isin,
+                avg_price_per_unit: record.trade_price.parse::<Decimal>()?,
+                eur_avg_price_per_unit: record.trade_price.parse::<Decimal>()?
+                    * record.fx_rate_to_base.parse::<Decimal>()?,
+                // IBKR assigns negative units on sell events
+                units: record.quantity.parse::<Decimal>()?.abs(),
+                direction: if record.buy_sell.contains("BUY") {
+                    "Buy".to_string()
+                } else {
+                    "Sell".to_string()
+                },
+                security_type: "Equity".to_string(),
+                currency: record.currency_primary.to_string(),
+                date_added: Utc::now(),
+                fees: parse_amount(&record.ib_commission)?
+                    * dec!(-1.0)
+                    * record.fx_rate_to_base.parse::<Decimal>()?,
+                withholding_tax: parse_amount(&record.taxes)?,
+                withholding_tax_currency: record.currency_primary,
+            };
+            ctx.add_trade(trade, Some(record.trade_id)).await?;
+        }
+        RecordType::DerivativeTrade(kind) => {
+            add_ibkr_derivative_trade(record, kind, &broker, ctx).await?;
+        }
+        RecordType::FxConversion => {
+            let currencies = record.symbol.split('.').collect_vec();
+
+            let fx_conversion = FxConversion {
+                date: parse_timestamp(&record.date_time)?,
+                broker: broker.clone(),
+                from_amount: if record.buy_sell.contains("SELL") {
+                    record.quantity.parse::<Decimal>()? * dec!(-1.0)
+                } else {
+                    record.proceeds.parse::<Decimal>()? * dec!(-1.0)
+                },
+                to_amount: if record.buy_sell.contains("SELL") {
+                    record.proceeds.parse::<Decimal>()?
+                } else {
+                    record.quantity.parse::<Decimal>()?
+                },
+                from_currency: if record.buy_sell.contains("SELL") {
+                    currencies[0].to_string()
+                } else {
+                    currencies[1].to_string()
+                },
+                to_currency: if record.buy_sell.contains("SELL") {
+                    currencies[1].to_string()
+                } else {
+                    currencies[0].to_string()
+                },
+                date_added: Utc::now(),
+                fees: parse_amount(&record.ib_commission)
+                    .unwrap_or(dec!(-0.0))
+                    .abs()
+                    * record.fx_rate_to_base.parse::<Decimal>()?,
+            };
+            ctx.add_fx_conversion(fx_conversion).await?;
+        }
+        RecordType::Unmatched => ctx.skip(),
+    }
+    Ok(())
+}
+
+pub async fn extract_ibkr_record(
+    file_content: &[u8],
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
     let cursor = Cursor::new(file_content);
-    let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(cursor);
-
-    for result in rdr.deserialize() {
-        let record: IBRKRecord = result?;
-
-        let record_type = detect_record_type(&record);
-
-        match record_type {
-            RecordType::EquityTrade => {
-                let trade = Trade {
-                    broker: broker.clone(),
-                    date: parse_timestamp(&record.date_time)?,
-                    isin: record.isin,
-                    avg_price_per_unit: record.trade_price.parse::<Decimal>()?,
-                    eur_avg_price_per_unit: record.trade_price.parse::<Decimal>()?
-                        * record.fx_rate_to_base.parse::<Decimal>()?,
-                    // IBKR assigns negative units on sell events
-                    units: record.quantity.parse::<Decimal>()?.abs(),
-                    direction: if record.buy_sell.contains("BUY") {
-                        "Buy".to_string()
-                    } else {
-                        "Sell".to_string()
-                    },
-                    security_type: "Equity".to_string(),
-                    currency: record.currency_primary.to_string(),
-                    date_added: Utc::now(),
-                    fees: record.ib_commission.parse::<Decimal>()?
-                        * dec!(-1.0)
-                        * record.fx_rate_to_base.parse::<Decimal>()?,
-                    withholding_tax: record.taxes.parse::<Decimal>()?,
-                    withholding_tax_currency: record.currency_primary,
-                };
-                add_trade_to_db(trade, Some(record.trade_id)).await?;
-            }
-            RecordType::FxConversion => {
-                let currencies = record._symbol.split('.').collect_vec();
-
-                let fx_conversion = FxConversion {
-                    date: parse_timestamp(&record.date_time)?,
-                    broker: broker.clone(),
-                    from_amount: if record.buy_sell.contains("SELL") {
-                        record.quantity.parse::<Decimal>()? * dec!(-1.0)
-                    } else {
-                        record.proceeds.parse::<Decimal>()? * dec!(-1.0)
-                    },
-                    to_amount: if record.buy_sell.contains("SELL") {
-                        record.proceeds.parse::<Decimal>()?
-                    } else {
-                        record.quantity.parse::<Decimal>()?
-                    },
-                    from_currency: if record.buy_sell.contains("SELL") {
-                        currencies[0].to_string()
-                    } else {
-                        currencies[1].to_string()
-                    },
-                    to_currency: if record.buy_sell.contains("SELL") {
-                        currencies[1].to_string()
-                    } else {
-                        currencies[0].to_string()
-                    },
-                    date_added: Utc::now(),
-                    fees: if record
-                        .ib_commission
-                        .parse::<Decimal>()
-                        .unwrap_or(dec!(-0.0))
-                        < dec!(0.0)
-                    {
-                        record
-                            .ib_commission
-                            .parse::<Decimal>()
-                            .unwrap_or(dec!(-0.0))
-                            * dec!(-1.0)
-                            * record.fx_rate_to_base.parse::<Decimal>()?
-                    } else {
-                        record
-                            .ib_commission
-                            .parse::<Decimal>()
-                            .unwrap_or(dec!(-0.0))
-                            * record.fx_rate_to_base.parse::<Decimal>()?
-                    },
-                };
-                add_fx_conversion_to_db(fx_conversion).await?;
-            }
-            RecordType::Unmatched => continue,
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(cursor);
+    let headers = rdr.headers()?.clone();
+
+    for (index, result) in rdr.records().enumerate() {
+        // the header is line 1
+        ctx.set_line(index as u64 + 2);
+        let row = result?;
+
+        // a query over multiple accounts repeats the header for every account
+        if row.get(0) == Some("ClientAccountID") {
+            continue;
         }
+
+        let record: IBRKRecord = row.deserialize(Some(&headers))?;
+        add_ibkr_trade(record, ctx).await?;
     }
     Ok(())
 }
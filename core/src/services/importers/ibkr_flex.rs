@@ -0,0 +1,435 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::prelude::*;
+use fancy_regex::Regex;
+use log::{info, warn};
+use roxmltree::{Document, Node};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{
+    de::{value::MapDeserializer, DeserializeOwned},
+    Deserialize,
+};
+
+use crate::{
+    database::{
+        models::{
+            dividend::Dividend, instrument::Instrument, interest::InterestPayment,
+            listing_change::ListingChange,
+        },
+        queries::instrument::{get_instrument_by_id, update_instrument_price},
+    },
+    services::{
+        importers::{
+            context::ImportContext,
+            errors::ImportError,
+            ibkr::{add_ibkr_trade, IBRKRecord},
+        },
+        instruments::{
+            derivatives::{future_identifier, option_identifier},
+            stock_splits::StockSplit,
+        },
+        parsers::parse_timestamp,
+        shared::util::hash_string,
+    },
+};
+
+/// Whether an XML file is an Interactive Brokers Flex Query.
+pub fn is_flex_query(text: &str) -> bool {
+    text.contains("<FlexQueryResponse")
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CashTransaction {
+    currency: String,
+    fx_rate_to_base: String,
+    #[serde(default)]
+    isin: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    date_time: String,
+    #[serde(default)]
+    settle_date: String,
+    amount: String,
+    #[serde(rename = "type")]
+    transaction_type: String,
+    #[serde(default)]
+    level_of_detail: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CorporateAction {
+    #[serde(default)]
+    isin: String,
+    description: String,
+    #[serde(default)]
+    date_time: String,
+    #[serde(default)]
+    report_date: String,
+    #[serde(default)]
+    quantity: String,
+    #[serde(rename = "type")]
+    action_type: String,
+    #[serde(default)]
+    level_of_detail: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenPosition {
+    fx_rate_to_base: String,
+    asset_category: String,
+    #[serde(default)]
+    isin: String,
+    #[serde(default)]
+    symbol: String,
+    #[serde(default)]
+    multiplier: String,
+    #[serde(default)]
+    description: String,
+    report_date: String,
+    mark_price: String,
+    #[serde(default)]
+    level_of_detail: String,
+}
+
+// attributes are looked up by name, so the column order chosen in the query doesn't matter
+fn deserialize_attributes<T: DeserializeOwned>(node: &Node) -> anyhow::Result<T> {
+    let attributes: HashMap<&str, &str> = node
+        .attributes()
+        .map(|attribute| (attribute.name(), attribute.value()))
+        .collect();
+    let deserializer = MapDeserializer::<_, serde::de::value::Error>::new(attributes.into_iter());
+    Ok(T::deserialize(deserializer)?)
+}
+
+// depending on the date format chosen in the query, the time is omitted
+fn parse_flex_date(value: &str) -> anyhow::Result<DateTime<Utc>> {
+    if value.contains(';') {
+        return parse_timestamp(value);
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d"))
+        .map_err(|_| ImportError::unparsable("date", value))?;
+    Ok(date.and_hms_opt(16, 0, 0).unwrap().and_utc())
+}
+
+fn parse_decimal(field: &str, value: &str) -> anyhow::Result<Decimal> {
+    value
+        .parse::<Decimal>()
+        .map_err(|_| ImportError::unparsable(field, value).into())
+}
+
+// the level of detail is only exported if the query includes several levels
+fn is_level(level_of_detail: &str, level: &str) -> bool {
+    level_of_detail.is_empty() || level_of_detail == level
+}
+
+#[derive(Debug, Default)]
+struct CashEntry {
+    date: Option<DateTime<Utc>>,
+    fx_rate_to_base: Decimal,
+    amount: Decimal,
+    withholding_tax: Decimal,
+}
+
+// (account, ISIN, date, currency), the ISIN being empty for interest
+type CashKey = (String, String, NaiveDate, String);
+
+#[derive(Debug, Default)]
+struct CashEntries {
+    dividends: BTreeMap<CashKey, CashEntry>,
+    interest: BTreeMap<CashKey, CashEntry>,
+    // withholding tax is booked separately and joined with its payment afterwards
+    withholding_taxes: Vec<(CashKey, Decimal)>,
+}
+
+impl CashEntries {
+    fn add(&mut self, account: &str, transaction: CashTransaction) -> anyhow::Result<bool> {
+        let date = parse_flex_date(if transaction.date_time.is_empty() {
+            &transaction.settle_date
+        } else {
+            &transaction.date_time
+        })?;
+        let amount = parse_decimal("amount", &transaction.amount)?;
+        let key = (
+            account.to_string(),
+            transaction.isin.clone(),
+            date.date_naive(),
+            transaction.currency.clone(),
+        );
+
+        let entries = match transaction.transaction_type.as_str() {
+            // payments in lieu are made while shares are lent out and are taxed like dividends
+            "Dividends" | "Payment In Lieu Of Dividends" => &mut self.dividends,
+            "Broker Interest Received" => &mut self.interest,
+            "Withholding Tax" => {
+                self.withholding_taxes.push((key, -amount));
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        };
+        let entry = entries.entry(key).or_default();
+        entry.date = Some(date);
+        entry.fx_rate_to_base = parse_decimal("fxRateToBase", &transaction.fx_rate_to_base)?;
+        // reversals and corrections are booked as separate entries on the same day
+        entry.amount += amount;
+        Ok(true)
+    }
+
+    fn join_withholding_taxes(&mut self) {
+        for (key, withholding_tax) in self.withholding_taxes.drain(..) {
+            let entries = if key.1.is_empty() {
+                &mut self.interest
+            } else {
+                &mut self.dividends
+            };
+            match entries.get_mut(&key) {
+                Some(entry) => entry.withholding_tax += withholding_tax,
+                None => warn!(
+                    "Withholding tax of {} {} on {} doesn't belong to a payment in this statement, skipping.",
+                    withholding_tax, key.3, key.2
+                ),
+            }
+        }
+    }
+}
+
+fn split_factors(description: &str) -> Option<(Decimal, Decimal)> {
+    let regex = Regex::new(r"SPLIT (\d+(?:\.\d+)?) FOR (\d+(?:\.\d+)?)").unwrap();
+    let captures = regex.captures(description).ok()??;
+    let to_factor = captures.get(1)?.as_str().parse::<Decimal>().ok()?;
+    let from_factor = captures.get(2)?.as_str().parse::<Decimal>().ok()?;
+    Some((from_factor, to_factor))
+}
+
+// the description starts with the instrument as it was before the action, e.g.
+// "AAPL(US0378331005) SPLIT 4 FOR 1 (AAPL, APPLE INC, US0378331005)"
+fn previous_isin(description: &str) -> Option<String> {
+    let regex = Regex::new(r"\(([A-Z]{2}[A-Z0-9]{9}\d)\)").unwrap();
+    let captures = regex.captures(description).ok()??;
+    Some(captures.get(1)?.as_str().to_string())
+}
+
+async fn extract_corporate_action(
+    action: CorporateAction,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let ex_date = parse_flex_date(if action.date_time.is_empty() {
+        &action.report_date
+    } else {
+        &action.date_time
+    })?;
+    // an action changing the ISIN is booked as removing the old and adding the new instrument,
+    // only the latter is needed
+    if action.quantity.starts_with('-') || action.isin.is_empty() {
+        ctx.skip();
+        return Ok(());
+    }
+    let to_isin = action.isin.clone();
+    let from_isin = previous_isin(&action.description).unwrap_or(to_isin.clone());
+
+    let (from_factor, to_factor) = match action.action_type.as_str() {
+        "FS" | "RS" => split_factors(&action.description)
+            .ok_or_else(|| ImportError::unparsable("description", &action.description))?,
+        "IC" => (dec!(1), dec!(1)),
+        _ => {
+            info!(
+                "Skipping corporate action of type {}: {}",
+                action.action_type, action.description
+            );
+            ctx.skip();
+            return Ok(());
+        }
+    };
+
+    if from_isin == to_isin {
+        // same id as splits fetched from polygon, see the manual importer
+        let stock_split = StockSplit {
+            id: hash_string(format!("{}{}", to_isin, ex_date.date_naive()).as_str()),
+            ex_date,
+            from_factor,
+            to_factor,
+            isin: to_isin,
+        };
+        ctx.add_stock_split(stock_split).await?;
+    } else {
+        let listing_change = ListingChange {
+            id: hash_string(format!("{}{}{}", from_isin, to_isin, ex_date.date_naive()).as_str()),
+            ex_date,
+            from_factor,
+            to_factor,
+            from_identifier: from_isin,
+            to_identifier: to_isin,
+        };
+        ctx.add_listing_change(listing_change).await?;
+    }
+    Ok(())
+}
+
+// the mark price of an open position updates the instrument price, the same way a trade does
+async fn extract_open_position(
+    position: OpenPosition,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    // options and futures are priced per contract, like their trades
+    let (id, multiplier) = match position.asset_category.as_str() {
+        "STK" if !position.isin.is_empty() => (position.isin.clone(), dec!(1)),
+        "OPT" | "FOP" | "FUT" if !position.symbol.is_empty() => (
+            if position.asset_category == "FUT" {
+                future_identifier(&position.symbol)
+            } else {
+                option_identifier(&position.symbol)
+            },
+            if position.multiplier.is_empty() {
+                dec!(1)
+            } else {
+                parse_decimal("multiplier", &position.multiplier)?
+            },
+        ),
+        _ => {
+            ctx.skip();
+            return Ok(());
+        }
+    };
+    let report_date = parse_flex_date(&position.report_date)?;
+    let instrument = Instrument {
+        id: id.clone(),
+        last_price_update: report_date,
+        price: parse_decimal("markPrice", &position.mark_price)?
+            * multiplier
+            * parse_decimal("fxRateToBase", &position.fx_rate_to_base)?,
+        name: if position.description.is_empty() {
+            id.clone()
+        } else {
+            position.description
+        },
+    };
+    if ctx.dry_run {
+        return Ok(());
+    }
+
+    match get_instrument_by_id(&id).await? {
+        Some(existing) if existing.last_price_update >= report_date => {}
+        _ => update_instrument_price(instrument).await?,
+    }
+    Ok(())
+}
+
+async fn add_cash_entries(
+    mut cash_entries: CashEntries,
+    broker: &str,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    cash_entries.join_withholding_taxes();
+
+    for ((_, isin, _, currency), entry) in cash_entries.dividends {
+        let Some(date) = entry.date else { continue };
+        let dividend = Dividend {
+            isin,
+            date,
+            amount: entry.amount,
+            broker: broker.to_string(),
+            currency: currency.clone(),
+            amount_eur: entry.amount * entry.fx_rate_to_base,
+            withholding_tax: entry.withholding_tax,
+            withholding_tax_currency: currency,
+        };
+        if ctx.add_dividend(dividend.clone(), None).await? {
+            println!("💵 Dividend added: {:?}", dividend);
+        }
+    }
+
+    for ((_, _, _, currency), entry) in cash_entries.interest {
+        let Some(date) = entry.date else { continue };
+        // a fully reversed payment nets to zero
+        if entry.amount <= dec!(0.0) {
+            ctx.skip();
+            continue;
+        }
+        let interest_payment = InterestPayment {
+            date,
+            amount: entry.amount,
+            broker: broker.to_string(),
+            principal: "Cash".to_string(),
+            currency: currency.clone(),
+            amount_eur: entry.amount * entry.fx_rate_to_base,
+            withholding_tax: entry.withholding_tax,
+            withholding_tax_currency: currency,
+        };
+        ctx.add_interest(interest_payment, None).await?;
+    }
+    Ok(())
+}
+
+pub async fn extract_ibkr_flex_record(text: &str, ctx: &mut ImportContext) -> anyhow::Result<()> {
+    let broker = "Interactive Brokers";
+    let document = Document::parse(text)?;
+    let mut cash_entries = CashEntries::default();
+
+    // a query over several accounts contains one statement per account
+    for statement in document
+        .descendants()
+        .filter(|node| node.has_tag_name("FlexStatement"))
+    {
+        let account = statement.attribute("accountId").unwrap_or_default();
+        info!("Importing Flex statement of account {}.", account);
+
+        for node in statement.descendants().filter(|node| node.is_element()) {
+            ctx.set_line(document.text_pos_at(node.range().start).row as u64);
+
+            let result = match node.tag_name().name() {
+                // the trades section also contains orders and closed lots if selected
+                "Trade"
+                    if is_level(
+                        node.attribute("levelOfDetail").unwrap_or_default(),
+                        "EXECUTION",
+                    ) =>
+                {
+                    match deserialize_attributes::<IBRKRecord>(&node) {
+                        Ok(record) => add_ibkr_trade(record, ctx).await,
+                        Err(e) => Err(e),
+                    }
+                }
+                "CashTransaction" => {
+                    deserialize_attributes::<CashTransaction>(&node).and_then(|transaction| {
+                        if !is_level(&transaction.level_of_detail, "DETAIL") {
+                            return Ok(());
+                        }
+                        let description = transaction.description.clone();
+                        if !cash_entries.add(account, transaction)? {
+                            info!("Skipping cash transaction: {}", description);
+                            ctx.skip();
+                        }
+                        Ok(())
+                    })
+                }
+                "CorporateAction" => match deserialize_attributes::<CorporateAction>(&node) {
+                    Ok(action) if is_level(&action.level_of_detail, "DETAIL") => {
+                        extract_corporate_action(action, ctx).await
+                    }
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e),
+                },
+                "OpenPosition" => match deserialize_attributes::<OpenPosition>(&node) {
+                    Ok(position) if is_level(&position.level_of_detail, "SUMMARY") => {
+                        extract_open_position(position, ctx).await
+                    }
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e),
+                },
+                _ => Ok(()),
+            };
+
+            // a faulty element is reported and doesn't keep the remaining ones from being imported
+            if let Err(e) = result {
+                ctx.record_error(&e);
+            }
+        }
+    }
+
+    add_cash_entries(cash_entries, broker, ctx).await
+}
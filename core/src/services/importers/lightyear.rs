@@ -4,17 +4,13 @@ use rust_decimal_macros::dec;
 use std::{collections::HashMap, io::Cursor};
 
 use crate::{
-    database::{
-        models::{
-            dividend::Dividend, fx_conversion::FxConversion, interest::InterestPayment,
-            trade::Trade,
-        },
-        queries::{
-            composite::add_trade_to_db, dividend::add_dividend_to_db,
-            fx_conversion::add_fx_conversion_to_db, interest::add_interest_to_db,
-        },
+    database::models::{
+        dividend::Dividend, fx_conversion::FxConversion, interest::InterestPayment, trade::Trade,
+    },
+    services::{
+        importers::context::ImportContext, market_data::fx_rates::convert_amount,
+        parsers::parse_timestamp,
     },
-    services::{market_data::fx_rates::convert_amount, parsers::parse_timestamp},
 };
 use chrono::prelude::*;
 use serde::Deserialize;
@@ -65,7 +61,10 @@ fn detect_record_type(record: &LightyearRecord) -> RecordType {
     RecordType::Unmatched
 }
 
-pub async fn extract_lightyear_record(file_content: &[u8]) -> anyhow::Result<()> {
+pub async fn extract_lightyear_record(
+    file_content: &[u8],
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
     let broker = "Lightyear".to_string();
 
     let cursor = Cursor::new(file_content);
@@ -76,7 +75,8 @@ pub async fn extract_lightyear_record(file_content: &[u8]) -> anyhow::Result<()>
 
     let mut records_by_timestamp: HashMap<String, Vec<LightyearRecord>> = HashMap::new();
 
-    for result in rdr.deserialize() {
+    for (index, result) in rdr.deserialize().enumerate() {
+        ctx.set_line(index as u64 + 1);
         let record: LightyearRecord = result?;
         let timestamp = record.time.clone();
         records_by_timestamp
@@ -85,7 +85,8 @@ pub async fn extract_lightyear_record(file_content: &[u8]) -> anyhow::Result<()>
             .push(record);
     }
 
-    for result in rdr2.deserialize() {
+    for (index, result) in rdr2.deserialize().enumerate() {
+        ctx.set_line(index as u64 + 1);
         let record: LightyearRecord = result?;
 
         let record_type = detect_record_type(&record);
@@ -114,7 +115,7 @@ pub async fn extract_lightyear_record(file_content: &[u8]) -> anyhow::Result<()>
                                     date_added: Utc::now(),
                                     fees: record.fee.parse::<Decimal>().unwrap_or(dec!(0.0)),
                                 };
-                                add_fx_conversion_to_db(fx_conversion).await?;
+                                ctx.add_fx_conversion(fx_conversion).await?;
                             }
                         }
                     };
@@ -141,7 +142,7 @@ pub async fn extract_lightyear_record(file_content: &[u8]) -> anyhow::Result<()>
                     withholding_tax: record.tax_amount.parse::<Decimal>().unwrap_or(dec!(0.0)),
                     withholding_tax_currency: record.currency.to_string(),
                 };
-                if add_dividend_to_db(dividend.clone(), None).await? {
+                if ctx.add_dividend(dividend.clone(), None).await? {
                     println!("💵 Dividend added: {:?}", dividend);
                 }
             }
@@ -179,7 +180,7 @@ pub async fn extract_lightyear_record(file_content: &[u8]) -> anyhow::Result<()>
                     withholding_tax: record.tax_amount.parse::<Decimal>().unwrap_or(dec!(0.0)),
                     withholding_tax_currency: record.currency.to_string(),
                 };
-                add_trade_to_db(trade, Some(record.reference)).await?;
+                ctx.add_trade(trade, Some(record.reference)).await?;
             }
             RecordType::CashInterest => {
                 let interest_payment = InterestPayment {
@@ -202,12 +203,12 @@ pub async fn extract_lightyear_record(file_content: &[u8]) -> anyhow::Result<()>
                     withholding_tax: record.tax_amount.parse::<Decimal>().unwrap_or(dec!(0.0)),
                     withholding_tax_currency: record.currency.to_string(),
                 };
-                if add_interest_to_db(interest_payment.clone(), None).await? {
+                if ctx.add_interest(interest_payment.clone(), None).await? {
                     println!("💵 Interest payment added: {:?}", interest_payment);
                 }
             }
-            RecordType::CashTransfer => continue,
-            RecordType::Unmatched => continue,
+            RecordType::CashTransfer => ctx.skip(),
+            RecordType::Unmatched => ctx.skip(),
         }
     }
     Ok(())
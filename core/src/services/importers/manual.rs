@@ -1,87 +1,353 @@
 use std::io::Cursor;
 
-use chrono::Utc;
-use csv::ReaderBuilder;
+use chrono::{DateTime, Utc};
+use csv::{ReaderBuilder, StringRecord};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::Deserialize;
 
 use crate::{
-    database::{models::trade::Trade, queries::composite::add_trade_to_db},
-    services::{market_data::fx_rates::convert_amount, parsers::parse_timestamp},
+    database::models::{
+        dividend::Dividend, fx_conversion::FxConversion, interest::InterestPayment,
+        listing_change::ListingChange, tax_optimization::TaxOptimization, trade::Trade,
+    },
+    services::{
+        importers::{context::ImportContext, errors::ImportError},
+        instruments::stock_splits::StockSplit,
+        market_data::fx_rates::convert_amount,
+        parsers::parse_timestamp,
+        shared::util::hash_string,
+    },
 };
 
+// columns are matched by name, so their order doesn't matter and columns not needed for an
+// action can be left empty or omitted
 #[derive(Debug, Deserialize)]
 pub struct ManualRecord {
     date: String,
+    action: String,
+    #[serde(default)]
     isin: String,
+    #[serde(default)]
     broker: String,
-    action: String,
+    #[serde(default)]
     direction: String,
+    #[serde(default)]
     units: String,
+    #[serde(default)]
     avg_price_per_unit: String,
+    #[serde(default)]
     currency: String,
+    #[serde(default)]
     security_type: String,
+    #[serde(default)]
     fees: String,
+    #[serde(default)]
     withholding_tax: String,
+    #[serde(default)]
     withholding_tax_currency: String,
+    #[serde(default)]
+    amount: String,
+    #[serde(default)]
+    to_amount: String,
+    #[serde(default)]
+    to_currency: String,
+    #[serde(default)]
+    from_factor: String,
+    #[serde(default)]
+    to_factor: String,
+    #[serde(default)]
+    to_isin: String,
+    #[serde(default)]
+    tax_type: String,
+    #[serde(default)]
+    description: String,
 }
 
 enum RecordType {
-    EquityTrade,
-    Unmatched,
+    Trade,
+    Dividend,
+    Interest,
+    FxConversion,
+    TaxOptimization,
+    StockSplit,
+    ListingChange,
+    Transfer,
+}
+
+fn detect_record_type(record: &ManualRecord) -> Option<RecordType> {
+    match record.action.as_str() {
+        "Trade" => Some(RecordType::Trade),
+        "Dividend" => Some(RecordType::Dividend),
+        "Interest" => Some(RecordType::Interest),
+        "FxConversion" => Some(RecordType::FxConversion),
+        "TaxOptimization" => Some(RecordType::TaxOptimization),
+        "StockSplit" => Some(RecordType::StockSplit),
+        "ListingChange" => Some(RecordType::ListingChange),
+        "Transfer" => Some(RecordType::Transfer),
+        _ => None,
+    }
+}
+
+fn required<'a>(field: &str, value: &'a str) -> anyhow::Result<&'a str> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(ImportError::unparsable(field, value).into());
+    }
+    Ok(value)
+}
+
+fn required_decimal(field: &str, value: &str) -> anyhow::Result<Decimal> {
+    required(field, value)?
+        .parse::<Decimal>()
+        .map_err(|_| ImportError::unparsable(field, value).into())
+}
+
+// empty optional amounts (e.g. fees) fall back to the default, but anything else has to be a number
+fn optional_decimal(field: &str, value: &str, default: Decimal) -> anyhow::Result<Decimal> {
+    if value.trim().is_empty() {
+        return Ok(default);
+    }
+    required_decimal(field, value)
+}
+
+fn one_of<'a>(field: &str, value: &'a str, allowed: &[&str]) -> anyhow::Result<&'a str> {
+    let value = required(field, value)?;
+    if !allowed.contains(&value) {
+        return Err(ImportError::unparsable(field, value).into());
+    }
+    Ok(value)
+}
+
+fn parse_date(value: &str) -> anyhow::Result<DateTime<Utc>> {
+    parse_timestamp(required("date", value)?)
+        .map_err(|_| ImportError::unparsable("date", value).into())
+}
+
+fn optional_string(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+async fn to_eur(amount: Decimal, date: &DateTime<Utc>, currency: &str) -> anyhow::Result<Decimal> {
+    convert_amount(amount, &date.date_naive(), currency, "EUR").await
+}
+
+// transfers in are booked as buys at the original cost, so the position keeps its cost basis
+async fn extract_trade(
+    record: &ManualRecord,
+    direction: &str,
+    security_types: &[&str],
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let date = parse_date(&record.date)?;
+    let currency = required("currency", &record.currency)?;
+    let avg_price_per_unit = required_decimal("avg_price_per_unit", &record.avg_price_per_unit)?;
+
+    let trade = Trade {
+        broker: required("broker", &record.broker)?.to_string(),
+        date,
+        isin: required("isin", &record.isin)?.to_string(),
+        avg_price_per_unit,
+        eur_avg_price_per_unit: to_eur(avg_price_per_unit, &date, currency).await?,
+        units: required_decimal("units", &record.units)?,
+        direction: direction.to_string(),
+        security_type: one_of("security_type", &record.security_type, security_types)?.to_string(),
+        currency: currency.to_string(),
+        date_added: Utc::now(),
+        fees: optional_decimal("fees", &record.fees, dec!(0.0))?,
+        withholding_tax: optional_decimal("withholding_tax", &record.withholding_tax, dec!(0.0))?,
+        withholding_tax_currency: optional_string(&record.withholding_tax_currency)
+            .unwrap_or(currency.to_string()),
+    };
+    let id = ctx.identity.trade_id(&trade);
+    ctx.add_trade(trade, Some(id)).await?;
+    Ok(())
 }
 
-fn detect_record_type(record: &ManualRecord) -> RecordType {
-    if record.action == "Trade" {
-        return RecordType::EquityTrade;
+async fn extract_dividend(record: &ManualRecord, ctx: &mut ImportContext) -> anyhow::Result<()> {
+    let date = parse_date(&record.date)?;
+    let currency = required("currency", &record.currency)?;
+    let amount = required_decimal("amount", &record.amount)?;
+
+    let dividend = Dividend {
+        isin: required("isin", &record.isin)?.to_string(),
+        date,
+        amount,
+        broker: required("broker", &record.broker)?.to_string(),
+        currency: currency.to_string(),
+        amount_eur: to_eur(amount, &date, currency).await?,
+        withholding_tax: optional_decimal("withholding_tax", &record.withholding_tax, dec!(0.0))?,
+        withholding_tax_currency: optional_string(&record.withholding_tax_currency)
+            .unwrap_or(currency.to_string()),
+    };
+    ctx.add_dividend(dividend, None).await?;
+    Ok(())
+}
+
+async fn extract_interest(record: &ManualRecord, ctx: &mut ImportContext) -> anyhow::Result<()> {
+    let date = parse_date(&record.date)?;
+    let currency = required("currency", &record.currency)?;
+    let amount = required_decimal("amount", &record.amount)?;
+
+    let interest_payment = InterestPayment {
+        date,
+        amount,
+        broker: required("broker", &record.broker)?.to_string(),
+        principal: optional_string(&record.description).unwrap_or("Cash".to_string()),
+        currency: currency.to_string(),
+        amount_eur: to_eur(amount, &date, currency).await?,
+        withholding_tax: optional_decimal("withholding_tax", &record.withholding_tax, dec!(0.0))?,
+        withholding_tax_currency: optional_string(&record.withholding_tax_currency)
+            .unwrap_or(currency.to_string()),
+    };
+    ctx.add_interest(interest_payment, None).await?;
+    Ok(())
+}
+
+async fn extract_fx_conversion(
+    record: &ManualRecord,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let fx_conversion = FxConversion {
+        date: parse_date(&record.date)?,
+        broker: required("broker", &record.broker)?.to_string(),
+        from_amount: required_decimal("amount", &record.amount)?,
+        to_amount: required_decimal("to_amount", &record.to_amount)?,
+        from_currency: required("currency", &record.currency)?.to_string(),
+        to_currency: required("to_currency", &record.to_currency)?.to_string(),
+        date_added: Utc::now(),
+        fees: optional_decimal("fees", &record.fees, dec!(0.0))?,
+    };
+    ctx.add_fx_conversion(fx_conversion).await?;
+    Ok(())
+}
+
+async fn extract_tax_optimization(
+    record: &ManualRecord,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let date = parse_date(&record.date)?;
+    let currency = required("currency", &record.currency)?;
+    let amount = required_decimal("amount", &record.amount)?;
+
+    let tax_optimization = TaxOptimization {
+        date,
+        broker: required("broker", &record.broker)?.to_string(),
+        amount,
+        currency: currency.to_string(),
+        amount_eur: to_eur(amount, &date, currency).await?,
+        tax_type: one_of(
+            "tax_type",
+            &record.tax_type,
+            &["CapitalGains", "Dividend", "Interest"],
+        )?
+        .to_string(),
+        description: optional_string(&record.description),
+        transaction_id: None,
+    };
+    ctx.add_tax_optimization(tax_optimization).await?;
+    Ok(())
+}
+
+async fn extract_stock_split(record: &ManualRecord, ctx: &mut ImportContext) -> anyhow::Result<()> {
+    let ex_date = parse_date(&record.date)?;
+    let isin = required("isin", &record.isin)?;
+
+    // same id as splits fetched from polygon, so a manual entry isn't duplicated by housekeeping
+    let stock_split = StockSplit {
+        id: hash_string(format!("{}{}", isin, ex_date.date_naive()).as_str()),
+        ex_date,
+        from_factor: required_decimal("from_factor", &record.from_factor)?,
+        to_factor: required_decimal("to_factor", &record.to_factor)?,
+        isin: isin.to_string(),
+    };
+    ctx.add_stock_split(stock_split).await?;
+    Ok(())
+}
+
+async fn extract_listing_change(
+    record: &ManualRecord,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let ex_date = parse_date(&record.date)?;
+    let from_identifier = required("isin", &record.isin)?;
+    let to_identifier = required("to_isin", &record.to_isin)?;
+
+    let listing_change = ListingChange {
+        id: hash_string(
+            format!(
+                "{}{}{}",
+                from_identifier,
+                to_identifier,
+                ex_date.date_naive()
+            )
+            .as_str(),
+        ),
+        ex_date,
+        from_factor: optional_decimal("from_factor", &record.from_factor, dec!(1))?,
+        to_factor: optional_decimal("to_factor", &record.to_factor, dec!(1))?,
+        from_identifier: from_identifier.to_string(),
+        to_identifier: to_identifier.to_string(),
+    };
+    ctx.add_listing_change(listing_change).await?;
+    Ok(())
+}
+
+async fn extract_record(record: ManualRecord, ctx: &mut ImportContext) -> anyhow::Result<()> {
+    let Some(record_type) = detect_record_type(&record) else {
+        return Err(ImportError::unparsable("action", &record.action).into());
+    };
+
+    match record_type {
+        RecordType::Trade => {
+            let direction = one_of("direction", &record.direction, &["Buy", "Sell"])?;
+            extract_trade(&record, direction, &["Equity", "Bond", "Derivative"], ctx).await
+        }
+        RecordType::Transfer => {
+            // transfers out leave the depot without being taxed, the receiving depot books them
+            // as a buy
+            let direction = match one_of("direction", &record.direction, &["In", "Out"])? {
+                "In" => "Buy",
+                _ => "TransferOut",
+            };
+            extract_trade(&record, direction, &["Equity", "Bond"], ctx).await
+        }
+        RecordType::Dividend => extract_dividend(&record, ctx).await,
+        RecordType::Interest => extract_interest(&record, ctx).await,
+        RecordType::FxConversion => extract_fx_conversion(&record, ctx).await,
+        RecordType::TaxOptimization => extract_tax_optimization(&record, ctx).await,
+        RecordType::StockSplit => extract_stock_split(&record, ctx).await,
+        RecordType::ListingChange => extract_listing_change(&record, ctx).await,
     }
-    RecordType::Unmatched
 }
 
-pub async fn extract_manual_record(file_content: &[u8]) -> anyhow::Result<()> {
+pub async fn extract_manual_record(
+    file_content: &[u8],
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
     let cursor = Cursor::new(file_content);
 
-    let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(cursor);
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_reader(cursor);
+    // headers are matched case-insensitively, earlier manual imports used `ISIN`
+    let headers: StringRecord = rdr
+        .headers()?
+        .iter()
+        .map(|header| header.to_lowercase())
+        .collect();
+    rdr.set_headers(headers);
 
-    for result in rdr.deserialize() {
+    for (index, result) in rdr.deserialize().enumerate() {
+        // the header is line 1
+        ctx.set_line(index as u64 + 2);
         let record: ManualRecord = result?;
 
-        let record_type = detect_record_type(&record);
-
-        match record_type {
-            RecordType::EquityTrade => {
-                let trade = Trade {
-                    broker: record.broker,
-                    date: parse_timestamp(&record.date)?,
-                    isin: record.isin,
-                    avg_price_per_unit: record.avg_price_per_unit.parse::<Decimal>()?,
-                    eur_avg_price_per_unit: if record.currency == "EUR" {
-                        record.avg_price_per_unit.parse::<Decimal>()?
-                    } else {
-                        convert_amount(
-                            record.avg_price_per_unit.parse::<Decimal>()?,
-                            &parse_timestamp(&record.date)?.date_naive(),
-                            &record.currency,
-                            "EUR",
-                        )
-                        .await?
-                    },
-                    units: record.units.parse::<Decimal>()?,
-                    direction: record.direction,
-                    security_type: record.security_type,
-                    currency: record.currency.to_string(),
-                    date_added: Utc::now(),
-                    fees: record.fees.parse::<Decimal>().unwrap_or(dec!(0.0)),
-                    withholding_tax: record
-                        .withholding_tax
-                        .parse::<Decimal>()
-                        .unwrap_or(dec!(0.0)),
-                    withholding_tax_currency: record.withholding_tax_currency,
-                };
-                add_trade_to_db(trade, None).await?;
-            }
-            RecordType::Unmatched => continue,
+        // a faulty row is reported and doesn't keep the remaining rows from being imported
+        if let Err(e) = extract_record(record, ctx).await {
+            ctx.record_error(&e);
         }
     }
     Ok(())
@@ -0,0 +1,529 @@
+use std::{fs, io::Cursor, path::Path, str::FromStr};
+
+use anyhow::anyhow;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use csv::{ReaderBuilder, StringRecord};
+use fancy_regex::Regex;
+use log::error;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+
+use crate::{
+    database::models::{
+        dividend::Dividend, fx_conversion::FxConversion, interest::InterestPayment, trade::Trade,
+    },
+    services::{
+        importers::{context::ImportContext, errors::ImportError},
+        market_data::fx_rates::convert_amount,
+        parsers::parse_timestamp,
+        shared::constants::PROFILES_DIR,
+    },
+};
+
+/// Declarative description of a broker's CSV export, read from a TOML file in the profiles
+/// directory. See the README for the available options.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MappingProfile {
+    pub name: String,
+    #[serde(default = "default_delimiter")]
+    delimiter: char,
+    detect: Detection,
+    #[serde(default)]
+    format: Format,
+    columns: Columns,
+    #[serde(default)]
+    rules: Vec<ActionRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Detection {
+    // a file is matched if its header row contains all of these columns
+    headers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Format {
+    // chrono format strings, tried in order, both date-times and plain dates are supported
+    #[serde(default)]
+    date_formats: Vec<String>,
+    #[serde(default = "default_decimal_separator")]
+    decimal_separator: char,
+    thousands_separator: Option<char>,
+    // used if there's no currency column or it's empty
+    #[serde(default = "default_currency")]
+    currency: String,
+    #[serde(default = "default_security_type")]
+    security_type: String,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format {
+            date_formats: vec![],
+            decimal_separator: default_decimal_separator(),
+            thousands_separator: None,
+            currency: default_currency(),
+            security_type: default_security_type(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Columns {
+    date: String,
+    action: Option<String>,
+    isin: Option<String>,
+    units: Option<String>,
+    price: Option<String>,
+    amount: Option<String>,
+    currency: Option<String>,
+    fees: Option<String>,
+    withholding_tax: Option<String>,
+    withholding_tax_currency: Option<String>,
+    to_amount: Option<String>,
+    to_currency: Option<String>,
+    id: Option<String>,
+    description: Option<String>,
+}
+
+impl Columns {
+    fn mapped(&self) -> Vec<&String> {
+        [
+            &self.action,
+            &self.isin,
+            &self.units,
+            &self.price,
+            &self.amount,
+            &self.currency,
+            &self.fees,
+            &self.withholding_tax,
+            &self.withholding_tax_currency,
+            &self.to_amount,
+            &self.to_currency,
+            &self.id,
+            &self.description,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(std::iter::once(&self.date))
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+enum RecordType {
+    Trade,
+    Dividend,
+    Interest,
+    FxConversion,
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+enum DirectionRule {
+    Buy,
+    Sell,
+    // negative units are a sell
+    UnitsSign,
+    // money flowing out of the account (negative amount) is a buy
+    AmountSign,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ActionRule {
+    // exact value of the action column
+    action: Option<String>,
+    // regex matched against the action column
+    pattern: Option<String>,
+    record: RecordType,
+    direction: Option<DirectionRule>,
+    security_type: Option<String>,
+}
+
+impl ActionRule {
+    // a rule without action or pattern matches every row
+    fn matches(&self, action: &str) -> bool {
+        if let Some(expected) = &self.action {
+            if expected != action {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            // patterns are checked when loading the profile
+            let regex = Regex::new(pattern).unwrap();
+            if !regex.is_match(action).unwrap_or(false) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_decimal_separator() -> char {
+    '.'
+}
+
+fn default_currency() -> String {
+    "EUR".to_string()
+}
+
+fn default_security_type() -> String {
+    "Equity".to_string()
+}
+
+impl MappingProfile {
+    fn validate(&self) -> anyhow::Result<()> {
+        if !self.delimiter.is_ascii() {
+            return Err(anyhow!("delimiter needs to be an ASCII character"));
+        }
+        if self.detect.headers.is_empty() {
+            return Err(anyhow!("at least one header is needed for detection"));
+        }
+        if self.rules.is_empty() {
+            return Err(anyhow!("at least one rule is needed"));
+        }
+        for rule in &self.rules {
+            if let Some(pattern) = &rule.pattern {
+                Regex::new(pattern)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn reader<'a>(&self, file: &'a [u8]) -> csv::Reader<Cursor<&'a [u8]>> {
+        ReaderBuilder::new()
+            .delimiter(self.delimiter as u8)
+            .has_headers(true)
+            .flexible(true)
+            .from_reader(Cursor::new(file))
+    }
+
+    pub fn matches_header(&self, file: &[u8]) -> bool {
+        match self.reader(file).headers() {
+            Ok(headers) => self
+                .detect
+                .headers
+                .iter()
+                .all(|column| headers.iter().any(|header| header.trim() == column)),
+            Err(_) => false,
+        }
+    }
+}
+
+fn load_mapping_profile(path: &Path) -> anyhow::Result<MappingProfile> {
+    let profile: MappingProfile = toml::from_str(&fs::read_to_string(path)?)?;
+    profile.validate()?;
+    Ok(profile)
+}
+
+/// Reads all profiles from the profiles directory. Faulty profiles are logged and left out, so
+/// they don't prevent importing files of other brokers.
+pub fn load_mapping_profiles() -> Vec<MappingProfile> {
+    let Ok(entries) = fs::read_dir(PROFILES_DIR) else {
+        return vec![];
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension() == Some(std::ffi::OsStr::new("toml")))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .filter_map(|path| match load_mapping_profile(path) {
+            Ok(profile) => Some(profile),
+            Err(e) => {
+                error!("Skipping mapping profile {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn detect_mapping_profile(file: &[u8]) -> Option<MappingProfile> {
+    load_mapping_profiles()
+        .into_iter()
+        .find(|profile| profile.matches_header(file))
+}
+
+struct Row<'a> {
+    profile: &'a MappingProfile,
+    headers: &'a StringRecord,
+    record: &'a StringRecord,
+}
+
+impl<'a> Row<'a> {
+    fn value(&self, column: &str) -> Option<&'a str> {
+        let index = self
+            .headers
+            .iter()
+            .position(|header| header.trim() == column)?;
+        self.record
+            .get(index)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn get(&self, column: &Option<String>) -> Option<&'a str> {
+        self.value(column.as_ref()?)
+    }
+
+    fn required(&self, field: &str, column: &Option<String>) -> anyhow::Result<&'a str> {
+        self.get(column)
+            .ok_or_else(|| ImportError::unparsable(field, "").into())
+    }
+
+    fn parse_decimal(&self, field: &str, value: &str) -> anyhow::Result<Decimal> {
+        let format = &self.profile.format;
+        let normalized: String = value
+            .chars()
+            .filter(|c| !c.is_whitespace() && Some(*c) != format.thousands_separator)
+            .map(|c| {
+                if c == format.decimal_separator {
+                    '.'
+                } else {
+                    c
+                }
+            })
+            .collect();
+        Decimal::from_str(normalized.trim_start_matches('+'))
+            .map_err(|_| ImportError::unparsable(field, value).into())
+    }
+
+    fn decimal(&self, field: &str, column: &Option<String>) -> anyhow::Result<Option<Decimal>> {
+        self.get(column)
+            .map(|value| self.parse_decimal(field, value))
+            .transpose()
+    }
+
+    fn required_decimal(&self, field: &str, column: &Option<String>) -> anyhow::Result<Decimal> {
+        self.parse_decimal(field, self.required(field, column)?)
+    }
+
+    fn date(&self) -> anyhow::Result<DateTime<Utc>> {
+        let value = self
+            .value(&self.profile.columns.date)
+            .ok_or_else(|| ImportError::unparsable("date", ""))?;
+        let date_formats = &self.profile.format.date_formats;
+        if date_formats.is_empty() {
+            return parse_timestamp(value)
+                .map_err(|_| ImportError::unparsable("date", value).into());
+        }
+        date_formats
+            .iter()
+            .find_map(|format| {
+                NaiveDateTime::parse_from_str(value, format)
+                    .ok()
+                    .or_else(|| {
+                        NaiveDate::parse_from_str(value, format)
+                            .ok()
+                            .and_then(|date| date.and_hms_opt(0, 0, 0))
+                    })
+                    .map(|date| date.and_utc())
+            })
+            .ok_or_else(|| ImportError::unparsable("date", value).into())
+    }
+
+    fn currency(&self, column: &Option<String>) -> String {
+        self.get(column)
+            .unwrap_or(&self.profile.format.currency)
+            .to_string()
+    }
+}
+
+async fn extract_trade(
+    row: &Row<'_>,
+    rule: &ActionRule,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let columns = &row.profile.columns;
+    let date = row.date()?;
+    let currency = row.currency(&columns.currency);
+    let units = row.required_decimal("units", &columns.units)?;
+    let amount = row.decimal("amount", &columns.amount)?;
+
+    let direction = match rule.direction.unwrap_or(DirectionRule::UnitsSign) {
+        DirectionRule::Buy => "Buy",
+        DirectionRule::Sell => "Sell",
+        DirectionRule::UnitsSign if units < dec!(0) => "Sell",
+        DirectionRule::UnitsSign => "Buy",
+        DirectionRule::AmountSign => match amount {
+            Some(amount) if amount > dec!(0) => "Sell",
+            Some(_) => "Buy",
+            None => return Err(ImportError::unparsable("amount", "").into()),
+        },
+    };
+
+    // without a price column, the price is derived from the trade's total
+    let avg_price_per_unit = match row.decimal("price", &columns.price)? {
+        Some(price) => price.abs(),
+        None => match amount {
+            Some(amount) if units != dec!(0) => (amount / units).abs(),
+            _ => return Err(ImportError::unparsable("price", "").into()),
+        },
+    };
+
+    let trade = Trade {
+        broker: row.profile.name.clone(),
+        date,
+        isin: row.required("isin", &columns.isin)?.to_string(),
+        avg_price_per_unit,
+        eur_avg_price_per_unit: convert_amount(
+            avg_price_per_unit,
+            &date.date_naive(),
+            &currency,
+            "EUR",
+        )
+        .await?,
+        units: units.abs(),
+        direction: direction.to_string(),
+        security_type: rule
+            .security_type
+            .clone()
+            .unwrap_or(row.profile.format.security_type.clone()),
+        currency: currency.clone(),
+        date_added: Utc::now(),
+        fees: row.decimal("fees", &columns.fees)?.unwrap_or(dec!(0)).abs(),
+        withholding_tax: row
+            .decimal("withholding_tax", &columns.withholding_tax)?
+            .unwrap_or(dec!(0))
+            .abs(),
+        withholding_tax_currency: row.currency(&columns.withholding_tax_currency),
+    };
+
+    let id = match row.get(&columns.id) {
+        Some(id) => id.to_string(),
+        None => ctx.identity.trade_id(&trade),
+    };
+    ctx.add_trade(trade, Some(id)).await?;
+    Ok(())
+}
+
+async fn extract_dividend(row: &Row<'_>, ctx: &mut ImportContext) -> anyhow::Result<()> {
+    let columns = &row.profile.columns;
+    let date = row.date()?;
+    let currency = row.currency(&columns.currency);
+    let amount = row.required_decimal("amount", &columns.amount)?.abs();
+
+    let dividend = Dividend {
+        isin: row.required("isin", &columns.isin)?.to_string(),
+        date,
+        amount,
+        broker: row.profile.name.clone(),
+        currency: currency.clone(),
+        amount_eur: convert_amount(amount, &date.date_naive(), &currency, "EUR").await?,
+        withholding_tax: row
+            .decimal("withholding_tax", &columns.withholding_tax)?
+            .unwrap_or(dec!(0))
+            .abs(),
+        withholding_tax_currency: row.currency(&columns.withholding_tax_currency),
+    };
+    ctx.add_dividend(dividend, row.get(&columns.id)).await?;
+    Ok(())
+}
+
+async fn extract_interest(row: &Row<'_>, ctx: &mut ImportContext) -> anyhow::Result<()> {
+    let columns = &row.profile.columns;
+    let date = row.date()?;
+    let currency = row.currency(&columns.currency);
+    let amount = row.required_decimal("amount", &columns.amount)?.abs();
+
+    let interest_payment = InterestPayment {
+        date,
+        amount,
+        broker: row.profile.name.clone(),
+        principal: row.get(&columns.description).unwrap_or("Cash").to_string(),
+        currency: currency.clone(),
+        amount_eur: convert_amount(amount, &date.date_naive(), &currency, "EUR").await?,
+        withholding_tax: row
+            .decimal("withholding_tax", &columns.withholding_tax)?
+            .unwrap_or(dec!(0))
+            .abs(),
+        withholding_tax_currency: row.currency(&columns.withholding_tax_currency),
+    };
+    ctx.add_interest(interest_payment, row.get(&columns.id))
+        .await?;
+    Ok(())
+}
+
+async fn extract_fx_conversion(row: &Row<'_>, ctx: &mut ImportContext) -> anyhow::Result<()> {
+    let columns = &row.profile.columns;
+
+    let fx_conversion = FxConversion {
+        date: row.date()?,
+        broker: row.profile.name.clone(),
+        from_amount: row.required_decimal("amount", &columns.amount)?.abs(),
+        to_amount: row.required_decimal("to_amount", &columns.to_amount)?.abs(),
+        from_currency: row.currency(&columns.currency),
+        to_currency: row
+            .required("to_currency", &columns.to_currency)?
+            .to_string(),
+        date_added: Utc::now(),
+        fees: row.decimal("fees", &columns.fees)?.unwrap_or(dec!(0)).abs(),
+    };
+    ctx.add_fx_conversion(fx_conversion).await?;
+    Ok(())
+}
+
+pub async fn extract_mapping_profile_record(
+    file_content: &[u8],
+    profile: &MappingProfile,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let mut rdr = profile.reader(file_content);
+    let headers = rdr.headers()?.clone();
+
+    for column in profile.columns.mapped() {
+        if !headers.iter().any(|header| header.trim() == column) {
+            return Err(anyhow!(
+                "Column '{}' of mapping profile {} is missing",
+                column,
+                profile.name
+            ));
+        }
+    }
+
+    for (index, result) in rdr.records().enumerate() {
+        // the header is line 1
+        ctx.set_line(index as u64 + 2);
+        let record = result?;
+        let row = Row {
+            profile,
+            headers: &headers,
+            record: &record,
+        };
+
+        let action = row.get(&profile.columns.action).unwrap_or_default();
+        let Some(rule) = profile.rules.iter().find(|rule| rule.matches(action)) else {
+            ctx.skip();
+            continue;
+        };
+
+        let result = match rule.record {
+            RecordType::Trade => extract_trade(&row, rule, ctx).await,
+            RecordType::Dividend => extract_dividend(&row, ctx).await,
+            RecordType::Interest => extract_interest(&row, ctx).await,
+            RecordType::FxConversion => extract_fx_conversion(&row, ctx).await,
+            RecordType::Skip => {
+                ctx.skip();
+                Ok(())
+            }
+        };
+        // a faulty row is reported and doesn't keep the remaining rows from being imported
+        if let Err(e) = result {
+            ctx.record_error(&e);
+        }
+    }
+    Ok(())
+}
@@ -1,9 +1,20 @@
+pub mod baader;
+pub mod bitpanda;
+pub mod context;
+pub mod degiro;
+pub mod errors;
 pub mod erste_bank;
+pub mod flatex;
 pub mod ibkr;
+pub mod ibkr_flex;
 pub mod lightyear;
 pub mod manual;
+pub mod mapping_profile;
+pub mod oekb;
+pub mod report;
 pub mod revolut;
 pub mod scalable;
 pub mod trade_republic;
 pub mod trading212;
+pub mod validation;
 pub mod wise;
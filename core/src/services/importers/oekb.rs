@@ -0,0 +1,27 @@
+use serde::Deserialize;
+
+use crate::services::{importers::context::ImportContext, market_data::oekb::OekbRawReport};
+
+// either a single report, e.g. saved from my.oekb.at, or a list of them, e.g. exported with
+// `funds export-reports`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OekbReportFile {
+    Single(OekbRawReport),
+    Multiple(Vec<OekbRawReport>),
+}
+
+pub fn is_oekb_report(text: &str) -> bool {
+    text.contains("\"steuerMeldung\"") && text.contains("\"privatAnl\"")
+}
+
+pub async fn extract_oekb_report_record(text: &str, ctx: &mut ImportContext) -> anyhow::Result<()> {
+    let reports = match serde_json::from_str::<OekbReportFile>(text)? {
+        OekbReportFile::Single(report) => vec![report],
+        OekbReportFile::Multiple(reports) => reports,
+    };
+    for report in reports {
+        ctx.add_fund_report(report).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use typeshare::typeshare;
+
+use super::errors::ImportError;
+
+#[typeshare]
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RecordKind {
+    Trade,
+    Dividend,
+    Interest,
+    FxConversion,
+    TaxOptimization,
+    StockSplit,
+    ListingChange,
+    CryptoSwap,
+    Derivative,
+    OptionExercise,
+    FundReport,
+}
+
+#[typeshare]
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportErrorEntry {
+    pub kind: String,
+    pub message: String,
+    pub line: Option<u64>,
+}
+
+#[typeshare]
+#[derive(Debug, Clone, Serialize)]
+pub struct FileImportReport {
+    pub path: String,
+    // for files unpacked from an archive, the archive and the path of the file within it
+    pub archive: Option<String>,
+    pub inner_path: Option<String>,
+    pub broker: Option<String>,
+    pub created: BTreeMap<RecordKind, u32>,
+    pub duplicates: BTreeMap<RecordKind, u32>,
+    pub skipped: u32,
+    pub errors: Vec<ImportErrorEntry>,
+    // false if an error might not occur when importing the file again, e.g. a failing request
+    #[serde(skip)]
+    pub complete: bool,
+}
+
+impl FileImportReport {
+    pub fn new(path: &str) -> FileImportReport {
+        FileImportReport {
+            path: path.to_string(),
+            archive: None,
+            inner_path: None,
+            broker: None,
+            created: BTreeMap::new(),
+            duplicates: BTreeMap::new(),
+            skipped: 0,
+            errors: vec![],
+            complete: true,
+        }
+    }
+
+    pub fn count_created(&mut self, kind: RecordKind) {
+        *self.created.entry(kind).or_insert(0) += 1;
+    }
+
+    pub fn count_duplicate(&mut self, kind: RecordKind) {
+        *self.duplicates.entry(kind).or_insert(0) += 1;
+    }
+
+    pub fn total_created(&self) -> u32 {
+        self.created.values().sum()
+    }
+
+    // errors raised by importers are either an `ImportError` or anything else `anyhow` carries,
+    // the latter being reported with the line the importer was at when it failed
+    pub fn add_error(&mut self, error: &anyhow::Error, line: Option<u64>) {
+        self.complete &= error
+            .downcast_ref::<ImportError>()
+            .is_some_and(|import_error| import_error.is_data_error());
+        let entry = match error.downcast_ref::<ImportError>() {
+            Some(import_error) => ImportErrorEntry {
+                kind: import_error.kind().to_string(),
+                message: import_error.to_string(),
+                line: import_error.line().or(line),
+            },
+            None => ImportErrorEntry {
+                kind: "Other".to_string(),
+                message: format!("{:#}", error),
+                line: error
+                    .downcast_ref::<csv::Error>()
+                    .and_then(|csv_error| csv_error.position())
+                    .map(|position| position.line())
+                    .or(line),
+            },
+        };
+        self.errors.push(entry);
+    }
+}
+
+#[typeshare]
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub created_at: DateTime<Utc>,
+    // with dry runs, `created` counts the records that would have been created
+    pub dry_run: bool,
+    pub files: Vec<FileImportReport>,
+}
+
+impl ImportReport {
+    pub fn new() -> ImportReport {
+        ImportReport {
+            created_at: Utc::now(),
+            dry_run: false,
+            files: vec![],
+        }
+    }
+}
+
+impl Default for ImportReport {
+    fn default() -> Self {
+        ImportReport::new()
+    }
+}
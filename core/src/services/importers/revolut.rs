@@ -9,13 +9,12 @@ use serde::Deserialize;
 use crate::{
     database::{
         models::{dividend::Dividend, fx_conversion::FxConversion, trade::Trade},
-        queries::{
-            composite::add_trade_to_db, dividend::add_dividend_to_db,
-            fx_conversion::add_fx_conversion_to_db, listing_change::get_listing_changes,
-            ticker_conversion::query_isin_from_symbol,
-        },
+        queries::listing_change::get_listing_changes,
+    },
+    services::{
+        importers::context::ImportContext, instruments::identifiers::get_changed_identifier,
+        parsers::parse_timestamp,
     },
-    services::{instruments::identifiers::get_changed_identifier, parsers::parse_timestamp},
 };
 
 #[derive(Debug, Deserialize)]
@@ -88,7 +87,10 @@ fn detect_account_record_type(record: &RevolutAccountRecord) -> AccountRecordTyp
     AccountRecordType::Unmatched
 }
 
-pub async fn extract_revolut_record(file_content: &[u8]) -> anyhow::Result<()> {
+pub async fn extract_revolut_record(
+    file_content: &[u8],
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
     let broker = "Revolut".to_string();
     let listing_changes = get_listing_changes().await?;
 
@@ -101,7 +103,8 @@ pub async fn extract_revolut_record(file_content: &[u8]) -> anyhow::Result<()> {
 
     match csv_type {
         CsvType::Trading => {
-            for result in rdr.deserialize() {
+            for (index, result) in rdr.deserialize().enumerate() {
+                ctx.set_line(index as u64 + 1);
                 let record: RevolutTradingRecord = result?;
                 let record_type = detect_trading_record_type(&record);
 
@@ -114,11 +117,18 @@ pub async fn extract_revolut_record(file_content: &[u8]) -> anyhow::Result<()> {
                             .replace(",", "")
                             .parse::<Decimal>()?;
 
+                        let Some(isin) = ctx
+                            .resolve_ticker(&get_changed_identifier(
+                                &record.ticker,
+                                listing_changes,
+                            ))
+                            .await?
+                        else {
+                            continue;
+                        };
+
                         let dividend = Dividend {
-                            isin: query_isin_from_symbol(
-                                get_changed_identifier(&record.ticker, listing_changes).as_str(),
-                            )
-                            .await?,
+                            isin,
                             date: Utc
                                 .from_utc_datetime(&parse_timestamp(&record.time)?.naive_utc()),
                             broker: broker.clone(),
@@ -131,7 +141,7 @@ pub async fn extract_revolut_record(file_content: &[u8]) -> anyhow::Result<()> {
                             withholding_tax: parsed_amount * dec!(0.15),
                             withholding_tax_currency: record.currency.clone(),
                         };
-                        if add_dividend_to_db(dividend.clone(), None).await? {
+                        if ctx.add_dividend(dividend.clone(), None).await? {
                             println!("💵 Dividend added: {:?}", dividend);
                         }
                     }
@@ -144,14 +154,21 @@ pub async fn extract_revolut_record(file_content: &[u8]) -> anyhow::Result<()> {
                             .replace(",", "")
                             .parse::<Decimal>()?;
 
+                        let Some(isin) = ctx
+                            .resolve_ticker(&get_changed_identifier(
+                                &record.ticker,
+                                listing_changes,
+                            ))
+                            .await?
+                        else {
+                            continue;
+                        };
+
                         let trade = Trade {
                             broker: broker.clone(),
                             date: Utc
                                 .from_utc_datetime(&parse_timestamp(&record.time)?.naive_utc()),
-                            isin: query_isin_from_symbol(
-                                get_changed_identifier(&record.ticker, listing_changes).as_str(),
-                            )
-                            .await?,
+                            isin,
                             avg_price_per_unit: parsed_price_per_share,
                             eur_avg_price_per_unit: parsed_price_per_share
                                 / record.fx_rate.parse::<Decimal>()?,
@@ -172,10 +189,11 @@ pub async fn extract_revolut_record(file_content: &[u8]) -> anyhow::Result<()> {
                             withholding_tax: dec!(0.0),
                             withholding_tax_currency: record.currency,
                         };
-                        add_trade_to_db(trade, None).await?;
+                        let id = ctx.identity.trade_id(&trade);
+                        ctx.add_trade(trade, Some(id)).await?;
                     }
-                    TradingRecordType::CashTransfer => continue,
-                    TradingRecordType::Unmatched => continue,
+                    TradingRecordType::CashTransfer => ctx.skip(),
+                    TradingRecordType::Unmatched => ctx.skip(),
                 }
             }
         }
@@ -185,7 +203,8 @@ pub async fn extract_revolut_record(file_content: &[u8]) -> anyhow::Result<()> {
             let mut records_by_timestamp: HashMap<String, Vec<RevolutAccountRecord>> =
                 HashMap::new();
 
-            for result in rdr2.deserialize() {
+            for (index, result) in rdr2.deserialize().enumerate() {
+                ctx.set_line(index as u64 + 1);
                 let record: RevolutAccountRecord = result?;
                 let timestamp = record.started_date.clone();
                 records_by_timestamp
@@ -193,7 +212,8 @@ pub async fn extract_revolut_record(file_content: &[u8]) -> anyhow::Result<()> {
                     .or_default()
                     .push(record);
             }
-            for result in rdr.deserialize() {
+            for (index, result) in rdr.deserialize().enumerate() {
+                ctx.set_line(index as u64 + 1);
                 let record: RevolutAccountRecord = result?;
                 let record_type = detect_account_record_type(&record);
                 match record_type {
@@ -218,13 +238,13 @@ pub async fn extract_revolut_record(file_content: &[u8]) -> anyhow::Result<()> {
                                                 .parse::<Decimal>()
                                                 .unwrap_or(dec!(0.0)),
                                         };
-                                        add_fx_conversion_to_db(fx_conversion).await?;
+                                        ctx.add_fx_conversion(fx_conversion).await?;
                                     }
                                 }
                             };
                         }
                     }
-                    AccountRecordType::Unmatched => continue,
+                    AccountRecordType::Unmatched => ctx.skip(),
                 }
             }
         }
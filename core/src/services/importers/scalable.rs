@@ -4,11 +4,11 @@ use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
 use crate::{
-    database::{
-        models::{dividend::Dividend, trade::Trade},
-        queries::{composite::add_trade_to_db, dividend::add_dividend_to_db},
+    database::models::{dividend::Dividend, trade::Trade},
+    services::{
+        importers::context::ImportContext,
+        parsers::{does_match_exist, parse_timestamp},
     },
-    services::parsers::{does_match_exist, parse_timestamp},
 };
 
 #[derive(Debug)]
@@ -165,7 +165,7 @@ fn extract_dividend_details(text: &str) -> Option<(String, String, String)> {
     }
 }
 
-pub async fn extract_scalable_record(text: &str) -> anyhow::Result<()> {
+pub async fn extract_scalable_record(text: &str, ctx: &mut ImportContext) -> anyhow::Result<()> {
     let broker = "Scalable".to_string();
 
     match detect_record_type(text) {
@@ -191,7 +191,7 @@ pub async fn extract_scalable_record(text: &str) -> anyhow::Result<()> {
                     withholding_tax: dec!(0.0),
                     withholding_tax_currency: "EUR".to_string(),
                 };
-                add_trade_to_db(trade, Some(id)).await?;
+                ctx.add_trade(trade, Some(id)).await?;
             }
         }
         RecordType::Dividend => {
@@ -206,12 +206,12 @@ pub async fn extract_scalable_record(text: &str) -> anyhow::Result<()> {
                     withholding_tax: dec!(0.0),
                     withholding_tax_currency: "EUR".to_string(),
                 };
-                if add_dividend_to_db(dividend.clone(), None).await? {
+                if ctx.add_dividend(dividend.clone(), None).await? {
                     println!("💵 Dividend added: {:?}", dividend);
                 }
             }
         }
-        RecordType::Unmatched => (),
+        RecordType::Unmatched => ctx.skip(),
     }
     Ok(())
 }
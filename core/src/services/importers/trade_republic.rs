@@ -1,11 +1,11 @@
 use anyhow::anyhow;
+use csv::ReaderBuilder;
 use csv::StringRecord;
-use log::info;
+use log::{info, warn};
 use regex::Regex;
 use rust_decimal_macros::dec;
 use std::io;
 use std::io::Cursor;
-use csv::ReaderBuilder;
 
 use crate::cli::import::choose_match_from_regex;
 use crate::database::db_client;
@@ -13,16 +13,20 @@ use crate::database::models::dividend::Dividend;
 use crate::database::models::interest::InterestPayment;
 use crate::database::models::tax_optimization::TaxOptimization;
 use crate::database::models::trade::Trade;
-use crate::database::queries::composite::add_trade_to_db;
-use crate::database::queries::dividend::add_dividend_to_db;
-use crate::database::queries::interest::add_interest_to_db;
-use crate::database::queries::position::get_positions_for_isin;
-use crate::database::queries::tax_optimization::add_tax_optimization_to_db;
+use crate::database::queries::position::{get_positions, get_positions_for_isin};
 use crate::database::queries::trade::find_similar_trade;
+use crate::services::importers::context::ImportContext;
+use crate::services::importers::errors::ImportError;
+use crate::services::importers::report::RecordKind;
+use crate::services::instruments::bonds::{
+    accrued_interest_payment, bond_principal, bond_units, BOND_PAR_PRICE,
+};
+use crate::services::instruments::crypto::crypto_identifier;
 use crate::services::parsers::does_match_exist;
 use crate::services::parsers::parse_timestamp;
+use crate::services::parsers::return_first_captures;
 use crate::services::parsers::return_first_match;
-use crate::services::shared::util::hash_string;
+use crate::services::shared::identity::trade_hash;
 use chrono::prelude::*;
 use rust_decimal::Decimal;
 
@@ -31,6 +35,7 @@ enum RecordType {
     EquityTrade,
     Liquidation,
     BondTrade,
+    Coupon,
     Dividend,
     InvestmentPlanExecution,
     InterestPayment,
@@ -38,16 +43,222 @@ enum RecordType {
     Unmatched,
 }
 
-fn detect_record_type(text: &str) -> anyhow::Result<RecordType> {
-    let dividend_patterns = Regex::new(r"(Dividende|COUPON|Ausschuttung)")?;
-    let bond_trade_pattern = Regex::new(r"Stuckzinsen")?;
-    let interest_pattern = Regex::new(r"Zinsen")?;
-    let liquidation_pattern = Regex::new(r"Tilgung")?;
-    let investment_plan_pattern = Regex::new(r"(Sparplanausfuhrung|Saveback|Round up)")?;
-    let portfolio_transfer_pattern = Regex::new(r"Depotubertrag")?;
+// TR issues statements in the language of the account, the layout is the same in all of them.
+// Keywords are regex alternations, matched against the text after umlauts and accents were
+// stripped (e.g. "Ausführung" becomes "Ausfuhrung")
+#[derive(Debug)]
+struct Locale {
+    language: &'static str,
+    // words found in most statements of a language, to tell them apart
+    markers: &'static [&'static str],
+    dividend: &'static str,
+    accrued_interest: &'static str,
+    interest: &'static str,
+    liquidation: &'static str,
+    investment_plan: &'static str,
+    portfolio_transfer: &'static str,
+    buy: &'static str,
+    units: &'static str,
+    // between the date and the time of an execution, e.g. "07.03.2024 um 10:00"
+    time_connector: &'static str,
+    // before the date of an interest statement
+    period_end: &'static str,
+    execution: &'static str,
+    external_costs: &'static str,
+    total: &'static str,
+    decimal_separator: char,
+    thousands_separator: char,
+}
+
+const LOCALES: &[Locale] = &[
+    Locale {
+        language: "German",
+        markers: &[
+            "DATUM",
+            "ANZAHL",
+            "Stk.",
+            "BUCHUNG",
+            "GESAMT",
+            "AUSFUHRUNG",
+            "Kauf",
+            "Verkauf",
+        ],
+        dividend: "Dividende|Ausschuttung",
+        accrued_interest: "Stuckzinsen",
+        interest: "Zinsen",
+        liquidation: "Tilgung",
+        investment_plan: "Sparplanausfuhrung",
+        portfolio_transfer: "Depotubertrag",
+        buy: "Kauf|Sparplan",
+        units: r"Stk\.",
+        time_connector: "um",
+        period_end: "zum",
+        execution: "AUSFUHRUNG",
+        external_costs: "Fremdkostenzuschlag",
+        total: "GESAMT",
+        decimal_separator: ',',
+        thousands_separator: '.',
+    },
+    Locale {
+        language: "English",
+        markers: &[
+            "DATE",
+            "QUANTITY",
+            "Pcs.",
+            "BOOKING",
+            "TOTAL",
+            "EXECUTION",
+            "Buy",
+            "Sell",
+        ],
+        dividend: "Dividend|Distribution",
+        accrued_interest: "Accrued [Ii]nterest",
+        interest: "Interest",
+        liquidation: "Repayment|Redemption",
+        investment_plan: "Savings [Pp]lan [Ee]xecution",
+        portfolio_transfer: "Securities transfer|Portfolio transfer",
+        buy: "Buy|Savings [Pp]lan",
+        units: r"Pcs\.",
+        time_connector: "at",
+        period_end: "as of|to",
+        execution: "EXECUTION",
+        external_costs: "Third-party costs surcharge|External cost surcharge",
+        total: "TOTAL",
+        decimal_separator: '.',
+        thousands_separator: ',',
+    },
+    Locale {
+        language: "French",
+        markers: &[
+            "QUANTITE",
+            "titre(s)",
+            "REGLEMENT",
+            "TOTAL",
+            "EXECUTION",
+            "Achat",
+            "Vente",
+        ],
+        dividend: "Dividende|Distribution",
+        accrued_interest: "Interets courus",
+        interest: "Interets",
+        liquidation: "Remboursement",
+        investment_plan: "Execution du plan d'investissement",
+        portfolio_transfer: "Transfert de titres",
+        buy: "Achat|Plan d'investissement",
+        units: r"titre\(s\)",
+        time_connector: "a",
+        period_end: "au",
+        execution: "EXECUTION",
+        external_costs: "Supplement pour frais externes",
+        total: "TOTAL",
+        decimal_separator: ',',
+        thousands_separator: '.',
+    },
+    Locale {
+        language: "Italian",
+        markers: &[
+            "DATA",
+            "QUANTITA",
+            "pz.",
+            "PRENOTAZIONE",
+            "TOTALE",
+            "ESECUZIONE",
+            "Acquisto",
+            "Vendita",
+        ],
+        dividend: "Dividendo|Distribuzione",
+        accrued_interest: "Rateo|Interessi maturati",
+        interest: "Interessi",
+        liquidation: "Rimborso",
+        investment_plan: "Esecuzione del piano di accumulo",
+        portfolio_transfer: "Trasferimento titoli",
+        buy: "Acquisto|Piano di accumulo",
+        units: r"[Pp]z\.",
+        time_connector: "alle",
+        period_end: "al",
+        execution: "ESECUZIONE",
+        external_costs: "Supplemento costi esterni",
+        total: "TOTALE",
+        decimal_separator: ',',
+        thousands_separator: '.',
+    },
+    Locale {
+        language: "Spanish",
+        markers: &[
+            "FECHA",
+            "CANTIDAD",
+            "tit.",
+            "RESERVA",
+            "TOTAL",
+            "EJECUCION",
+            "Compra",
+            "Venta",
+        ],
+        dividend: "Dividendo|Distribucion",
+        accrued_interest: "Intereses devengados|Cupon corrido",
+        interest: "Intereses",
+        liquidation: "Amortizacion|Reembolso",
+        investment_plan: "Ejecucion del plan de inversion",
+        portfolio_transfer: "Traspaso de valores",
+        buy: "Compra|Plan de inversion",
+        units: r"[Tt]it\.",
+        time_connector: "a las",
+        period_end: "a",
+        execution: "EJECUCION",
+        external_costs: "Recargo por costes externos",
+        total: "TOTAL",
+        decimal_separator: ',',
+        thousands_separator: '.',
+    },
+];
+
+// amounts and units in either notation, e.g. `1.234,56` or `1,234.56`
+const NUMBER: &str = r"\d+(?:[.,]\d+)*";
+// dates are formatted like `07.03.2024`, some statements use slashes instead
+const DATE: &str = r"\d{2}[./]\d{2}[./]\d{4}";
+
+// the language with the most markers wins, German on a tie since most statements are German
+fn detect_locale(text: &str) -> &'static Locale {
+    LOCALES
+        .iter()
+        .rev()
+        .max_by_key(|locale| {
+            locale
+                .markers
+                .iter()
+                .filter(|marker| text.contains(*marker))
+                .count()
+        })
+        .unwrap_or(&LOCALES[0])
+}
+
+fn parse_number(locale: &Locale, value: &str) -> anyhow::Result<Decimal> {
+    value
+        .trim()
+        .replace(locale.thousands_separator, "")
+        .replace(locale.decimal_separator, ".")
+        .parse::<Decimal>()
+        .map_err(|_| ImportError::unparsable("amount", value).into())
+}
+
+fn parse_date(date: &str, time: &str) -> anyhow::Result<DateTime<Utc>> {
+    parse_timestamp(&format!("{} {}:00", date.replace('/', "."), time))
+}
+
+fn detect_record_type(text: &str, locale: &Locale) -> anyhow::Result<RecordType> {
+    // coupons of bonds are statements of their own, the same in all languages
+    let coupon_pattern = Regex::new("COUPON")?;
+    let dividend_patterns = Regex::new(locale.dividend)?;
+    let bond_trade_pattern = Regex::new(locale.accrued_interest)?;
+    let interest_pattern = Regex::new(locale.interest)?;
+    let liquidation_pattern = Regex::new(locale.liquidation)?;
+    let investment_plan_pattern =
+        Regex::new(&format!("({}|Saveback|Round up)", locale.investment_plan))?;
+    let portfolio_transfer_pattern = Regex::new(locale.portfolio_transfer)?;
     let trade_patterns = Regex::new(r"Market-Order|Limit-Order|Stop-Market-Order")?;
 
     Ok(match text {
+        _ if coupon_pattern.is_match(text) => RecordType::Coupon,
         _ if dividend_patterns.is_match(text) => RecordType::Dividend,
         _ if bond_trade_pattern.is_match(text) => RecordType::BondTrade,
         _ if interest_pattern.is_match(text) => RecordType::InterestPayment,
@@ -73,7 +284,7 @@ fn require_import_confirmation(trade: &Trade) -> anyhow::Result<bool> {
 // Check if trade with exact same transaction ID already exists
 async fn trade_with_transaction_id_exists(id: &str) -> anyhow::Result<bool> {
     let client = db_client().await?;
-    let hash = hash_string(&format!("Trade Republic{}", id));
+    let hash = trade_hash("Trade Republic", id);
     
     let row = client
         .query_opt("SELECT 1 FROM trade WHERE hash = $1", &[&hash])
@@ -83,9 +294,14 @@ async fn trade_with_transaction_id_exists(id: &str) -> anyhow::Result<bool> {
 }
 
 // Non-interactive version for CSV imports - uses exact transaction_id match
-async fn add_csv_trade_with_duplicate_check(trade: Trade, id: String) -> anyhow::Result<bool> {
+async fn add_csv_trade_with_duplicate_check(
+    ctx: &mut ImportContext,
+    trade: Trade,
+    id: String,
+) -> anyhow::Result<bool> {
     // Check for exact duplicate by transaction_id
     if trade_with_transaction_id_exists(&id).await? {
+        ctx.report.count_duplicate(RecordKind::Trade);
         return Ok(false);
     }
 
@@ -93,57 +309,76 @@ async fn add_csv_trade_with_duplicate_check(trade: Trade, id: String) -> anyhow:
     if let Some(ref existing) = find_similar_trade(&trade).await? {
         log::debug!("Skipping duplicate trade (similar found - hash: {}): ISIN {} date {}", 
                   existing.hash, trade.isin, trade.date);
+        ctx.report.count_duplicate(RecordKind::Trade);
         return Ok(false);
     }
 
     // No duplicate found, add the trade
-    add_trade_to_db(trade, Some(id)).await?;
-    Ok(true)
+    ctx.add_trade(trade, Some(id)).await
 }
 
-async fn add_with_import_confirmation(trade: Trade, id: String) -> anyhow::Result<()> {
+// returns whether the trade was added
+async fn add_with_import_confirmation(
+    ctx: &mut ImportContext,
+    trade: Trade,
+    id: String,
+) -> anyhow::Result<bool> {
     let existing_trade = find_similar_trade(&trade).await?;
 
     if let Some(existing_trade) = existing_trade {
-        if existing_trade.hash != hash_string(&format!("{}{}", &trade.broker, id.as_str()))
-            && require_import_confirmation(&trade)?
-        {
-            add_trade_to_db(trade, Some(id)).await?;
+        if existing_trade.hash == trade_hash(&trade.broker, &id) {
+            ctx.report.count_duplicate(RecordKind::Trade);
+            Ok(false)
+        } else if !ctx.interactive {
+            warn!(
+                "A similar trade was found with a different ID, skipping it: {:?}",
+                trade
+            );
+            ctx.skip();
+            Ok(false)
+        } else if require_import_confirmation(&trade)? {
+            ctx.add_trade(trade, Some(id)).await
+        } else {
+            ctx.skip();
+            Ok(false)
         }
     } else {
-        add_trade_to_db(trade, Some(id)).await?;
+        ctx.add_trade(trade, Some(id)).await
     }
-    Ok(())
 }
 
-pub async fn extract_trade_republic_record(text: &str) -> anyhow::Result<()> {
+pub async fn extract_trade_republic_record(
+    text: &str,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
+    let locale = detect_locale(text);
     // TR supports decimialization of up to 6 decimals
-    let units_default_regex = r"\d+(,|\.)*\d{0,6}\sStk.";
+    let units_regex = format!(r"({NUMBER})\s{}", locale.units);
+    // date and time of executions, e.g. "07.03.2024 um 10:00" or "07.03.2024, at 10:00"
+    let execution_date_regex = format!(
+        r"({DATE}),?\s(?:{})\s(\d{{2}}:\d{{2}})",
+        locale.time_connector
+    );
+    let external_costs_regex = format!(r"(?:{})\s(-?{NUMBER})\sEUR", locale.external_costs);
     let broker = "Trade Republic".to_string();
-    match detect_record_type(text)? {
+    log::debug!("Trade Republic statement in {}", locale.language);
+
+    match detect_record_type(text, locale)? {
         RecordType::InvestmentPlanExecution => {
-            let date_match = return_first_match(r"(..\...\.....)", text)?;
-            let date_string_to_parse = format!("{date_match} 16:00:00");
-            let date = parse_timestamp(&date_string_to_parse)?;
+            let date_match = return_first_match(DATE, text)?;
+            let date = parse_date(&date_match, "16:00")?;
 
             let isin =
                 return_first_match(r"\b[a-zA-Z]{2}\s*[0-9a-zA-Z]{9}[0-9](?![0-9a-zA-Z-])", text)?;
-            let units = return_first_match(units_default_regex, text)?
-                .replace(" Stk.", "")
-                .replace(',', ".")
-                .parse::<Decimal>()?;
-
-            let mut avg_price_per_unit = return_first_match(r"Stk\.\s\d+,\d{1,}\sEUR", text)?
-                .replace(" EUR", "")
-                .replace("Stk.", "")
-                .replace(',', ".")
-                .replace(" ", "")
-                .parse::<Decimal>()?;
-
-            let id = return_first_match(r"AUSFUHRUNG\s*(\S+)", text)?
-                .replace("AUSFUHRUNG", "")
-                .replace(" ", "")
-                .replace("\n", "");
+            let units = parse_number(locale, &return_first_captures(&units_regex, text)?[0])?;
+
+            let mut avg_price_per_unit = parse_number(
+                locale,
+                &return_first_captures(&format!(r"{}\s({NUMBER})\sEUR", locale.units), text)?[0],
+            )?;
+
+            let id =
+                return_first_captures(&format!(r"{}\s*(\S+)", locale.execution), text)?[0].clone();
 
             // set price per unit to 0 if saveback
             let is_saveback = does_match_exist("Saveback", text);
@@ -171,37 +406,51 @@ pub async fn extract_trade_republic_record(text: &str) -> anyhow::Result<()> {
                 withholding_tax: dec!(0.0),
                 withholding_tax_currency: "EUR".to_string(),
             };
-            add_with_import_confirmation(trade, id).await?;
+            add_with_import_confirmation(ctx, trade, id).await?;
         }
         RecordType::Liquidation => {
-            let date_match = return_first_match(r"(..\...\.....)", text)?;
+            let date_match = return_first_match(DATE, text)?;
             // liquidations do not have an hourly time stamp
-            let date_string_to_parse = format!("{date_match} 16:00:00");
-            let date = parse_timestamp(&date_string_to_parse)?;
+            let date = parse_date(&date_match, "16:00")?;
 
-            let isin = choose_match_from_regex(r"[A-Z]{2}[A-Z0-9]{9}[0-9]", text)?;
+            let isin = choose_match_from_regex(r"[A-Z]{2}[A-Z0-9]{9}[0-9]", text, ctx.interactive)?;
 
-            let is_bond_liquidation = !does_match_exist("Stk.", text);
+            let is_bond_liquidation = !does_match_exist(locale.units, text);
 
             let units;
             if is_bond_liquidation {
-                units = get_positions_for_isin(&isin, None).await?;
+                // bonds are redeemed in full, i.e. all units held at that date
+                units = get_positions_for_isin(&isin, Some(date)).await?;
                 if units == dec!(0) {
                     return Ok(());
                 }
             } else {
-                units = return_first_match(units_default_regex, text)?
-                    .replace(" Stk.", "")
-                    .replace(',', ".")
-                    .parse::<Decimal>()?;
+                units = parse_number(locale, &return_first_captures(&units_regex, text)?[0])?;
             }
 
-            let avg_price_per_unit =
-                return_first_match(r"(\d{1,3}(?:[.,]\d{3})*(?:,\d+)?\s*EUR)", text)?
-                    .replace(" EUR", "")
-                    .replace(',', ".")
-                    .parse::<Decimal>()?
-                    / units;
+            let amount = parse_number(
+                locale,
+                &return_first_captures(&format!(r"({NUMBER})\s*EUR"), text)?[0],
+            )?;
+            let mut avg_price_per_unit = amount / units;
+
+            // bonds are redeemed at par, anything paid on top is the last coupon
+            if is_bond_liquidation && avg_price_per_unit > BOND_PAR_PRICE {
+                avg_price_per_unit = BOND_PAR_PRICE;
+                let coupon = InterestPayment {
+                    date,
+                    amount: amount - units * BOND_PAR_PRICE,
+                    broker: broker.clone(),
+                    principal: bond_principal(&isin),
+                    // TR only supports EUR
+                    currency: "EUR".to_string(),
+                    amount_eur: amount - units * BOND_PAR_PRICE,
+                    // TR doesn't withhold any tax in AT
+                    withholding_tax: dec!(0.0),
+                    withholding_tax_currency: "EUR".to_string(),
+                };
+                ctx.add_interest(coupon, None).await?;
+            }
 
             let trade = Trade {
                 broker,
@@ -225,62 +474,61 @@ pub async fn extract_trade_republic_record(text: &str) -> anyhow::Result<()> {
                 withholding_tax: dec!(0.0),
                 withholding_tax_currency: "EUR".to_string(),
             };
-            add_trade_to_db(trade, None).await?;
+            ctx.add_trade(trade, None).await?;
         }
         RecordType::BondTrade => {
-            let date_match_regex = r"(..\...\.....(?:,)? um ..:..)";
-            let does_date_match_exist = does_match_exist(date_match_regex, text);
             //skip file if it's not a valid trade confirmation
-            if !does_date_match_exist {
+            if !does_match_exist(&execution_date_regex, text) {
                 return Ok(());
             }
-            let date_match = return_first_match(date_match_regex, text)?
-                .replace(", um", "")
-                .replace(" um", "");
-
-            let date_string_to_parse = format!("{date_match}:00");
-            let date = parse_timestamp(&date_string_to_parse)?;
+            let date_match = return_first_captures(&execution_date_regex, text)?;
+            let date = parse_date(&date_match[0], &date_match[1])?;
             let isin =
                 return_first_match(r"\b[a-zA-Z]{2}\s*[0-9a-zA-Z]{9}[0-9](?![0-9a-zA-Z-])", text)?;
 
-            let avg_price_per_unit = return_first_match(r"(\d+,\d+\s*%)", text)?
-                .replace(" %", "")
-                .replace(',', ".")
-                .parse::<Decimal>()?;
+            let avg_price_per_unit = parse_number(
+                locale,
+                &return_first_captures(&format!(r"({NUMBER})\s*%"), text)?[0],
+            )?;
 
-            let units = return_first_match(
-                r"\d+,\d+ EUR",
-                &return_first_match(r"(\d+,\d+\s*%)[\s\S]*?(\d+(?:\.\d+)? EUR)", text)?,
-            )?
-            .replace(" EUR", "")
-            .replace(',', ".")
-            .parse::<Decimal>()?
-                / avg_price_per_unit;
+            // the amount is the first one after the price in percent, the nominal is derived from
+            // it since it isn't always stated
+            let amount = parse_number(
+                locale,
+                &return_first_captures(&format!(r"%\D*?({NUMBER})\s?EUR"), text)?[0],
+            )?;
+            let units = bond_units(amount / avg_price_per_unit * dec!(100));
+
+            let accrued_interest_regex =
+                format!(r"(?:{})\D*?(-?{NUMBER})\s?EUR", locale.accrued_interest);
+            let accrued_interest = if does_match_exist(&accrued_interest_regex, text) {
+                parse_number(
+                    locale,
+                    &return_first_captures(&accrued_interest_regex, text)?[0],
+                )?
+            } else {
+                dec!(0.0)
+            };
 
             // get direction
-            let direction =
-                if does_match_exist(r"\sKauf", text) || does_match_exist(r"\Sparplan", text) {
-                    "Buy"
-                } else {
-                    "Sell"
-                };
+            let direction = if does_match_exist(&format!(r"\s(?:{})", locale.buy), text) {
+                "Buy"
+            } else {
+                "Sell"
+            };
 
             let mut fees = dec!(0.0);
-            if does_match_exist(r"Fremdkostenzuschlag", text) {
-                fees = return_first_match(r"Fremdkostenzuschlag\s(-?\d+,\d{2})\sEUR", text)?
-                    .replace("Fremdkostenzuschlag", "")
-                    .replace("-", "")
-                    .replace(" EUR", "")
-                    .replace(" ", "")
-                    .replace(',', ".")
-                    .parse::<Decimal>()?;
+            if does_match_exist(&external_costs_regex, text) {
+                fees = parse_number(
+                    locale,
+                    &return_first_captures(&external_costs_regex, text)?[0],
+                )?
+                .abs();
             };
 
-            let id = return_first_match(r"AUSFUHRUNG\s*(\S+)|Ausfuhrung\s*(\S+))", text)?
-                .replace("AUSFUHRUNG", "")
-                .replace("AusfUhrung", "")
-                .replace(" ", "")
-                .replace("\n", "");
+            let id =
+                return_first_captures(&format!(r"(?i)(?:{})\s*(\S+)", locale.execution), text)?[0]
+                    .to_uppercase();
 
             let trade = Trade {
                 broker,
@@ -300,24 +548,60 @@ pub async fn extract_trade_republic_record(text: &str) -> anyhow::Result<()> {
                 withholding_tax: dec!(0.0),
                 withholding_tax_currency: "EUR".to_string(),
             };
-            add_with_import_confirmation(trade, id).await?;
+            let accrued_interest = accrued_interest_payment(&trade, accrued_interest);
+            if add_with_import_confirmation(ctx, trade, id.clone()).await?
+                && accrued_interest.amount != dec!(0.0)
+            {
+                ctx.add_interest(accrued_interest, Some(&format!("{}-ACCRUED", id)))
+                    .await?;
+            }
+        }
+        RecordType::Coupon => {
+            let date_match = return_first_match(DATE, text)?;
+            let date = parse_date(&date_match, "16:00")?;
+
+            let isin =
+                return_first_match(r"\b[a-zA-Z]{2}\s*[0-9a-zA-Z]{9}[0-9](?![0-9a-zA-Z-])", text)?;
+
+            let amount = parse_number(
+                locale,
+                &return_first_captures(
+                    &format!(r"EUR\s?(?:{})\s+({NUMBER})\s+EUR", locale.total),
+                    text,
+                )?[0],
+            )?;
+
+            let coupon = InterestPayment {
+                date,
+                amount,
+                broker: broker.clone(),
+                principal: bond_principal(&isin),
+                // Trade Republic only supports EUR
+                currency: "EUR".to_string(),
+                amount_eur: amount,
+                // TR doesn't withhold any tax in AT
+                withholding_tax: dec!(0.0),
+                withholding_tax_currency: "EUR".to_string(),
+            };
+
+            if ctx.add_interest(coupon.clone(), None).await? {
+                println!("💵 Coupon added: {:?}", coupon);
+            }
         }
         RecordType::Dividend => {
-            let date_match = return_first_match(r"(..\...\.....)", text)?;
-            let date_string_to_parse = format!("{date_match} 16:00:00");
-            let date = parse_timestamp(&date_string_to_parse)?;
+            let date_match = return_first_match(DATE, text)?;
+            let date = parse_date(&date_match, "16:00")?;
 
             let isin =
                 return_first_match(r"\b[a-zA-Z]{2}\s*[0-9a-zA-Z]{9}[0-9](?![0-9a-zA-Z-])", text)?;
 
-            let amount = return_first_match(r"EURGESAMT\s+([\d,]+)\s+EUR", text)?
-                .replace("EURGESAMT", "")
-                .replace(".", "")
-                .replace(",", ".")
-                .replace("EUR", "")
-                .replace("\n", "")
-                .replace(" ", "")
-                .parse::<Decimal>()?;
+            let amount = parse_number(
+                locale,
+                &return_first_captures(
+                    &format!(r"EUR\s?(?:{})\s+({NUMBER})\s+EUR", locale.total),
+                    text,
+                )?[0],
+            )?;
 
             let dividend = Dividend {
                 isin,
@@ -332,25 +616,18 @@ pub async fn extract_trade_republic_record(text: &str) -> anyhow::Result<()> {
                 withholding_tax_currency: "EUR".to_string(),
             };
 
-            if add_dividend_to_db(dividend.clone(), None).await? {
+            if ctx.add_dividend(dividend.clone(), None).await? {
                 println!("💵 Dividend added: {:?}", dividend);
             }
         }
 
         RecordType::EquityTrade => {
-            let date_match_regex = r"(..\...\.....(?:,)? um ..:..)";
-
-            let does_date_match_exist = does_match_exist(date_match_regex, text);
             //skip file if it's not a valid trade confirmation
-            if !does_date_match_exist {
+            if !does_match_exist(&execution_date_regex, text) {
                 return Ok(());
             }
-            let date_match = return_first_match(date_match_regex, text)?
-                .replace(", um", "")
-                .replace(" um", "");
-
-            let date_string_to_parse = format!("{date_match}:00");
-            let date = parse_timestamp(&date_string_to_parse)?;
+            let date_match = return_first_captures(&execution_date_regex, text)?;
+            let date = parse_date(&date_match[0], &date_match[1])?;
 
             let isin_match_regex = r"ISIN:\s([A-Z]{2}[0-9A-Z]{10})";
 
@@ -361,27 +638,14 @@ pub async fn extract_trade_republic_record(text: &str) -> anyhow::Result<()> {
 
             let isin = return_first_match(isin_match_regex, text)?.replace("ISIN: ", "");
 
-            let units_match = return_first_match(units_default_regex, text)?;
-            let units = units_match
-                .replace(" Stk.", "")
-                .replace(" EUR", "")
-                .replace(" ", "")
-                .replace('.', "")
-                .replace(',', ".")
-                .parse::<Decimal>()?;
-
-            let avg_price_per_unit =
-                return_first_match(r"Stk\.\s(\d{1,3}(?:\.\d{3})*,\d{1,3})", text)?
-                    .replace(" EUR", "")
-                    .replace("Stk.", "")
-                    .replace('.', "")
-                    .replace(',', ".")
-                    .replace(" ", "")
-                    .parse::<Decimal>()?;
-
-            let direction = if does_match_exist(r"\sKauf", text)
-                || does_match_exist(r"OrderKauf", text)
-                || does_match_exist(r"\sSparplan", text)
+            let units = parse_number(locale, &return_first_captures(&units_regex, text)?[0])?;
+
+            let avg_price_per_unit = parse_number(
+                locale,
+                &return_first_captures(&format!(r"{}\s({NUMBER})", locale.units), text)?[0],
+            )?;
+
+            let direction = if does_match_exist(&format!(r"(?:\s|Order)(?:{})", locale.buy), text)
                 || does_match_exist(r"(?i)\bbuy\b", text)
             {
                 "Buy"
@@ -390,21 +654,18 @@ pub async fn extract_trade_republic_record(text: &str) -> anyhow::Result<()> {
             };
 
             let mut fees = dec!(0.0);
-            if does_match_exist(r"Fremdkostenzuschlag", text) {
-                fees = return_first_match(r"Fremdkostenzuschlag\s(-?\d+,\d{2})\sEUR", text)?
-                    .replace("Fremdkostenzuschlag", "")
-                    .replace("-", "")
-                    .replace(" EUR", "")
-                    .replace(" ", "")
-                    .replace(',', ".")
-                    .parse::<Decimal>()?;
+            if does_match_exist(&external_costs_regex, text) {
+                fees = parse_number(
+                    locale,
+                    &return_first_captures(&external_costs_regex, text)?[0],
+                )?
+                .abs();
             };
 
-            let id = return_first_match(r"(?i)ausfuhrung\s*(\S+)", text)? // `(?i)` makes it case-insensitive
-                .split_whitespace() // Removes spaces and newlines
-                .last() // Extracts the captured value
-                .unwrap_or("")
-                .to_uppercase(); // Or `.to_lowercase()`, depending on your needs
+            // the ID is matched case-insensitively and stored upper case
+            let id =
+                return_first_captures(&format!(r"(?i)(?:{})\s*(\S+)", locale.execution), text)?[0]
+                    .to_uppercase();
 
             let trade = Trade {
                 broker,
@@ -424,20 +685,18 @@ pub async fn extract_trade_republic_record(text: &str) -> anyhow::Result<()> {
                 withholding_tax: dec!(0.0),
                 withholding_tax_currency: "EUR".to_string(),
             };
-            add_with_import_confirmation(trade, id).await?;
+            add_with_import_confirmation(ctx, trade, id).await?;
         }
         RecordType::InterestPayment => {
-            let date_match = return_first_match(r"zum (..\...\.....)", text)?.replace("zum ", "");
-            let date_string_to_parse = format!("{date_match} 16:00:00");
-            let date = parse_timestamp(&date_string_to_parse)?;
-
-            let amount = return_first_match(r"\d+,\d{2} EUR", text)?
-                .replace("BUCHUNG", "")
-                .replace(",", ".")
-                .replace("EUR", "")
-                .replace("\n", "")
-                .replace(" ", "")
-                .parse::<Decimal>()?;
+            let period_end_regex = format!(r"(?:{})\s({DATE})", locale.period_end);
+            let date_match = return_first_captures(&period_end_regex, text)?;
+            let date = parse_date(&date_match[0], "16:00")?;
+
+            // the first amount with cents is the interest paid
+            let amount = parse_number(
+                locale,
+                &return_first_captures(r"(\d+(?:[.,]\d{3})*[.,]\d{2}) EUR", text)?[0],
+            )?;
 
             let interest_payment = InterestPayment {
                 date,
@@ -452,15 +711,17 @@ pub async fn extract_trade_republic_record(text: &str) -> anyhow::Result<()> {
                 withholding_tax_currency: "EUR".to_string(),
             };
 
-            if add_interest_to_db(interest_payment.clone(), None).await? {
+            if ctx.add_interest(interest_payment.clone(), None).await? {
                 println!("💵 Interest payment added: {:?}", interest_payment);
             }
         }
         RecordType::PortfolioTransfer => {
-            info!("Portfolio transfer, skipping.")
+            info!("Portfolio transfer, skipping.");
+            ctx.skip();
         }
         RecordType::Unmatched => {
-            info!("No valid statement found, skipping.")
+            info!("No valid statement found, skipping.");
+            ctx.skip();
         }
     }
     Ok(())
@@ -486,19 +747,37 @@ fn find_column_index(
 enum CsvRecordType {
     EquityTrade,
     Dividend,
+    Coupon,
     InterestPayment,
     Liquidation,
+    Redemption,
     TaxOptimization,
     Skip,
     Unmatched,
 }
 
-fn detect_csv_record_type(csv_type: &str, category: &str, asset_class: Option<&str>) -> CsvRecordType {
-    // Skip crypto trades - they use ticker symbols instead of proper ISINs
+fn detect_csv_record_type(
+    csv_type: &str,
+    category: &str,
+    asset_class: Option<&str>,
+) -> CsvRecordType {
+    // Crypto is identified by its symbol instead of an ISIN, only trades are imported
     if asset_class == Some("CRYPTO") {
-        return CsvRecordType::Skip;
+        return match (csv_type, category) {
+            ("BUY" | "bUY" | "BUY savings" | "SELL" | "sELL", "TRADING") => {
+                CsvRecordType::EquityTrade
+            }
+            _ => CsvRecordType::Skip,
+        };
     }
     
+    // distributions of bonds are their coupons
+    if asset_class == Some("BOND")
+        && matches!((csv_type, category), ("DIVIDEND" | "DISTRIBUTION", "CASH"))
+    {
+        return CsvRecordType::Coupon;
+    }
+
     match (csv_type, category) {
         // Trading operations
         ("BUY" | "bUY" | "BUY savings", "TRADING") => CsvRecordType::EquityTrade,
@@ -525,19 +804,36 @@ fn detect_csv_record_type(csv_type: &str, category: &str, asset_class: Option<&s
         ("GIFT", "CASH") => CsvRecordType::Skip,
         ("TRANSFER_INSTANT_INBOUND" | "TRANSFER_INSTANT_OUTBOUND" | "TRANSFER_INBOUND" | "TRANSFER_OUTBOUND" | "VIBAN_TRANSFER_INBOUND", "CASH") => CsvRecordType::Skip,
         ("FEE", "CASH") => CsvRecordType::Skip,
-        ("FINAL_MATURITY", "CASH") => CsvRecordType::Skip,
+        // Bonds paid back at maturity
+        ("FINAL_MATURITY", "CASH") => CsvRecordType::Redemption,
         _ => CsvRecordType::Unmatched,
     }
 }
 
+// Crypto is listed either by its symbol or a pseudo ISIN embedding it, e.g. XF000BTC0017
+fn tr_crypto_identifier(symbol: &str) -> String {
+    let symbol = match symbol.strip_prefix("XF000") {
+        Some(pseudo_isin) if symbol.len() == 12 => {
+            pseudo_isin.trim_end_matches(|c: char| c.is_ascii_digit())
+        }
+        _ => symbol,
+    };
+    crypto_identifier(symbol)
+}
+
 fn parse_csv_decimal(value: &str) -> anyhow::Result<Decimal> {
     if value.is_empty() {
         return Ok(dec!(0));
     }
-    value.parse::<Decimal>().map_err(|e| anyhow!("Failed to parse decimal '{}': {}", value, e))
+    value
+        .parse::<Decimal>()
+        .map_err(|e| anyhow!("Failed to parse decimal '{}': {}", value, e))
 }
 
-pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::Result<()> {
+pub async fn extract_trade_republic_csv_record(
+    file_content: &[u8],
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
     let broker = "Trade Republic".to_string();
     let cursor = Cursor::new(file_content);
     let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(cursor);
@@ -579,13 +875,16 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
     let mut liquidation_duplicates = 0;
     let mut skip_count = 0;
     let mut unmatched_count = 0;
-    
+
     for result in rdr.records() {
         record_count += 1;
+        // the header occupies the first line
+        ctx.set_line(record_count + 1);
         let record = match result {
             Ok(r) => r,
             Err(e) => {
                 log::error!("Failed to parse CSV record {}: {:?}", record_count, e);
+                ctx.record_error(&e.into());
                 continue;
             }
         };
@@ -599,9 +898,9 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
         match &record_type {
             CsvRecordType::EquityTrade => trade_count += 1,
             CsvRecordType::Dividend => dividend_count += 1,
-            CsvRecordType::InterestPayment => interest_count += 1,
+            CsvRecordType::InterestPayment | CsvRecordType::Coupon => interest_count += 1,
             CsvRecordType::TaxOptimization => tax_opt_count += 1,
-            CsvRecordType::Liquidation => liquidation_count += 1,
+            CsvRecordType::Liquidation | CsvRecordType::Redemption => liquidation_count += 1,
             CsvRecordType::Skip => skip_count += 1,
             CsvRecordType::Unmatched => {
                 unmatched_count += 1;
@@ -617,17 +916,22 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let date = match parse_timestamp(datetime_str) {
                     Ok(d) => d,
                     Err(e) => {
-                        log::error!("Failed to parse date '{}' at record {}: {:?}", datetime_str, record_count, e);
+                        ctx.record_field_error("date", datetime_str, &e);
                         continue;
                     }
                 };
                 
-                let isin = record[isin_idx].to_string();
+                let isin = if asset_class == Some("CRYPTO") {
+                    tr_crypto_identifier(&record[isin_idx])
+                } else {
+                    record[isin_idx].to_string()
+                };
                 let shares_str = shares_idx.and_then(|idx| record.get(idx)).unwrap_or("");
                 let shares = match parse_csv_decimal(shares_str) {
+                    // bonds are kept in the units of the export, like the rows of earlier imports
                     Ok(s) => s.abs(),
                     Err(e) => {
-                        log::error!("Failed to parse shares '{}' at record {}: {:?}", shares_str, record_count, e);
+                        ctx.record_field_error("shares", shares_str, &e);
                         continue;
                     }
                 };
@@ -636,7 +940,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let avg_price_per_unit = match parse_csv_decimal(price_str) {
                     Ok(p) => p.abs(),
                     Err(e) => {
-                        log::error!("Failed to parse price '{}' at record {}: {:?}", price_str, record_count, e);
+                        ctx.record_field_error("price", price_str, &e);
                         continue;
                     }
                 };
@@ -645,7 +949,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let amount = match parse_csv_decimal(amount_str) {
                     Ok(a) => a,
                     Err(e) => {
-                        log::error!("Failed to parse amount '{}' at record {}: {:?}", amount_str, record_count, e);
+                        ctx.record_field_error("amount", amount_str, &e);
                         continue;
                     }
                 };
@@ -654,7 +958,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let fee = match parse_csv_decimal(fee_str) {
                     Ok(f) => f.abs(),
                     Err(e) => {
-                        log::error!("Failed to parse fee '{}' at record {}: {:?}", fee_str, record_count, e);
+                        ctx.record_field_error("fee", fee_str, &e);
                         continue;
                     }
                 };
@@ -663,7 +967,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let withholding_tax = match parse_csv_decimal(tax_str) {
                     Ok(t) => t.abs(),
                     Err(e) => {
-                        log::error!("Failed to parse tax '{}' at record {}: {:?}", tax_str, record_count, e);
+                        ctx.record_field_error("tax", tax_str, &e);
                         continue;
                     }
                 };
@@ -679,6 +983,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                     Some("FUND") => "Equity".to_string(),
                     Some("DERIVATIVE") => "Derivative".to_string(),
                     Some("BOND") => "Bond".to_string(),
+                    Some("CRYPTO") => "Crypto".to_string(),
                     _ => "Equity".to_string(),
                 };
                 
@@ -701,7 +1006,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 };
                 
                 trade_insert_attempted += 1;
-                match add_csv_trade_with_duplicate_check(trade, transaction_id).await {
+                match add_csv_trade_with_duplicate_check(ctx, trade, transaction_id).await {
                     Ok(true) => {
                         trade_insert_succeeded += 1;
                     }
@@ -710,6 +1015,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                     }
                     Err(e) => {
                         log::error!("Failed to add trade at record {}: {:?}", record_count, e);
+                        ctx.record_error(&e);
                     }
                 }
             }
@@ -718,7 +1024,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let date = match parse_timestamp(datetime_str) {
                     Ok(d) => d,
                     Err(e) => {
-                        log::error!("Failed to parse date '{}' at record {}: {:?}", datetime_str, record_count, e);
+                        ctx.record_field_error("date", datetime_str, &e);
                         continue;
                     }
                 };
@@ -730,7 +1036,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let amount = match parse_csv_decimal(amount_str) {
                     Ok(a) => a.abs(),
                     Err(e) => {
-                        log::error!("Failed to parse amount '{}' at record {}: {:?}", amount_str, record_count, e);
+                        ctx.record_field_error("amount", amount_str, &e);
                         continue;
                     }
                 };
@@ -739,7 +1045,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let withholding_tax = match parse_csv_decimal(tax_str) {
                     Ok(t) => t.abs(),
                     Err(e) => {
-                        log::error!("Failed to parse tax '{}' at record {}: {:?}", tax_str, record_count, e);
+                        ctx.record_field_error("tax", tax_str, &e);
                         continue;
                     }
                 };
@@ -752,7 +1058,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                         match parse_csv_decimal(orig_amt) {
                             Ok(orig_amount) => (orig_curr.to_string(), orig_amount),
                             Err(e) => {
-                                log::error!("Failed to parse original amount '{}' at record {}: {:?}", orig_amt, record_count, e);
+                                ctx.record_field_error("original amount", orig_amt, &e);
                                 continue;
                             }
                         }
@@ -775,7 +1081,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 };
                 
                 let transaction_id = record[transaction_id_idx].to_string();
-                match add_dividend_to_db(dividend, Some(&transaction_id)).await {
+                match ctx.add_dividend(dividend, Some(&transaction_id)).await {
                     Ok(true) => {
                         dividend_inserted += 1;
                         println!("💵 Dividend added: ISIN {} on {}", isin, date);
@@ -783,6 +1089,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                     Ok(false) => dividend_duplicates += 1,
                     Err(e) => {
                         log::error!("Failed to add dividend at record {}: {:?}", record_count, e);
+                        ctx.record_error(&e);
                     }
                 }
             }
@@ -791,7 +1098,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let date = match parse_timestamp(datetime_str) {
                     Ok(d) => d,
                     Err(e) => {
-                        log::error!("Failed to parse date '{}' at record {}: {:?}", datetime_str, record_count, e);
+                        ctx.record_field_error("date", datetime_str, &e);
                         continue;
                     }
                 };
@@ -800,7 +1107,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let amount = match parse_csv_decimal(amount_str) {
                     Ok(a) => a.abs(),
                     Err(e) => {
-                        log::error!("Failed to parse amount '{}' at record {}: {:?}", amount_str, record_count, e);
+                        ctx.record_field_error("amount", amount_str, &e);
                         continue;
                     }
                 };
@@ -809,7 +1116,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let withholding_tax = match parse_csv_decimal(tax_str) {
                     Ok(t) => t.abs(),
                     Err(e) => {
-                        log::error!("Failed to parse tax '{}' at record {}: {:?}", tax_str, record_count, e);
+                        ctx.record_field_error("tax", tax_str, &e);
                         continue;
                     }
                 };
@@ -833,14 +1140,21 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 };
                 
                 let transaction_id = record[transaction_id_idx].to_string();
-                match add_interest_to_db(interest_payment, Some(&transaction_id)).await {
+                match ctx
+                    .add_interest(interest_payment, Some(&transaction_id))
+                    .await
+                {
                     Ok(true) => {
                         interest_inserted += 1;
-                        println!("💵 Interest payment added: {} EUR on {} (principal: {})", amount, date, principal);
+                        println!(
+                            "💵 Interest payment added: {} EUR on {} (principal: {})",
+                            amount, date, principal
+                        );
                     }
                     Ok(false) => interest_duplicates += 1,
                     Err(e) => {
                         log::error!("Failed to add interest at record {}: {:?}", record_count, e);
+                        ctx.record_error(&e);
                     }
                 }
             }
@@ -850,7 +1164,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let date = match parse_timestamp(datetime_str) {
                     Ok(d) => d,
                     Err(e) => {
-                        log::error!("Failed to parse date '{}' at record {}: {:?}", datetime_str, record_count, e);
+                        ctx.record_field_error("date", datetime_str, &e);
                         continue;
                     }
                 };
@@ -861,7 +1175,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let shares = match parse_csv_decimal(shares_str) {
                     Ok(s) => s.abs(),
                     Err(e) => {
-                        log::error!("Failed to parse shares '{}' at record {}: {:?}", shares_str, record_count, e);
+                        ctx.record_field_error("shares", shares_str, &e);
                         continue;
                     }
                 };
@@ -870,7 +1184,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let avg_price_per_unit = match parse_csv_decimal(price_str) {
                     Ok(p) => p.abs(),
                     Err(e) => {
-                        log::error!("Failed to parse price '{}' at record {}: {:?}", price_str, record_count, e);
+                        ctx.record_field_error("price", price_str, &e);
                         continue;
                     }
                 };
@@ -879,7 +1193,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let fee = match parse_csv_decimal(fee_str) {
                     Ok(f) => f.abs(),
                     Err(e) => {
-                        log::error!("Failed to parse fee '{}' at record {}: {:?}", fee_str, record_count, e);
+                        ctx.record_field_error("fee", fee_str, &e);
                         continue;
                     }
                 };
@@ -907,7 +1221,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 };
                 
                 let transaction_id = record[transaction_id_idx].to_string();
-                match add_csv_trade_with_duplicate_check(trade, transaction_id).await {
+                match add_csv_trade_with_duplicate_check(ctx, trade, transaction_id).await {
                     Ok(true) => {
                         liquidation_inserted += 1;
                         println!("✅ Liquidation trade added: {} {} on {}", shares, isin, date);
@@ -917,6 +1231,160 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                     }
                     Err(e) => {
                         log::error!("Failed to add liquidation trade at record {}: {:?}", record_count, e);
+                        ctx.record_error(&e);
+                    }
+                }
+            }
+            CsvRecordType::Coupon => {
+                let datetime_str = &record[datetime_idx];
+                let date = match parse_timestamp(datetime_str) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        ctx.record_field_error("date", datetime_str, &e);
+                        continue;
+                    }
+                };
+
+                let isin = record[isin_idx].to_string();
+
+                let amount_str = &record[amount_idx];
+                let amount = match parse_csv_decimal(amount_str) {
+                    Ok(a) => a.abs(),
+                    Err(e) => {
+                        ctx.record_field_error("amount", amount_str, &e);
+                        continue;
+                    }
+                };
+
+                let tax_str = &record[tax_idx];
+                let withholding_tax = match parse_csv_decimal(tax_str) {
+                    Ok(t) => t.abs(),
+                    Err(e) => {
+                        ctx.record_field_error("tax", tax_str, &e);
+                        continue;
+                    }
+                };
+
+                let coupon = InterestPayment {
+                    date,
+                    amount,
+                    broker: broker.clone(),
+                    principal: bond_principal(&isin),
+                    currency: "EUR".to_string(),
+                    amount_eur: amount,
+                    withholding_tax,
+                    withholding_tax_currency: "EUR".to_string(),
+                };
+
+                let transaction_id = record[transaction_id_idx].to_string();
+                match ctx.add_interest(coupon, Some(&transaction_id)).await {
+                    Ok(true) => {
+                        interest_inserted += 1;
+                        println!("💵 Coupon added: {} EUR for {} on {}", amount, isin, date);
+                    }
+                    Ok(false) => interest_duplicates += 1,
+                    Err(e) => {
+                        log::error!("Failed to add coupon at record {}: {:?}", record_count, e);
+                        ctx.record_error(&e);
+                    }
+                }
+            }
+            CsvRecordType::Redemption => {
+                // the bond is paid back in full, i.e. all units held at maturity are sold
+                let datetime_str = &record[datetime_idx];
+                let date = match parse_timestamp(datetime_str) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        ctx.record_field_error("date", datetime_str, &e);
+                        continue;
+                    }
+                };
+
+                let isin = record[isin_idx].to_string();
+
+                let amount_str = &record[amount_idx];
+                let amount = match parse_csv_decimal(amount_str) {
+                    Ok(a) => a.abs(),
+                    Err(e) => {
+                        ctx.record_field_error("amount", amount_str, &e);
+                        continue;
+                    }
+                };
+
+                let units = get_positions(Some(date), Some(&isin))
+                    .await?
+                    .first()
+                    .map(|position| position.units)
+                    .unwrap_or(dec!(0));
+                if units <= dec!(0) {
+                    warn!(
+                        "No units of {} held at its maturity on {}, skipping redemption.",
+                        isin, date
+                    );
+                    ctx.skip();
+                    continue;
+                }
+
+                let transaction_id = record[transaction_id_idx].to_string();
+
+                // bonds are redeemed at par, anything paid on top is the last coupon
+                let mut avg_price_per_unit = amount / units;
+                if avg_price_per_unit > BOND_PAR_PRICE {
+                    avg_price_per_unit = BOND_PAR_PRICE;
+                    let coupon = InterestPayment {
+                        date,
+                        amount: amount - units * BOND_PAR_PRICE,
+                        broker: broker.clone(),
+                        principal: bond_principal(&isin),
+                        currency: "EUR".to_string(),
+                        amount_eur: amount - units * BOND_PAR_PRICE,
+                        withholding_tax: dec!(0.0),
+                        withholding_tax_currency: "EUR".to_string(),
+                    };
+                    if let Err(e) = ctx
+                        .add_interest(coupon, Some(&format!("{}-COUPON", transaction_id)))
+                        .await
+                    {
+                        log::error!(
+                            "Failed to add final coupon at record {}: {:?}",
+                            record_count,
+                            e
+                        );
+                        ctx.record_error(&e);
+                    }
+                }
+
+                let trade = Trade {
+                    broker: broker.clone(),
+                    date,
+                    isin: isin.clone(),
+                    avg_price_per_unit,
+                    eur_avg_price_per_unit: avg_price_per_unit,
+                    units,
+                    direction: "Sell".to_string(),
+                    security_type: "Bond".to_string(),
+                    currency: "EUR".to_string(),
+                    date_added: Utc::now(),
+                    fees: dec!(0.0),
+                    withholding_tax: dec!(0.0),
+                    withholding_tax_currency: "EUR".to_string(),
+                };
+
+                match add_csv_trade_with_duplicate_check(ctx, trade, transaction_id).await {
+                    Ok(true) => {
+                        liquidation_inserted += 1;
+                        println!("✅ Bond redemption added: {} {} on {}", units, isin, date);
+                    }
+                    Ok(false) => {
+                        liquidation_duplicates += 1;
+                    }
+                    Err(e) => {
+                        log::error!(
+                            "Failed to add bond redemption at record {}: {:?}",
+                            record_count,
+                            e
+                        );
+                        ctx.record_error(&e);
                     }
                 }
             }
@@ -925,21 +1393,21 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                 let date = match parse_timestamp(datetime_str) {
                     Ok(d) => d,
                     Err(e) => {
-                        log::error!("Failed to parse date '{}' at record {}: {:?}", datetime_str, record_count, e);
+                        ctx.record_field_error("date", datetime_str, &e);
                         continue;
                     }
                 };
-                
+
                 // Tax optimization amount - negative means additional tax paid, positive means tax refund
                 let amount_str = &record[amount_idx];
                 let amount = match parse_csv_decimal(amount_str) {
                     Ok(a) => a,
                     Err(e) => {
-                        log::error!("Failed to parse amount '{}' at record {}: {:?}", amount_str, record_count, e);
+                        ctx.record_field_error("amount", amount_str, &e);
                         continue;
                     }
                 };
-                
+
                 let tax_str = &record[tax_idx];
                 let _withholding_tax = match parse_csv_decimal(tax_str) {
                     Ok(t) => t,
@@ -948,7 +1416,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                         dec!(0)
                     }
                 };
-                
+
                 // Determine tax type from description or name field
                 let description = name_idx.and_then(|idx| record.get(idx)).map(|s| s.to_string());
                 
@@ -980,7 +1448,7 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                     transaction_id: Some(transaction_id.clone()),
                 };
                 
-                match add_tax_optimization_to_db(tax_optimization).await {
+                match ctx.add_tax_optimization(tax_optimization).await {
                     Ok(true) => {
                         tax_opt_inserted += 1;
                         println!("📝 Tax optimization added: {} EUR (type: {}) on {} - ID: {}", amount, tax_type, date, transaction_id);
@@ -988,13 +1456,14 @@ pub async fn extract_trade_republic_csv_record(file_content: &[u8]) -> anyhow::R
                     Ok(false) => tax_opt_duplicates += 1,
                     Err(e) => {
                         log::error!("Failed to add tax optimization at record {}: {:?}", record_count, e);
+                        ctx.record_error(&e);
                     }
                 }
             }
-            CsvRecordType::Skip => {
+            CsvRecordType::Skip | CsvRecordType::Unmatched => {
                 // Skip cash transfers, bonuses, compensations, etc.
+                ctx.skip();
             }
-            CsvRecordType::Unmatched => {}
         }
     }
     
@@ -7,17 +7,13 @@ use chrono::{NaiveDate, Utc};
 use csv::ReaderBuilder;
 
 use crate::{
-    database::{
-        models::{
-            dividend::Dividend, fx_conversion::FxConversion, interest::InterestPayment,
-            trade::Trade,
-        },
-        queries::{
-            composite::add_trade_to_db, dividend::add_dividend_to_db,
-            fx_conversion::add_fx_conversion_to_db, interest::add_interest_to_db,
-        },
+    database::models::{
+        dividend::Dividend, fx_conversion::FxConversion, interest::InterestPayment, trade::Trade,
+    },
+    services::{
+        importers::context::ImportContext, market_data::fx_rates::convert_amount,
+        parsers::parse_timestamp,
     },
-    services::{market_data::fx_rates::convert_amount, parsers::parse_timestamp},
 };
 
 enum RecordType {
@@ -62,7 +58,10 @@ fn find_column_index(
     }
 }
 
-pub async fn extract_trading212_record(file_content: &[u8]) -> anyhow::Result<()> {
+pub async fn extract_trading212_record(
+    file_content: &[u8],
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
     let broker = "Trading212".to_string();
 
     let cursor = Cursor::new(file_content);
@@ -99,7 +98,8 @@ pub async fn extract_trading212_record(file_content: &[u8]) -> anyhow::Result<()
     let currency_conversion_to_amount_idx =
         find_column_index(&headers, "Currency conversion to amount", false)?;
 
-    for result in rdr.records() {
+    for (index, result) in rdr.records().enumerate() {
+        ctx.set_line(index as u64 + 1);
         let record = result?;
         let action = &record[action_idx];
 
@@ -119,7 +119,7 @@ pub async fn extract_trading212_record(file_content: &[u8]) -> anyhow::Result<()
                         .map_or(dec!(0), |value| value.parse::<Decimal>().unwrap_or(dec!(0))),
                     withholding_tax_currency: record[withholding_tax_currency_idx].to_string(),
                 };
-                if add_dividend_to_db(dividend.clone(), None).await? {
+                if ctx.add_dividend(dividend.clone(), None).await? {
                     println!("💵 Dividend added: {:?}", dividend);
                 }
             }
@@ -141,7 +141,7 @@ pub async fn extract_trading212_record(file_content: &[u8]) -> anyhow::Result<()
                     date_added: Utc::now(),
                     fees: record[fees_idx].parse::<Decimal>().unwrap_or(dec!(-0.0)) * -dec!(1.0),
                 };
-                add_fx_conversion_to_db(fx_conversion).await?;
+                ctx.add_fx_conversion(fx_conversion).await?;
             }
             RecordType::EquityTrade => {
                 let trade = Trade {
@@ -171,7 +171,8 @@ pub async fn extract_trading212_record(file_content: &[u8]) -> anyhow::Result<()
                         .unwrap_or(dec!(0.0)),
                     withholding_tax_currency: record[withholding_tax_idx].to_string(),
                 };
-                add_trade_to_db(trade, Some(record[id_idx].to_string())).await?;
+                ctx.add_trade(trade, Some(record[id_idx].to_string()))
+                    .await?;
             }
             RecordType::CashInterest => {
                 let amount = if record[currency_total_idx].to_string() == "EUR" {
@@ -203,7 +204,7 @@ pub async fn extract_trading212_record(file_content: &[u8]) -> anyhow::Result<()
                         .unwrap_or(dec!(0.0)),
                     withholding_tax_currency: record[withholding_tax_currency_idx].to_string(),
                 };
-                if add_interest_to_db(interest_payment.clone(), None).await? {
+                if ctx.add_interest(interest_payment.clone(), None).await? {
                     println!("💵 Interest payment added: {:?}", interest_payment);
                 }
             }
@@ -238,12 +239,12 @@ pub async fn extract_trading212_record(file_content: &[u8]) -> anyhow::Result<()
                         .unwrap_or(dec!(0.0)),
                     withholding_tax_currency: record[withholding_tax_currency_idx].to_string(),
                 };
-                if add_interest_to_db(interest_payment.clone(), None).await? {
+                if ctx.add_interest(interest_payment.clone(), None).await? {
                     println!("💵 Interest payment added: {:?}", interest_payment);
                 }
             }
-            RecordType::CashTransfer => continue,
-            RecordType::Unmatched => continue,
+            RecordType::CashTransfer => ctx.skip(),
+            RecordType::Unmatched => ctx.skip(),
         }
     }
 
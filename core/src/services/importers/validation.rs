@@ -0,0 +1,449 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::{
+    database::{
+        models::{
+            crypto_swap::CryptoSwap,
+            derivative::{Derivative, OptionExercise},
+            dividend::Dividend,
+            fund_report::FundTaxReport,
+            fx_conversion::FxConversion,
+            interest::InterestPayment,
+            listing_change::ListingChange,
+            tax_optimization::TaxOptimization,
+            trade::Trade,
+        },
+        queries::instrument::get_instrument_by_id,
+    },
+    services::instruments::{
+        crypto::is_crypto_identifier,
+        derivatives::{is_derivative_identifier, is_option_identifier},
+        stock_splits::StockSplit,
+    },
+};
+
+use super::errors::ImportError;
+
+// active ISO 4217 codes, plus GBX as LSE listings are quoted in pence
+const CURRENCY_CODES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT", "BHD",
+    "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD", "CDF", "CHF",
+    "CLP", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN",
+    "ETB", "EUR", "FJD", "FKP", "GBP", "GBX", "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD",
+    "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR", "IQD", "IRR", "ISK", "JMD", "JOD", "JPY",
+    "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD",
+    "LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK",
+    "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK",
+    "PHP", "PKR", "PLN", "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG",
+    "SEK", "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SYP", "SZL", "THB", "TJS", "TMT",
+    "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS", "VES", "VND",
+    "VUV", "WST", "XAF", "XCD", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+// a trade price this many times above or below the last known price is most likely a parsing
+// error, e.g. a German thousands separator read as decimal point
+const MAX_PRICE_DEVIATION: Decimal = dec!(10);
+// the last known price is only a reference for trades this close to its update, there's no
+// price history to compare older trades with
+const REFERENCE_PRICE_WINDOW: Duration = Duration::days(30);
+
+fn invalid(record: &impl std::fmt::Debug, reason: String) -> anyhow::Error {
+    ImportError::Invalid {
+        record: format!("{:?}", record),
+        reason,
+    }
+    .into()
+}
+
+/// Validates the ISIN check digit, see ISO 6166.
+pub fn is_valid_isin(isin: &str) -> bool {
+    if !isin.is_ascii()
+        || isin.len() != 12
+        || !isin[..2].chars().all(|c| c.is_ascii_uppercase())
+        || !isin[2..11]
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        || !isin[11..].chars().all(|c| c.is_ascii_digit())
+    {
+        return false;
+    }
+
+    // letters are converted to numbers (A = 10, ..., Z = 35) before applying the Luhn algorithm
+    let digits: Vec<u32> = isin
+        .chars()
+        .flat_map(|c| {
+            let value = c.to_digit(36).unwrap();
+            if value > 9 {
+                vec![value / 10, value % 10]
+            } else {
+                vec![value]
+            }
+        })
+        .collect();
+
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| {
+            if index % 2 == 1 {
+                let doubled = digit * 2;
+                doubled / 10 + doubled % 10
+            } else {
+                *digit
+            }
+        })
+        .sum();
+
+    sum.is_multiple_of(10)
+}
+
+pub fn is_valid_currency(currency: &str) -> bool {
+    CURRENCY_CODES.contains(&currency)
+}
+
+// statements are dated by day for some brokers, hence one day of tolerance for the upper bound
+pub fn is_valid_date(date: &DateTime<Utc>) -> bool {
+    let earliest = Utc.with_ymd_and_hms(1990, 1, 1, 0, 0, 0).unwrap();
+    *date >= earliest && *date <= Utc::now() + Duration::days(1)
+}
+
+fn validate_common(
+    record: &impl std::fmt::Debug,
+    date: &DateTime<Utc>,
+    currencies: &[&str],
+) -> anyhow::Result<()> {
+    if !is_valid_date(date) {
+        return Err(invalid(record, format!("date {} out of range", date)));
+    }
+    for currency in currencies {
+        if !is_valid_currency(currency) {
+            return Err(invalid(record, format!("unknown currency '{}'", currency)));
+        }
+    }
+    Ok(())
+}
+
+fn validate_isin(record: &impl std::fmt::Debug, isin: &str) -> anyhow::Result<()> {
+    if !is_valid_isin(isin) {
+        return Err(invalid(record, format!("invalid ISIN '{}'", isin)));
+    }
+    Ok(())
+}
+
+fn validate_crypto_identifier(
+    record: &impl std::fmt::Debug,
+    identifier: &str,
+) -> anyhow::Result<()> {
+    if !is_crypto_identifier(identifier) {
+        return Err(invalid(
+            record,
+            format!("invalid crypto identifier '{}'", identifier),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_derivative_identifier(
+    record: &impl std::fmt::Debug,
+    identifier: &str,
+) -> anyhow::Result<()> {
+    if !is_derivative_identifier(identifier) {
+        return Err(invalid(
+            record,
+            format!("invalid derivative identifier '{}'", identifier),
+        ));
+    }
+    Ok(())
+}
+
+// the splits are passed in, so they're loaded once per file rather than for every trade
+pub async fn validate_trade(trade: &Trade, splits: &[StockSplit]) -> anyhow::Result<()> {
+    if trade.security_type == "Crypto" {
+        validate_crypto_identifier(trade, &trade.isin)?;
+    } else if trade.security_type == "Option" || trade.security_type == "Future" {
+        validate_derivative_identifier(trade, &trade.isin)?;
+    } else {
+        validate_isin(trade, &trade.isin)?;
+    }
+    validate_common(trade, &trade.date, &[&trade.currency])?;
+    if trade.units <= dec!(0) {
+        return Err(invalid(
+            trade,
+            format!("units {} must be positive", trade.units),
+        ));
+    }
+
+    if trade.eur_avg_price_per_unit <= dec!(0) {
+        return Ok(());
+    }
+    let Some(instrument) = get_instrument_by_id(&trade.isin).await? else {
+        return Ok(());
+    };
+    if instrument.price <= dec!(0)
+        || (instrument.last_price_update - trade.date).abs() > REFERENCE_PRICE_WINDOW
+    {
+        return Ok(());
+    }
+
+    // the price of the trade in units as of the price update
+    let split_adjusted_price = trade.eur_avg_price_per_unit
+        / split_ratio(
+            splits,
+            &trade.isin,
+            &trade.date,
+            &instrument.last_price_update,
+        );
+    if deviates_from(split_adjusted_price, instrument.price) {
+        return Err(invalid(
+            trade,
+            format!(
+                "price of {} EUR deviates from the price of {} EUR on {}",
+                trade.eur_avg_price_per_unit,
+                instrument.price,
+                instrument.last_price_update.format("%Y-%m-%d")
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn deviates_from(price: Decimal, reference_price: Decimal) -> bool {
+    let deviation = price / reference_price;
+    deviation > MAX_PRICE_DEVIATION || deviation < dec!(1) / MAX_PRICE_DEVIATION
+}
+
+// units one unit at `from` became by `until`, below 1 if `until` is earlier
+fn split_ratio(
+    splits: &[StockSplit],
+    isin: &str,
+    from: &DateTime<Utc>,
+    until: &DateTime<Utc>,
+) -> Decimal {
+    let (start, end) = if from <= until {
+        (from, until)
+    } else {
+        (until, from)
+    };
+    let ratio = splits
+        .iter()
+        .filter(|split| split.isin == isin && split.ex_date > *start && split.ex_date <= *end)
+        .fold(dec!(1), |ratio, split| {
+            ratio * split.to_factor / split.from_factor
+        });
+    if from <= until {
+        ratio
+    } else {
+        dec!(1) / ratio
+    }
+}
+
+pub fn validate_dividend(dividend: &Dividend) -> anyhow::Result<()> {
+    validate_isin(dividend, &dividend.isin)?;
+    validate_common(dividend, &dividend.date, &[&dividend.currency])
+}
+
+pub fn validate_interest(interest_payment: &InterestPayment) -> anyhow::Result<()> {
+    validate_common(
+        interest_payment,
+        &interest_payment.date,
+        &[&interest_payment.currency],
+    )
+}
+
+pub fn validate_fx_conversion(fx_conversion: &FxConversion) -> anyhow::Result<()> {
+    validate_common(
+        fx_conversion,
+        &fx_conversion.date,
+        &[&fx_conversion.from_currency, &fx_conversion.to_currency],
+    )
+}
+
+pub fn validate_tax_optimization(tax_optimization: &TaxOptimization) -> anyhow::Result<()> {
+    validate_common(
+        tax_optimization,
+        &tax_optimization.date,
+        &[&tax_optimization.currency],
+    )
+}
+
+fn validate_factors(
+    record: &impl std::fmt::Debug,
+    from_factor: Decimal,
+    to_factor: Decimal,
+) -> anyhow::Result<()> {
+    if from_factor <= dec!(0) || to_factor <= dec!(0) {
+        return Err(invalid(
+            record,
+            format!("factors {}:{} must be positive", from_factor, to_factor),
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_stock_split(stock_split: &StockSplit) -> anyhow::Result<()> {
+    validate_isin(stock_split, &stock_split.isin)?;
+    validate_common(stock_split, &stock_split.ex_date, &[])?;
+    validate_factors(stock_split, stock_split.from_factor, stock_split.to_factor)
+}
+
+pub fn validate_listing_change(listing_change: &ListingChange) -> anyhow::Result<()> {
+    validate_isin(listing_change, &listing_change.from_identifier)?;
+    validate_isin(listing_change, &listing_change.to_identifier)?;
+    validate_common(listing_change, &listing_change.ex_date, &[])?;
+    validate_factors(
+        listing_change,
+        listing_change.from_factor,
+        listing_change.to_factor,
+    )
+}
+
+pub fn validate_crypto_swap(crypto_swap: &CryptoSwap) -> anyhow::Result<()> {
+    validate_crypto_identifier(crypto_swap, &crypto_swap.from_identifier)?;
+    validate_crypto_identifier(crypto_swap, &crypto_swap.to_identifier)?;
+    validate_common(crypto_swap, &crypto_swap.date, &[])?;
+
+    if crypto_swap.from_units <= dec!(0) || crypto_swap.to_units <= dec!(0) {
+        return Err(invalid(
+            crypto_swap,
+            format!(
+                "units {} and {} must be positive",
+                crypto_swap.from_units, crypto_swap.to_units
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_derivative(derivative: &Derivative) -> anyhow::Result<()> {
+    validate_derivative_identifier(derivative, &derivative.id)?;
+    if !is_valid_currency(&derivative.currency) {
+        return Err(invalid(
+            derivative,
+            format!("unknown currency '{}'", derivative.currency),
+        ));
+    }
+
+    if derivative.multiplier <= dec!(0) {
+        return Err(invalid(
+            derivative,
+            format!("multiplier {} must be positive", derivative.multiplier),
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_fund_report(report: &FundTaxReport) -> anyhow::Result<()> {
+    validate_isin(report, &report.isin)?;
+    validate_common(report, &report.date, &[&report.currency])
+}
+
+pub fn validate_option_exercise(option_exercise: &OptionExercise) -> anyhow::Result<()> {
+    if !is_option_identifier(&option_exercise.option_identifier) {
+        return Err(invalid(
+            option_exercise,
+            format!(
+                "invalid option identifier '{}'",
+                option_exercise.option_identifier
+            ),
+        ));
+    }
+    validate_isin(option_exercise, &option_exercise.underlying_identifier)?;
+    validate_common(option_exercise, &option_exercise.date, &[])?;
+
+    if option_exercise.contracts <= dec!(0) {
+        return Err(invalid(
+            option_exercise,
+            format!("contracts {} must be positive", option_exercise.contracts),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(
+        isin: &str,
+        ex_date: DateTime<Utc>,
+        from_factor: Decimal,
+        to_factor: Decimal,
+    ) -> StockSplit {
+        StockSplit {
+            id: format!("{}{}", isin, ex_date),
+            ex_date,
+            from_factor,
+            to_factor,
+            isin: isin.to_string(),
+        }
+    }
+
+    #[test]
+    fn isins_with_a_valid_check_digit_are_accepted() {
+        for isin in [
+            "US0378331005",
+            "DE0007164600",
+            "IE00B4L5Y983",
+            "AT0000969985",
+        ] {
+            assert!(is_valid_isin(isin), "{}", isin);
+        }
+    }
+
+    #[test]
+    fn isins_with_an_invalid_check_digit_or_format_are_rejected() {
+        for isin in [
+            "US0378331006",
+            "IE00B4L5Y984",
+            "us0378331005",
+            "US037833100",
+            "US03783310055",
+            "0S0378331005",
+            "US037833100A",
+        ] {
+            assert!(!is_valid_isin(isin), "{}", isin);
+        }
+    }
+
+    #[test]
+    fn prices_are_compared_in_units_as_of_the_price_update() {
+        let trade_date = Utc.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap();
+        let price_update = Utc.with_ymd_and_hms(2024, 6, 20, 0, 0, 0).unwrap();
+        let splits = vec![
+            split(
+                "US67066G1040",
+                Utc.with_ymd_and_hms(2024, 6, 10, 0, 0, 0).unwrap(),
+                dec!(1),
+                dec!(10),
+            ),
+            // of another ISIN, and after the price update
+            split(
+                "US0378331005",
+                Utc.with_ymd_and_hms(2024, 6, 10, 0, 0, 0).unwrap(),
+                dec!(1),
+                dec!(4),
+            ),
+            split(
+                "US67066G1040",
+                Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap(),
+                dec!(1),
+                dec!(2),
+            ),
+        ];
+
+        let ratio = split_ratio(&splits, "US67066G1040", &trade_date, &price_update);
+        assert_eq!(ratio, dec!(10));
+        // a price before a 1:10 split isn't mistaken for a parsing error once adjusted
+        assert!(deviates_from(dec!(2400), dec!(120)));
+        assert!(!deviates_from(dec!(2400) / ratio, dec!(120)));
+
+        // the ratio is inverted for trades after the price update
+        assert_eq!(
+            split_ratio(&splits, "US67066G1040", &price_update, &trade_date),
+            dec!(0.1)
+        );
+    }
+}
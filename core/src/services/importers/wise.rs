@@ -7,14 +7,11 @@ use csv::{ReaderBuilder, StringRecord};
 use serde::Deserialize;
 
 use crate::{
-    database::{
-        models::{fx_conversion::FxConversion, interest::InterestPayment, trade::Trade},
-        queries::{
-            composite::add_trade_to_db, fx_conversion::add_fx_conversion_to_db,
-            interest::add_interest_to_db,
-        },
+    database::models::{fx_conversion::FxConversion, interest::InterestPayment, trade::Trade},
+    services::{
+        importers::context::ImportContext, market_data::fx_rates::convert_amount,
+        parsers::parse_timestamp,
     },
-    services::{market_data::fx_rates::convert_amount, parsers::parse_timestamp},
 };
 
 #[derive(Debug, Deserialize)]
@@ -151,7 +148,10 @@ fn detect_asset_record_type(record: &WiseAssetRecord) -> AssetRecordTye {
     AssetRecordTye::Unmatched
 }
 
-pub async fn extract_wise_record(file_content: &[u8]) -> anyhow::Result<()> {
+pub async fn extract_wise_record(
+    file_content: &[u8],
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
     let broker = "Wise".to_string();
 
     let cursor = Cursor::new(file_content);
@@ -162,7 +162,8 @@ pub async fn extract_wise_record(file_content: &[u8]) -> anyhow::Result<()> {
 
     match statement_type {
         StatementType::Asset => {
-            for result in rdr.deserialize() {
+            for (index, result) in rdr.deserialize().enumerate() {
+                ctx.set_line(index as u64 + 1);
                 let record: WiseAssetRecord = result?;
                 let record_type = detect_asset_record_type(&record);
                 match record_type {
@@ -204,14 +205,15 @@ pub async fn extract_wise_record(file_content: &[u8]) -> anyhow::Result<()> {
                             withholding_tax: dec!(0.0),
                             withholding_tax_currency: record.asset_base_currency.clone(),
                         };
-                        add_trade_to_db(trade, Some(record.wise_id)).await?;
+                        ctx.add_trade(trade, Some(record.wise_id)).await?;
                     }
-                    AssetRecordTye::Unmatched => continue,
+                    AssetRecordTye::Unmatched => ctx.skip(),
                 }
             }
         }
         StatementType::CashLegacy => {
-            for result in rdr.deserialize() {
+            for (index, result) in rdr.deserialize().enumerate() {
+                ctx.set_line(index as u64 + 1);
                 let record: WiseCashLegacyRecord = result?;
                 let record_type = detect_legacy_cash_record_type(&record);
                 if record.date == *"Date" {
@@ -231,7 +233,7 @@ pub async fn extract_wise_record(file_content: &[u8]) -> anyhow::Result<()> {
                             date_added: Utc::now(),
                             fees: record.total_fees.parse::<Decimal>()?,
                         };
-                        add_fx_conversion_to_db(fx_conversion).await?
+                        ctx.add_fx_conversion(fx_conversion).await?;
                     }
                     CashRecordType::InterestPayment => {
                         let date = parse_timestamp(format!("{} 16:00:00", record.date).as_str())?;
@@ -258,16 +260,17 @@ pub async fn extract_wise_record(file_content: &[u8]) -> anyhow::Result<()> {
                             withholding_tax: dec!(0.0),
                             withholding_tax_currency: record.currency,
                         };
-                        if add_interest_to_db(interest_payment.clone(), None).await? {
+                        if ctx.add_interest(interest_payment.clone(), None).await? {
                             println!("💵 Interest payment added: {:?}", interest_payment);
                         }
                     }
-                    CashRecordType::Unmatched => continue,
+                    CashRecordType::Unmatched => ctx.skip(),
                 }
             }
         }
         StatementType::Cash => {
-            for result in rdr.deserialize() {
+            for (index, result) in rdr.deserialize().enumerate() {
+                ctx.set_line(index as u64 + 1);
                 let record: WiseCashRecord = result?;
                 let record_type = detect_cash_record_type(&record);
                 if record.date == *"Date" {
@@ -286,7 +289,7 @@ pub async fn extract_wise_record(file_content: &[u8]) -> anyhow::Result<()> {
                             date_added: Utc::now(),
                             fees: record.total_fees.parse::<Decimal>()?,
                         };
-                        add_fx_conversion_to_db(fx_conversion).await?
+                        ctx.add_fx_conversion(fx_conversion).await?;
                     }
                     CashRecordType::InterestPayment => {
                         let interest_payment = InterestPayment {
@@ -312,11 +315,11 @@ pub async fn extract_wise_record(file_content: &[u8]) -> anyhow::Result<()> {
                             withholding_tax: dec!(0.0),
                             withholding_tax_currency: record.currency,
                         };
-                        if add_interest_to_db(interest_payment.clone(), None).await? {
+                        if ctx.add_interest(interest_payment.clone(), None).await? {
                             println!("💵 Interest payment added: {:?}", interest_payment);
                         }
                     }
-                    CashRecordType::Unmatched => continue,
+                    CashRecordType::Unmatched => ctx.skip(),
                 }
             }
         }
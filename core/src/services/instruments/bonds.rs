@@ -0,0 +1,59 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::{
+    database::models::{interest::InterestPayment, trade::Trade},
+    services::importers::validation::is_valid_isin,
+};
+
+// Bonds are quoted in percent of their nominal value. They're held in units of 100 nominal, so the
+// price in percent is the price per unit and positions, performance and taxes work the same as
+// for shares, e.g. 5.000 EUR nominal bought at 98,50% are 50 units at 98.50. Trade Republic's CSV
+// export states its own units, which are kept as they are, like the rows of earlier imports.
+pub const BOND_UNIT_NOMINAL: Decimal = dec!(100);
+
+// bonds are redeemed at 100% at maturity
+pub const BOND_PAR_PRICE: Decimal = dec!(100);
+
+pub fn bond_units(nominal: Decimal) -> Decimal {
+    nominal / BOND_UNIT_NOMINAL
+}
+
+// coupons and accrued interest are stored as interest with the bond's ISIN prefixed as principal,
+// e.g. BOND:DE0001102580, which sets them apart from interest on cash or on lent shares
+const BOND_PRINCIPAL_PREFIX: &str = "BOND:";
+
+pub fn bond_principal(isin: &str) -> String {
+    format!("{}{}", BOND_PRINCIPAL_PREFIX, isin)
+}
+
+pub fn bond_isin_from_principal(principal: &str) -> Option<&str> {
+    principal
+        .strip_prefix(BOND_PRINCIPAL_PREFIX)
+        .filter(|isin| is_valid_isin(isin))
+}
+
+// accrued interest (Stückzinsen) is paid to the seller on top of the price, for the buyer it's
+// negative income that's offset against the next coupon
+pub fn accrued_interest_payment(trade: &Trade, accrued_interest: Decimal) -> InterestPayment {
+    let amount = if trade.direction == "Buy" {
+        -accrued_interest.abs()
+    } else {
+        accrued_interest.abs()
+    };
+    let amount_eur = if trade.avg_price_per_unit == dec!(0) {
+        amount
+    } else {
+        amount * trade.eur_avg_price_per_unit / trade.avg_price_per_unit
+    };
+    InterestPayment {
+        date: trade.date,
+        amount,
+        broker: trade.broker.clone(),
+        principal: bond_principal(&trade.isin),
+        currency: trade.currency.clone(),
+        amount_eur,
+        withholding_tax: dec!(0.0),
+        withholding_tax_currency: trade.currency.clone(),
+    }
+}
@@ -0,0 +1,64 @@
+use chrono::Utc;
+use log::warn;
+
+use crate::{
+    database::{
+        models::instrument::Instrument,
+        queries::{composite::get_used_crypto_identifiers, instrument::update_instrument_price},
+    },
+    services::market_data::bitpanda::get_crypto_prices,
+};
+
+// crypto assets don't have an ISIN, they're identified by their symbol instead, e.g. CRYPTO:BTC
+const CRYPTO_IDENTIFIER_PREFIX: &str = "CRYPTO:";
+
+pub fn crypto_identifier(symbol: &str) -> String {
+    format!(
+        "{}{}",
+        CRYPTO_IDENTIFIER_PREFIX,
+        symbol.trim().to_uppercase()
+    )
+}
+
+pub fn is_crypto_identifier(identifier: &str) -> bool {
+    identifier
+        .strip_prefix(CRYPTO_IDENTIFIER_PREFIX)
+        .is_some_and(|symbol| {
+            !symbol.is_empty()
+                && symbol.len() <= 16
+                && symbol
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        })
+}
+
+pub fn crypto_symbol(identifier: &str) -> Option<&str> {
+    identifier.strip_prefix(CRYPTO_IDENTIFIER_PREFIX)
+}
+
+pub async fn update_crypto_prices() -> anyhow::Result<()> {
+    let identifiers = get_used_crypto_identifiers().await?;
+    if identifiers.is_empty() {
+        return Ok(());
+    }
+
+    let prices = get_crypto_prices().await?;
+    for identifier in identifiers {
+        let Some(symbol) = crypto_symbol(&identifier) else {
+            continue;
+        };
+        match prices.get(symbol) {
+            Some(price) => {
+                let instrument = Instrument {
+                    id: identifier.clone(),
+                    last_price_update: Utc::now(),
+                    price: *price,
+                    name: symbol.to_string(),
+                };
+                update_instrument_price(instrument).await?;
+            }
+            None => warn!("No current price found for {}, skipping.", identifier),
+        }
+    }
+    Ok(())
+}
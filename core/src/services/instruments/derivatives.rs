@@ -0,0 +1,45 @@
+// exchange traded options and futures don't have an ISIN, they're identified by their kind and
+// the broker's symbol of the contract instead, e.g. OPT:AAPL 250117C00150000 or FUT:ESZ4
+const OPTION_IDENTIFIER_PREFIX: &str = "OPT:";
+const FUTURE_IDENTIFIER_PREFIX: &str = "FUT:";
+
+pub fn option_identifier(symbol: &str) -> String {
+    format!("{}{}", OPTION_IDENTIFIER_PREFIX, normalize_symbol(symbol))
+}
+
+pub fn future_identifier(symbol: &str) -> String {
+    format!("{}{}", FUTURE_IDENTIFIER_PREFIX, normalize_symbol(symbol))
+}
+
+// OCC option symbols pad the underlying with spaces, e.g. "AAPL  250117C00150000"
+fn normalize_symbol(symbol: &str) -> String {
+    symbol
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_uppercase()
+}
+
+fn is_valid_symbol(symbol: &str) -> bool {
+    !symbol.is_empty()
+        && symbol.len() <= 32
+        && symbol
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || " .-/".contains(c))
+}
+
+pub fn is_option_identifier(identifier: &str) -> bool {
+    identifier
+        .strip_prefix(OPTION_IDENTIFIER_PREFIX)
+        .is_some_and(is_valid_symbol)
+}
+
+pub fn is_future_identifier(identifier: &str) -> bool {
+    identifier
+        .strip_prefix(FUTURE_IDENTIFIER_PREFIX)
+        .is_some_and(is_valid_symbol)
+}
+
+pub fn is_derivative_identifier(identifier: &str) -> bool {
+    is_option_identifier(identifier) || is_future_identifier(identifier)
+}
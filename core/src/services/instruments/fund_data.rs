@@ -1,12 +1,112 @@
+use anyhow::bail;
+use chrono::NaiveDate;
+use log::{error, info};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
 use crate::{
-    database::queries::composite::get_used_isins,
-    services::market_data::oekb::fetch_and_store_oekb_fund_report,
+    database::{
+        models::fund::FundPrice,
+        queries::{
+            composite::get_used_isins,
+            fund::{
+                add_fund_classification, remove_fund_classification, upsert_fund_classification,
+                upsert_fund_price,
+            },
+            fund_report::get_raw_oekb_fund_reports,
+        },
+    },
+    services::{
+        files::export_json,
+        importers::validation::{is_valid_currency, is_valid_isin},
+        market_data::oekb::{
+            fetch_and_store_oekb_fund_report, store_oekb_fund_report, OekbRawReport,
+        },
+    },
 };
 
+// funds reporting their taxable income to the OeKB ("Meldefonds")
+pub const REPORTING_FUND: &str = "ReportingFund";
+// funds taxed with the lump-sum rule ("Nicht-Meldefonds"), e.g. many US ETFs
+pub const NON_REPORTING_FUND: &str = "NonReportingFund";
+
 pub async fn update_oekb_fund_reports() -> anyhow::Result<()> {
     let isins = get_used_isins().await?;
     for isin in isins {
-        fetch_and_store_oekb_fund_report(&isin).await?;
+        // a missing report shouldn't keep the remaining funds from being updated
+        match fetch_and_store_oekb_fund_report(&isin).await {
+            Ok(0) => {}
+            Ok(_) => {
+                if add_fund_classification(&isin, REPORTING_FUND).await? {
+                    info!("Classified {} as reporting fund", isin);
+                }
+            }
+            Err(e) => error!("Failed to update OeKB fund report for {}: {:?}", isin, e),
+        }
     }
     Ok(())
 }
+
+// interprets the stored raw reports again, e.g. after more tax codes are taken into account,
+// returns the number of reports
+pub async fn reinterpret_oekb_fund_reports() -> anyhow::Result<usize> {
+    let raw_reports = get_raw_oekb_fund_reports().await?;
+    for raw_report in &raw_reports {
+        store_oekb_fund_report(&serde_json::from_str::<OekbRawReport>(raw_report)?).await?;
+    }
+    Ok(raw_reports.len())
+}
+
+// the export can be imported again, e.g. into another instance without fetching the reports
+pub async fn export_oekb_fund_reports() -> anyhow::Result<usize> {
+    let raw_reports = get_raw_oekb_fund_reports()
+        .await?
+        .iter()
+        .map(|raw_report| serde_json::from_str::<OekbRawReport>(raw_report))
+        .collect::<Result<Vec<_>, _>>()?;
+    export_json(&raw_reports, "oekb_reports")?;
+    Ok(raw_reports.len())
+}
+
+// removes the classification if no kind is given, i.e. the ISIN isn't a fund
+pub async fn set_fund_classification(isin: &str, kind: Option<&str>) -> anyhow::Result<()> {
+    let isin = isin.trim().to_uppercase();
+    if !is_valid_isin(&isin) {
+        bail!("{} is not a valid ISIN", isin);
+    }
+    match kind {
+        Some(kind) => upsert_fund_classification(&isin, kind).await,
+        None => remove_fund_classification(&isin).await,
+    }
+}
+
+pub async fn set_fund_price(
+    isin: &str,
+    date: NaiveDate,
+    price: Decimal,
+    currency: &str,
+) -> anyhow::Result<FundPrice> {
+    let fund_price = FundPrice {
+        isin: isin.trim().to_uppercase(),
+        date,
+        price,
+        currency: currency.trim().to_uppercase(),
+    };
+    if !is_valid_isin(&fund_price.isin) {
+        bail!("{} is not a valid ISIN", fund_price.isin);
+    }
+    if !is_valid_currency(&fund_price.currency) {
+        bail!("{} is not a valid currency", fund_price.currency);
+    }
+    if price <= dec!(0) {
+        bail!("The price needs to be positive");
+    }
+    upsert_fund_price(&fund_price).await?;
+    Ok(fund_price)
+}
+
+// the deemed distribution of a non-reporting fund per unit: 90% of the increase between the first
+// and the last price of the year, at least 10% of the last price
+pub fn lump_sum_income_per_unit(first_price: Decimal, last_price: Decimal) -> Decimal {
+    (dec!(0.9) * (last_price - first_price)).max(dec!(0.1) * last_price)
+}
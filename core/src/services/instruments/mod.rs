@@ -1,3 +1,7 @@
+pub mod bonds;
+pub mod crypto;
+pub mod derivatives;
 pub mod fund_data;
 pub mod identifiers;
 pub mod stock_splits;
+pub mod tickers;
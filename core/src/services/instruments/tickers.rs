@@ -0,0 +1,173 @@
+use anyhow::bail;
+use chrono::{Duration, Utc};
+use log::warn;
+use serde::Serialize;
+use typeshare::typeshare;
+
+use crate::{
+    database::{
+        models::ticker_conversion::{TickerConversion, UnresolvedTicker},
+        queries::{
+            composite::get_used_isins,
+            ticker_conversion::{
+                get_isins_without_ticker_conversion, get_ticker_conversions, get_unresolved_ticker,
+                get_unresolved_tickers, insert_ticker_conversion, query_isin_from_figi,
+                query_isin_from_symbol, remove_unresolved_ticker, update_unresolved_ticker_lookup,
+                upsert_ticker_conversion,
+            },
+        },
+    },
+    services::{
+        importers::validation::is_valid_isin,
+        market_data::openfigi::{get_instrument_from_ticker, get_instruments_from_isins},
+    },
+};
+
+// tickers OpenFIGI couldn't resolve aren't looked up again for a week, unless asked to
+const LOOKUP_INTERVAL_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Default)]
+pub struct TickerResolution {
+    pub isin: Option<String>,
+    // what OpenFIGI knows about the ticker, shown in the review queue when there's no ISIN
+    pub share_class_figi: Option<String>,
+    pub name: Option<String>,
+    pub looked_up: bool,
+}
+
+#[typeshare]
+#[derive(Debug, Serialize)]
+pub struct TickerOverview {
+    pub conversions: Vec<TickerConversion>,
+    pub unresolved: Vec<UnresolvedTicker>,
+}
+
+// OpenFIGI doesn't return ISINs, so a ticker is resolved by matching its share class FIGI against
+// the ones of ISINs already known, e.g. from trades imported from other brokers. Tickers of
+// instruments that aren't known yet stay in the review queue until a mapping is set by hand.
+pub async fn resolve_ticker(ticker: &str, force_lookup: bool) -> anyhow::Result<TickerResolution> {
+    if let Some(isin) = query_isin_from_symbol(ticker).await? {
+        return Ok(TickerResolution {
+            isin: Some(isin),
+            ..Default::default()
+        });
+    }
+
+    if !force_lookup {
+        if let Some(unresolved) = get_unresolved_ticker(ticker).await? {
+            let looked_up_recently = unresolved.last_lookup.is_some_and(|last_lookup| {
+                last_lookup > Utc::now() - Duration::days(LOOKUP_INTERVAL_DAYS)
+            });
+            if looked_up_recently {
+                return Ok(TickerResolution {
+                    isin: None,
+                    share_class_figi: unresolved.share_class_figi,
+                    name: unresolved.name,
+                    looked_up: false,
+                });
+            }
+        }
+    }
+
+    match look_up_ticker(ticker).await {
+        Ok(resolution) => Ok(resolution),
+        // OpenFIGI being unavailable leaves the ticker unresolved, to be looked up next time
+        Err(e) => {
+            warn!("Failed to look up ticker {} via OpenFIGI: {:?}", ticker, e);
+            Ok(TickerResolution::default())
+        }
+    }
+}
+
+async fn look_up_ticker(ticker: &str) -> anyhow::Result<TickerResolution> {
+    let Some(instrument) = get_instrument_from_ticker(ticker).await? else {
+        return Ok(TickerResolution {
+            looked_up: true,
+            ..Default::default()
+        });
+    };
+    let mut resolution = TickerResolution {
+        isin: None,
+        share_class_figi: instrument.share_class_figi.clone(),
+        name: instrument.name.clone(),
+        looked_up: true,
+    };
+    let Some(share_class_figi) = instrument.share_class_figi else {
+        return Ok(resolution);
+    };
+
+    if query_isin_from_figi(&share_class_figi).await?.is_none() {
+        map_known_isins().await?;
+    }
+
+    if let Some(isin) = query_isin_from_figi(&share_class_figi).await? {
+        insert_ticker_conversion(&isin, ticker, Some(&share_class_figi)).await?;
+        resolution.isin = Some(isin);
+    }
+    Ok(resolution)
+}
+
+// stores ticker and FIGI of every used ISIN that has no ticker mapping yet
+async fn map_known_isins() -> anyhow::Result<()> {
+    let isins = get_isins_without_ticker_conversion(&get_used_isins().await?).await?;
+    if isins.is_empty() {
+        return Ok(());
+    }
+
+    let instruments = get_instruments_from_isins(&isins).await?;
+    for (isin, instrument) in isins.iter().zip(instruments) {
+        let Some(instrument) = instrument else {
+            continue;
+        };
+        if let Some(ticker) = instrument.ticker.filter(|ticker| !ticker.is_empty()) {
+            insert_ticker_conversion(isin, &ticker, instrument.share_class_figi.as_deref()).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn get_ticker_overview() -> anyhow::Result<TickerOverview> {
+    Ok(TickerOverview {
+        conversions: get_ticker_conversions().await?,
+        unresolved: get_unresolved_tickers().await?,
+    })
+}
+
+// adds or overrides the mapping of a ticker, returning its entry in the review queue if it had one
+pub async fn set_ticker_mapping(
+    ticker: &str,
+    isin: &str,
+) -> anyhow::Result<Option<UnresolvedTicker>> {
+    let ticker = ticker.trim().to_uppercase();
+    let isin = isin.trim().to_uppercase();
+    if ticker.is_empty() {
+        bail!("The ticker can't be empty");
+    }
+    if !is_valid_isin(&isin) {
+        bail!("{} is not a valid ISIN", isin);
+    }
+
+    upsert_ticker_conversion(&ticker, &isin).await?;
+    remove_unresolved_ticker(&ticker).await
+}
+
+// looks up every ticker in the review queue again, returning the ones resolved
+pub async fn resolve_unresolved_tickers() -> anyhow::Result<Vec<UnresolvedTicker>> {
+    let mut resolved = vec![];
+    for unresolved in get_unresolved_tickers().await? {
+        let resolution = resolve_ticker(&unresolved.ticker, true).await?;
+        if resolution.isin.is_some() {
+            if let Some(unresolved) = remove_unresolved_ticker(&unresolved.ticker).await? {
+                resolved.push(unresolved);
+            }
+        } else if resolution.looked_up {
+            update_unresolved_ticker_lookup(
+                &unresolved.ticker,
+                resolution.share_class_figi.as_deref(),
+                resolution.name.as_deref(),
+            )
+            .await?;
+        }
+    }
+    Ok(resolved)
+}
@@ -0,0 +1,27 @@
+use std::collections::HashMap;
+
+use reqwest::Client;
+use rust_decimal::Decimal;
+
+// the public ticker lists the current price of every asset traded on Bitpanda, keyed by symbol
+// and quote currency, e.g. {"BTC": {"EUR": "60123.45", "USD": "65432.10"}}
+type BitpandaTicker = HashMap<String, HashMap<String, Decimal>>;
+
+/// Current EUR prices of all crypto assets traded on Bitpanda, by symbol.
+pub async fn get_crypto_prices() -> anyhow::Result<HashMap<String, Decimal>> {
+    let client = Client::new();
+
+    let ticker_response_body = client
+        .get("https://api.bitpanda.com/v1/ticker")
+        .send()
+        .await?
+        .text()
+        .await?;
+
+    let ticker = serde_json::from_str::<BitpandaTicker>(&ticker_response_body)?;
+
+    Ok(ticker
+        .into_iter()
+        .filter_map(|(symbol, prices)| prices.get("EUR").map(|price| (symbol, *price)))
+        .collect())
+}
@@ -1,3 +1,4 @@
+pub mod bitpanda;
 pub mod fred;
 pub mod fx_rates;
 pub mod oekb;
@@ -1,20 +1,44 @@
+use anyhow::bail;
+use reqwest::StatusCode;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::{
     database::{
-        models::fund_report::FundTaxReport, queries::fund_report::add_oekb_fund_report_to_db,
+        models::fund_report::{FundTaxReport, FundTaxReportItem},
+        queries::fund_report::{add_oekb_fund_report_to_db, get_cached_oekb_report_ids},
     },
-    services::parsers::parse_timestamp,
+    services::{parsers::parse_timestamp, shared::env::get_env_variable},
 };
 
+const OEKB_URL: &str = "https://my.oekb.at/fond-info/rest/public/steuerMeldung";
+
+// the web app of the OeKB sends the platform it's used on with every request, i.e. the base64 of
+// {"language":"de","platform":"KMS","dashboard":"KMS_OUTPUT"}, it can be overridden with
+// OEKB_PLATFORM_CONTEXT should that change
+const DEFAULT_PLATFORM_CONTEXT: &str =
+    "eyJsYW5ndWFnZSI6ImRlIiwicGxhdGZvcm0iOiJLTVMiLCJkYXNoYm9hcmQiOiJLTVNfT1VUUFVUIn0=";
+
+/// A fund report as published by the OeKB, i.e. its entry in the list of reports of the fund and
+/// its tax data for private investors. Reports are stored in this format, so they can be
+/// interpreted again later, and can be imported from JSON files.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OekbRawReport {
+    #[serde(rename = "steuerMeldung")]
+    pub report: Value,
+    #[serde(rename = "privatAnl")]
+    pub private_investors: Value,
+}
+
 #[derive(Deserialize, Debug)]
-pub struct OekbFullTaxReport {
+struct OekbFullTaxReport {
     #[serde(alias = "steuerCode")]
     id: i32,
+    // codes without an amount for private investors are left out
     #[serde(alias = "pvMitOption4")]
-    amount: Decimal,
+    amount: Option<Decimal>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -49,100 +73,119 @@ struct OekbFundReportResponseItem {
     report_id: i32,
     #[serde(alias = "waehrung")]
     currency: String,
-    #[serde(alias = "gjEnde")]
-    _period_end_date: String,
-    #[serde(alias = "gjBeginn")]
-    _period_start_date: String,
     #[serde(alias = "zufluss")]
     report_date: String,
-    #[serde(alias = "gueltAb")]
-    _valid_from: String,
     isin: String,
 }
 
 #[derive(Deserialize, Debug)]
 struct OekbFundReportResponse {
-    list: Vec<OekbFundReportResponseItem>,
+    list: Vec<Value>,
 }
-pub async fn fetch_and_store_oekb_fund_report(isin: &str) -> anyhow::Result<()> {
+
+// returns `None` if the OeKB doesn't know the requested fund or report
+async fn get_from_oekb(url: &str) -> anyhow::Result<Option<String>> {
+    let platform_context =
+        get_env_variable("OEKB_PLATFORM_CONTEXT").unwrap_or(DEFAULT_PLATFORM_CONTEXT.to_string());
     let client = reqwest::Client::new();
     let response = client
-        .get(format!("https://my.oekb.at/fond-info/rest/public/steuerMeldung/isin/{}", isin))
+        .get(url)
         .header("Accept", "application/json")
         .header("Accept-Language", "de")
-        .header("OeKB-Platform-Context",
-          "eyJsYW5ndWFnZSI6ImRlIiwicGxhdGZvcm0iOiJLTVMiLCJkYXNoYm9hcmQiOiJLTVNfT1VUUFVUIn0=")
+        .header("OeKB-Platform-Context", platform_context)
         .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.4.1 Safari/605.1.15")
         .send()
         .await?;
 
+    match response.status() {
+        status if status.is_success() => Ok(Some(response.text().await?)),
+        StatusCode::NOT_FOUND => Ok(None),
+        status => bail!("Request to {} failed: {}", url, status),
+    }
+}
+
+/// Interprets a raw report, picking the amounts used for taxation and keeping all of its tax
+/// codes.
+pub fn interpret_oekb_report(
+    raw_report: &OekbRawReport,
+) -> anyhow::Result<(FundTaxReport, Vec<FundTaxReportItem>)> {
+    let entry: OekbFundReportResponseItem = serde_json::from_value(raw_report.report.clone())?;
+    let tax_data: OekbFullTaxReportResponse =
+        serde_json::from_value(raw_report.private_investors.clone())?;
+
+    let mut report = FundTaxReport {
+        id: entry.report_id,
+        date: parse_timestamp(entry.report_date.as_str())?,
+        isin: entry.isin,
+        currency: entry.currency,
+        dividend: dec!(0),
+        dividend_aequivalent: dec!(0),
+        intermittent_dividends: dec!(0),
+        withheld_dividend: dec!(0),
+        wac_adjustment: dec!(0),
+    };
+    let mut items = vec![];
+
+    for report_item in tax_data.list {
+        let Some(amount) = report_item.amount else {
+            continue;
+        };
+        match ReportItemType::from_id(report_item.id) {
+            Some(ReportItemType::Dividends) => report.dividend = amount,
+            Some(ReportItemType::DividendAequivalents) => report.dividend_aequivalent = amount,
+            Some(ReportItemType::IntermittentDividends) => report.intermittent_dividends = amount,
+            Some(ReportItemType::WithHeldDividend) => report.withheld_dividend = amount,
+            Some(ReportItemType::WacAdjustment) => report.wac_adjustment = amount,
+            None => {}
+        }
+        items.push(FundTaxReportItem {
+            report_id: report.id,
+            tax_code: report_item.id,
+            amount,
+        });
+    }
+
+    Ok((report, items))
+}
+
+/// Interprets and stores a raw report, returns whether the report is new.
+pub async fn store_oekb_fund_report(raw_report: &OekbRawReport) -> anyhow::Result<bool> {
+    let (report, items) = interpret_oekb_report(raw_report)?;
+    add_oekb_fund_report_to_db(report, &items, &serde_json::to_string(raw_report)?).await
+}
+
+// returns the number of reports found for the fund, reports already stored with their raw report
+// aren't fetched again
+pub async fn fetch_and_store_oekb_fund_report(isin: &str) -> anyhow::Result<usize> {
     println!("Getting OeKB fund reports for {:?}", &isin);
 
-    if response.status().is_success() {
-        let oekb_funds_reponse_data =
-            serde_json::from_str::<OekbFundReportResponse>(&response.text().await?);
-
-        for report in oekb_funds_reponse_data?.list {
-            // now for each item in the list, make call to get the actual report
-            let mut report_to_store = FundTaxReport {
-                id: report.report_id,
-                date: parse_timestamp(report.report_date.as_str())?,
-                isin: report.isin,
-                currency: report.currency,
-                dividend: dec!(0),
-                dividend_aequivalent: dec!(0),
-                intermittent_dividends: dec!(0),
-                withheld_dividend: dec!(0),
-                wac_adjustment: dec!(0),
-            };
-
-            let report_items = query_oekb_fund_report(report.report_id).await?;
-            for report_item in report_items {
-                let fund_type = ReportItemType::from_id(report_item.id);
-                match fund_type {
-                    Some(fund_type) => match fund_type {
-                        ReportItemType::Dividends => report_to_store.dividend = report_item.amount,
-                        ReportItemType::DividendAequivalents => {
-                            report_to_store.dividend_aequivalent = report_item.amount
-                        }
-                        ReportItemType::IntermittentDividends => {
-                            report_to_store.intermittent_dividends = report_item.amount
-                        }
-                        ReportItemType::WithHeldDividend => {
-                            report_to_store.withheld_dividend = report_item.amount
-                        }
-                        ReportItemType::WacAdjustment => {
-                            report_to_store.wac_adjustment = report_item.amount
-                        }
-                    },
-                    None => println!("No fund type found for id {}", report_item.id),
-                }
-            }
-            add_oekb_fund_report_to_db(report_to_store).await?;
+    let Some(response) = get_from_oekb(&format!("{}/isin/{}", OEKB_URL, isin)).await? else {
+        return Ok(0);
+    };
+    let entries = serde_json::from_str::<OekbFundReportResponse>(&response)?.list;
+    let cached_report_ids = get_cached_oekb_report_ids().await?;
+
+    for entry in &entries {
+        let report_id =
+            serde_json::from_value::<OekbFundReportResponseItem>(entry.clone())?.report_id;
+        if cached_report_ids.contains(&report_id) {
+            continue;
         }
-    } else {
-        println!("Error while getting oekb funds data: {:?}", response);
+
+        let private_investors = query_oekb_fund_report(report_id).await?;
+        let raw_report = OekbRawReport {
+            report: entry.clone(),
+            private_investors,
+        };
+        store_oekb_fund_report(&raw_report).await?;
     }
-    Ok(())
+    Ok(entries.len())
 }
 
-pub async fn query_oekb_fund_report(report_id: i32) -> anyhow::Result<Vec<OekbFullTaxReport>> {
-    let client = reqwest::Client::new();
-    let response = client
-    .get(format!("https://my.oekb.at/fond-info/rest/public/steuerMeldung/stmId/{}/privatAnl", &report_id))
-    .header("Accept", "application/json")
-    .header("Accept-Language", "de")
-    .header("OeKB-Platform-Context",
-    "eyJsYW5ndWFnZSI6ImRlIiwicGxhdGZvcm0iOiJLTVMiLCJkYXNoYm9hcmQiOiJLTVNfT1VUUFVUIn0=")
-    .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.4.1 Safari/605.1.15")
-    .send()
-    .await?;
-
-    if response.status().is_success() {
-        let oekb_tax_report_data =
-            serde_json::from_str::<OekbFullTaxReportResponse>(&response.text().await?)?;
-        Ok(oekb_tax_report_data.list)
-    } else {
-        panic!("Couldn't get OeKB tax report.")
+async fn query_oekb_fund_report(report_id: i32) -> anyhow::Result<Value> {
+    let url = format!("{}/stmId/{}/privatAnl", OEKB_URL, report_id);
+    match get_from_oekb(&url).await? {
+        Some(response) => Ok(serde_json::from_str(&response)?),
+        None => bail!("OeKB tax report {} not found", report_id),
     }
 }
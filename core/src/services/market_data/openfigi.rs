@@ -1,22 +1,24 @@
 use std::time::Duration;
 
 use anyhow::Context;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde::Deserialize;
 use tokio::time::sleep;
 
 use crate::{
     database::queries::ticker_conversion::{insert_ticker_conversion, query_symbol_from_isin},
-    services::parsers::remove_first_and_last,
+    services::{parsers::remove_first_and_last, shared::env::get_env_variable},
 };
 
+const OPENFIGI_MAPPING_URL: &str = "https://api.openfigi.com/v3/mapping/";
+
 #[derive(Deserialize, Debug)]
 
 struct OpenFigiResponseItem {
     ticker: String,
     // _name: String,
     #[serde(alias = "shareClassFIGI")]
-    _share_class_figi: String,
+    share_class_figi: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -24,6 +26,46 @@ struct OpenFIGIResponse {
     data: Vec<OpenFigiResponseItem>,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct OpenFigiInstrument {
+    pub ticker: Option<String>,
+    pub name: Option<String>,
+    #[serde(rename = "shareClassFIGI")]
+    pub share_class_figi: Option<String>,
+}
+
+// one result per mapping job, without data if nothing was found
+#[derive(Deserialize, Debug)]
+struct OpenFigiMappingResult {
+    data: Option<Vec<OpenFigiInstrument>>,
+}
+
+// registered users (OPENFIGI_API_KEY) get higher rate limits and can map 100 instead of 10
+// identifiers per request
+fn mapping_request(client: &Client, jobs: &serde_json::Value) -> RequestBuilder {
+    let request = client.post(OPENFIGI_MAPPING_URL).json(jobs);
+    match get_env_variable("OPENFIGI_API_KEY") {
+        Some(api_key) => request.header("X-OPENFIGI-APIKEY", api_key),
+        None => request,
+    }
+}
+
+fn mapping_batch_size() -> usize {
+    match get_env_variable("OPENFIGI_API_KEY") {
+        Some(_) => 100,
+        None => 10,
+    }
+}
+
+fn rate_limit_delay() -> Duration {
+    match get_env_variable("OPENFIGI_API_KEY") {
+        // 25 requests per 6 seconds
+        Some(_) => Duration::from_millis(250),
+        // OpenFIGI API is rate limited to 5 requests / minute for unregistered users
+        None => Duration::from_millis(12000),
+    }
+}
+
 pub async fn get_symbol_from_isin(isin: &str, exch_code: Option<&str>) -> anyhow::Result<String> {
     println!("Getting symbol for ISIN {}...", &isin);
 
@@ -36,18 +78,19 @@ pub async fn get_symbol_from_isin(isin: &str, exch_code: Option<&str>) -> anyhow
         println!("Getting symbol for ISIN {} from OpenFIGI", &isin);
         let client = Client::new();
 
-        let open_figi_mapping_response = client
-            .post("https://api.openfigi.com/v3/mapping/")
-            .json(&serde_json::json!([{
+        let open_figi_mapping_response = mapping_request(
+            &client,
+            &serde_json::json!([{
                 "idType":"ID_ISIN",
                 "idValue": isin,
                 "exchCode": exch_code.unwrap_or("US"),
                 "includeUnlistedEquities": true
-            }]))
-            .send()
-            .await?
-            .text()
-            .await?;
+            }]),
+        )
+        .send()
+        .await?
+        .text()
+        .await?;
 
         // Check for specific error responses
         if open_figi_mapping_response == r#"[{"warning":"No identifier found."}]"# {
@@ -76,14 +119,77 @@ pub async fn get_symbol_from_isin(isin: &str, exch_code: Option<&str>) -> anyhow
             return Ok("NONE_FOUND".to_string());
         }
 
-        // OpenFIGI API is rate limited to 5 requests / minute for unregistered users
-        sleep(Duration::from_millis(12000)).await;
+        sleep(rate_limit_delay()).await;
 
         // We store the found ticker conversion in the database
-        insert_ticker_conversion(isin, ticker).await?;
+        insert_ticker_conversion(isin, ticker, response.data[0].share_class_figi.as_deref())
+            .await?;
 
         Ok(ticker.to_string())
     } else {
         Ok(symbol_in_db.to_string())
     }
 }
+
+// maps identifiers of one type (e.g. ID_ISIN or TICKER) listed in the US, returning the first
+// instrument found for each of them
+async fn map_identifiers(
+    id_type: &str,
+    identifiers: &[String],
+) -> anyhow::Result<Vec<Option<OpenFigiInstrument>>> {
+    let client = Client::new();
+    let mut instruments = vec![];
+
+    for (index, batch) in identifiers.chunks(mapping_batch_size()).enumerate() {
+        if index > 0 {
+            sleep(rate_limit_delay()).await;
+        }
+        let jobs: Vec<serde_json::Value> = batch
+            .iter()
+            .map(|identifier| {
+                serde_json::json!({
+                    "idType": id_type,
+                    "idValue": identifier,
+                    "exchCode": "US",
+                    "includeUnlistedEquities": true
+                })
+            })
+            .collect();
+
+        let response = mapping_request(&client, &serde_json::Value::Array(jobs))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let results = serde_json::from_str::<Vec<OpenFigiMappingResult>>(&response).context(
+            format!("Failed to deserialize OpenFIGI response: {}", response),
+        )?;
+
+        instruments.extend(
+            results
+                .into_iter()
+                .map(|result| result.data.and_then(|data| data.into_iter().next())),
+        );
+    }
+
+    Ok(instruments)
+}
+
+pub async fn get_instrument_from_ticker(
+    ticker: &str,
+) -> anyhow::Result<Option<OpenFigiInstrument>> {
+    println!("Getting instrument for ticker {} from OpenFIGI", ticker);
+    let mut instruments = map_identifiers("TICKER", &[ticker.to_string()]).await?;
+    Ok(instruments.pop().flatten())
+}
+
+pub async fn get_instruments_from_isins(
+    isins: &[String],
+) -> anyhow::Result<Vec<Option<OpenFigiInstrument>>> {
+    println!(
+        "Getting instruments for {} ISINs from OpenFIGI",
+        isins.len()
+    );
+    map_identifiers("ID_ISIN", isins).await
+}
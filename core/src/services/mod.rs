@@ -1,3 +1,4 @@
+pub mod archives;
 pub mod events;
 pub mod files;
 pub mod importers;
@@ -8,5 +9,8 @@ pub mod parsers;
 pub mod performance;
 pub mod portfolio;
 pub mod positions;
+pub mod sale_simulation;
 pub mod shared;
+pub mod tax_harvesting;
 pub mod taxation;
+pub mod withholding_tax;
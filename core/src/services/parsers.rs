@@ -2,6 +2,7 @@ use anyhow::anyhow;
 use chrono::prelude::*;
 use fancy_regex::Regex;
 use log::info;
+use rust_decimal::Decimal;
 use std::{io::Cursor, path::Path};
 
 use csv::ReaderBuilder;
@@ -10,19 +11,34 @@ use deunicode::deunicode;
 use super::{
     files::detect_file_format,
     importers::{
-        erste_bank::extract_erste_bank_record, ibkr::extract_ibkr_record,
-        lightyear::extract_lightyear_record, manual::extract_manual_record,
-        revolut::extract_revolut_record, scalable::extract_scalable_record,
-        trade_republic::{extract_trade_republic_record, extract_trade_republic_csv_record},
+        baader::{extract_baader_record, is_baader_statement},
+        bitpanda::{extract_bitpanda_record, is_bitpanda_csv},
+        context::ImportContext,
+        degiro::{extract_degiro_record, is_degiro_csv},
+        errors::ImportError,
+        erste_bank::extract_erste_bank_record,
+        flatex::extract_flatex_record,
+        ibkr::extract_ibkr_record,
+        ibkr_flex::{extract_ibkr_flex_record, is_flex_query},
+        lightyear::extract_lightyear_record,
+        manual::extract_manual_record,
+        mapping_profile::{detect_mapping_profile, extract_mapping_profile_record, MappingProfile},
+        oekb::{extract_oekb_report_record, is_oekb_report},
+        revolut::extract_revolut_record,
+        scalable::extract_scalable_record,
+        trade_republic::{extract_trade_republic_csv_record, extract_trade_republic_record},
         trading212::extract_trading212_record,
         wise::extract_wise_record,
     },
+    shared::env::get_env_variable,
 };
 
 #[derive(Debug)]
 pub enum ImportFileFormat {
     Pdf,
     Csv,
+    Xml,
+    Json,
     Unsupported,
 }
 
@@ -33,13 +49,56 @@ pub enum Broker {
     Lightyear,
     InteractiveBrokers,
     Scalable,
+    Baader,
+    Flatex,
     ErsteBank,
     Trading212,
     Wise,
+    Degiro,
+    Bitpanda,
     Manual,
+    // fund reports of the OeKB, not a broker but imported like one
+    Oekb,
+    Profile(Box<MappingProfile>),
 }
 
-pub fn detect_broker_from_csv_header(record: &csv::StringRecord) -> anyhow::Result<Option<Broker>> {
+impl Broker {
+    pub fn name(&self) -> &str {
+        match self {
+            Broker::TradeRepublic => "Trade Republic",
+            Broker::Revolut => "Revolut",
+            Broker::Lightyear => "Lightyear",
+            Broker::InteractiveBrokers => "Interactive Brokers",
+            Broker::Scalable => "Scalable Capital",
+            Broker::Baader => "Scalable Capital (Baader Bank)",
+            Broker::Flatex => "flatex",
+            Broker::ErsteBank => "Erste Bank",
+            Broker::Trading212 => "Trading 212",
+            Broker::Wise => "Wise",
+            Broker::Degiro => "DEGIRO",
+            Broker::Bitpanda => "Bitpanda",
+            Broker::Manual => "Manual",
+            Broker::Oekb => "OeKB",
+            Broker::Profile(profile) => &profile.name,
+        }
+    }
+}
+
+pub fn detect_broker_from_csv_header(
+    record: &csv::StringRecord,
+    path: &str,
+) -> anyhow::Result<Option<Broker>> {
+    // the disclaimer preceding the header is a single column
+    if is_bitpanda_csv(record) {
+        return Ok(Some(Broker::Bitpanda));
+    }
+    // every other export has a header of several columns
+    if record.len() < 2 {
+        return Err(ImportError::UnsupportedFile {
+            path: path.to_string(),
+        }
+        .into());
+    }
     if record.get(0).unwrap().contains("Action") {
         return Ok(Some(Broker::Trading212));
     }
@@ -48,6 +107,9 @@ pub fn detect_broker_from_csv_header(record: &csv::StringRecord) -> anyhow::Resu
     {
         return Ok(Some(Broker::Wise));
     }
+    if is_degiro_csv(record) {
+        return Ok(Some(Broker::Degiro));
+    }
     if (record.get(0).unwrap() == "Date" && record.get(1).unwrap() == "Ticker")
         || record.get(1).unwrap() == "Product"
     {
@@ -59,7 +121,11 @@ pub fn detect_broker_from_csv_header(record: &csv::StringRecord) -> anyhow::Resu
     if record.get(0).unwrap() == "ClientAccountID" {
         return Ok(Some(Broker::InteractiveBrokers));
     }
-    if record.get(0).unwrap() == "date" && record.get(4).unwrap() == "direction" {
+    if record.get(0).unwrap().eq_ignore_ascii_case("date")
+        && record
+            .iter()
+            .any(|column| column.eq_ignore_ascii_case("action"))
+    {
         return Ok(Some(Broker::Manual));
     }
     // Trade Republic CSV has "datetime" as first column and "transaction_id" in column 19 (index 18)
@@ -69,6 +135,17 @@ pub fn detect_broker_from_csv_header(record: &csv::StringRecord) -> anyhow::Resu
     Ok(None)
 }
 
+// user-defined mapping profiles take precedence, so they can also override a built-in importer
+pub fn detect_broker_from_csv(file: &[u8], path: &str) -> anyhow::Result<Option<Broker>> {
+    if let Some(profile) = detect_mapping_profile(file) {
+        return Ok(Some(Broker::Profile(Box::new(profile))));
+    }
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(Cursor::new(file));
+    detect_broker_from_csv_header(rdr.headers()?, path)
+}
+
 pub fn detect_broker_from_pdf_text(text: &str) -> Option<Broker> {
     if does_match_exist(r"TRADE REPUBLIC BANK GMBH", text) {
         return Some(Broker::TradeRepublic);
@@ -76,16 +153,48 @@ pub fn detect_broker_from_pdf_text(text: &str) -> Option<Broker> {
     if does_match_exist(r"Erste Bank", text) {
         return Some(Broker::ErsteBank);
     }
+    if does_match_exist(r"(?i)flatex", text) {
+        return Some(Broker::Flatex);
+    }
+    if is_baader_statement(text) {
+        return Some(Broker::Baader);
+    }
     if does_match_exist(r"Scalable", text) {
         return Some(Broker::Scalable);
     }
     None
 }
 
-pub fn extract_pdf_text(file: &[u8]) -> anyhow::Result<String> {
-    let text = pdf_extract::extract_text_from_mem(file)?;
+impl Broker {
+    // passwords of encrypted statements are configured per broker, e.g. George exports of Erste Bank
+    fn pdf_password_variable(&self) -> Option<&'static str> {
+        match self {
+            Broker::TradeRepublic => Some("PDF_PASSWORD_TRADE_REPUBLIC"),
+            Broker::Scalable | Broker::Baader => Some("PDF_PASSWORD_SCALABLE"),
+            Broker::Flatex => Some("PDF_PASSWORD_FLATEX"),
+            Broker::ErsteBank => Some("PDF_PASSWORD_ERSTE_BANK"),
+            _ => None,
+        }
+    }
+}
+
+const PDF_PASSWORD_VARIABLES: &[&str] = &[
+    "PDF_PASSWORD_TRADE_REPUBLIC",
+    "PDF_PASSWORD_SCALABLE",
+    "PDF_PASSWORD_FLATEX",
+    "PDF_PASSWORD_ERSTE_BANK",
+];
+
+pub fn configured_pdf_passwords() -> Vec<String> {
+    PDF_PASSWORD_VARIABLES
+        .iter()
+        .filter_map(|variable| get_env_variable(variable))
+        .collect()
+}
+
+fn clean_pdf_text(text: &str) -> String {
     let re = Regex::new(r"\s+").unwrap();
-    let cleaned_text = deunicode(&text)
+    let cleaned_text = deunicode(text)
         .replace('\0', "")
         .replace("[?]", "")
         .replace("\n", " ")
@@ -93,72 +202,177 @@ pub fn extract_pdf_text(file: &[u8]) -> anyhow::Result<String> {
         .trim()
         .to_string();
 
-    Ok(re.replace_all(&cleaned_text, " ").to_string())
+    re.replace_all(&cleaned_text, " ").to_string()
+}
+
+// lopdf decrypts the objects while loading, pdf-extract reads the decrypted document saved again
+fn decrypt_pdf(file: &[u8], password: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut document = match lopdf::Document::load_mem_with_password(file, password) {
+        Ok(document) => document,
+        Err(lopdf::Error::InvalidPassword) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut decrypted = vec![];
+    document.save_to(&mut decrypted)?;
+    Ok(Some(decrypted))
+}
+
+/// Extracts the text of a PDF. Encrypted PDFs are decrypted with the first of the passwords that
+/// works, `None` is returned if none does.
+pub fn extract_pdf_text(file: &[u8], passwords: &[String]) -> anyhow::Result<Option<String>> {
+    if !lopdf::Document::load_mem(file)?.is_encrypted() {
+        return Ok(Some(clean_pdf_text(&pdf_extract::extract_text_from_mem(
+            file,
+        )?)));
+    }
+
+    // PDFs only protected against editing open with an empty password
+    for password in std::iter::once("").chain(passwords.iter().map(String::as_str)) {
+        if let Some(decrypted) = decrypt_pdf(file, password)? {
+            return Ok(Some(clean_pdf_text(&pdf_extract::extract_text_from_mem(
+                &decrypted,
+            )?)));
+        }
+    }
+    Ok(None)
 }
 
-pub async fn parse_file_for_import(file: &[u8], file_path: &Path) -> anyhow::Result<()> {
+// a configured password only counts for statements of its broker, passwords entered at the
+// prompt or uploaded along with the file for any statement
+fn extract_statement_text(
+    file: &[u8],
+    file_path: &Path,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<String> {
+    if let Some(text) = extract_pdf_text(file, &ctx.pdf_passwords)? {
+        return Ok(text);
+    }
+    for variable in PDF_PASSWORD_VARIABLES {
+        let Some(password) = get_env_variable(variable) else {
+            continue;
+        };
+        if let Some(text) = extract_pdf_text(file, &[password])? {
+            let detected_variable = detect_broker_from_pdf_text(&text)
+                .and_then(|broker| broker.pdf_password_variable());
+            if detected_variable == Some(*variable) {
+                return Ok(text);
+            }
+        }
+    }
+
+    // asks for passwords until one works, an empty input gives up on the file
+    let path = file_path.display().to_string();
+    if let Some(prompt) = ctx.pdf_password_prompt {
+        while let Some(password) = prompt(&path)? {
+            if let Some(text) = extract_pdf_text(file, std::slice::from_ref(&password))? {
+                ctx.pdf_passwords.push(password);
+                return Ok(text);
+            }
+            println!("The password doesn't decrypt {}.", path);
+        }
+    }
+    Err(ImportError::EncryptedPdf { path }.into())
+}
+
+pub async fn parse_file_for_import(
+    file: &[u8],
+    file_path: &Path,
+    ctx: &mut ImportContext,
+) -> anyhow::Result<()> {
     let file_format = detect_file_format(file, file_path);
 
     match file_format {
         ImportFileFormat::Pdf => {
-            let text = extract_pdf_text(file)?;
+            let text = extract_statement_text(file, file_path, ctx)?;
 
-            let broker = detect_broker_from_pdf_text(&text);
+            let broker = detect_broker_from_pdf_text(&text).ok_or(ImportError::UnknownBroker {
+                path: file_path.display().to_string(),
+            })?;
+            info!("Statement from {} detected.", broker.name());
+            ctx.set_broker(broker.name());
 
             match broker {
-                Some(Broker::TradeRepublic) => {
-                    info!("Statement from Trade Republic detected.");
-                    extract_trade_republic_record(&text).await?;
+                Broker::TradeRepublic => extract_trade_republic_record(&text, ctx).await?,
+                Broker::Scalable => extract_scalable_record(&text, ctx).await?,
+                Broker::Baader => extract_baader_record(&text, ctx).await?,
+                Broker::Flatex => extract_flatex_record(&text, ctx).await?,
+                Broker::ErsteBank => extract_erste_bank_record(&text, ctx).await?,
+                _ => {
+                    return Err(ImportError::UnknownBroker {
+                        path: file_path.display().to_string(),
+                    }
+                    .into())
                 }
-                Some(Broker::Scalable) => {
-                    info!("Statement from Scalable detected.");
-                    extract_scalable_record(&text).await?;
-                }
-                Some(Broker::ErsteBank) => {
-                    info!("Statement from Erste Bank detected.");
-                    extract_erste_bank_record(&text).await?;
-                }
-                Some(_) => panic!("Broker wrongly matched"),
-                None => println!("No broker matched"),
             }
         }
         ImportFileFormat::Csv => {
             let file_content = file;
 
-            let cursor = Cursor::new(file_content);
-
-            let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(cursor);
-
-            let broker = detect_broker_from_csv_header(rdr.headers()?)?;
+            let path = file_path.display().to_string();
+            let broker = detect_broker_from_csv(file_content, &path)?
+                .ok_or(ImportError::UnknownBroker { path })?;
+            info!("{} CSV detected.", broker.name());
+            ctx.set_broker(broker.name());
 
             match broker {
-                Some(Broker::Trading212) => {
-                    extract_trading212_record(file_content).await?;
+                Broker::Trading212 => extract_trading212_record(file_content, ctx).await?,
+                Broker::Revolut => extract_revolut_record(file_content, ctx).await?,
+                Broker::Lightyear => extract_lightyear_record(file_content, ctx).await?,
+                Broker::InteractiveBrokers => extract_ibkr_record(file_content, ctx).await?,
+                Broker::Wise => extract_wise_record(file_content, ctx).await?,
+                Broker::Degiro => extract_degiro_record(file_content, ctx).await?,
+                Broker::Bitpanda => extract_bitpanda_record(file_content, ctx).await?,
+                Broker::Manual => extract_manual_record(file_content, ctx).await?,
+                Broker::Profile(profile) => {
+                    extract_mapping_profile_record(file_content, &profile, ctx).await?
                 }
-                Some(Broker::Revolut) => {
-                    extract_revolut_record(file_content).await?;
+                Broker::TradeRepublic => {
+                    extract_trade_republic_csv_record(file_content, ctx).await?
                 }
-                Some(Broker::Lightyear) => {
-                    extract_lightyear_record(file_content).await?;
+                _ => {
+                    return Err(ImportError::UnknownBroker {
+                        path: file_path.display().to_string(),
+                    }
+                    .into())
                 }
-                Some(Broker::InteractiveBrokers) => {
-                    extract_ibkr_record(file_content).await?;
-                }
-                Some(Broker::Wise) => {
-                    extract_wise_record(file_content).await?;
-                }
-                Some(Broker::Manual) => {
-                    extract_manual_record(file_content).await?;
+            }
+        }
+        ImportFileFormat::Xml => {
+            let text = std::str::from_utf8(file)?;
+
+            if !is_flex_query(text) {
+                return Err(ImportError::UnknownBroker {
+                    path: file_path.display().to_string(),
                 }
-                Some(Broker::TradeRepublic) => {
-                    info!("Trade Republic CSV detected.");
-                    extract_trade_republic_csv_record(file_content).await?;
+                .into());
+            }
+            let broker = Broker::InteractiveBrokers;
+            info!("{} Flex Query detected.", broker.name());
+            ctx.set_broker(broker.name());
+
+            extract_ibkr_flex_record(text, ctx).await?;
+        }
+        ImportFileFormat::Json => {
+            let text = std::str::from_utf8(file)?;
+
+            if !is_oekb_report(text) {
+                return Err(ImportError::UnknownBroker {
+                    path: file_path.display().to_string(),
                 }
-                Some(_) => panic!("Broker wrongly matched"),
-                None => println!("No broker matched"),
+                .into());
+            }
+            let broker = Broker::Oekb;
+            info!("{} fund report detected.", broker.name());
+            ctx.set_broker(broker.name());
+
+            extract_oekb_report_record(text, ctx).await?;
+        }
+        ImportFileFormat::Unsupported => {
+            return Err(ImportError::UnsupportedFile {
+                path: file_path.display().to_string(),
             }
+            .into())
         }
-        ImportFileFormat::Unsupported => println!("File unsupported, skipping"),
     }
     Ok(())
 }
@@ -170,13 +384,54 @@ pub fn does_match_exist(regex_pattern: &str, text: &str) -> bool {
 
 pub fn return_first_match(regex_pattern: &str, text: &str) -> anyhow::Result<String> {
     let regex = Regex::new(regex_pattern)?;
-    let caps = regex
-        .captures(text)?
-        .unwrap_or_else(|| panic!("Expected regex {} couldn't be found", regex_pattern));
+    let caps = regex.captures(text)?.ok_or_else(|| {
+        ImportError::unparsable(regex_pattern, &text.chars().take(80).collect::<String>())
+    })?;
     let matched_text = caps.get(0).unwrap();
     Ok(matched_text.as_str().to_string())
 }
 
+/// Returns the capture groups of the first match, e.g. to extract a value behind a label.
+pub fn return_first_captures(regex_pattern: &str, text: &str) -> anyhow::Result<Vec<String>> {
+    let regex = Regex::new(regex_pattern)?;
+    let caps = regex.captures(text)?.ok_or_else(|| {
+        ImportError::unparsable(regex_pattern, &text.chars().take(80).collect::<String>())
+    })?;
+    Ok(caps
+        .iter()
+        .skip(1)
+        .map(|group| group.map(|m| m.as_str().to_string()).unwrap_or_default())
+        .collect())
+}
+
+/// Parses amounts in German notation, e.g. `1.234,56`.
+pub fn parse_german_decimal(value: &str) -> anyhow::Result<Decimal> {
+    value
+        .replace('.', "")
+        .replace(',', ".")
+        .parse::<Decimal>()
+        .map_err(|_| ImportError::unparsable("amount", value).into())
+}
+
+/// Returns the slices of the first run of `sequence` after the first `keyword` token, allowing
+/// up to `lookahead` other tokens in between (e.g. labels of statement tables).
+pub fn find_token_sequence<'a, T: PartialEq>(
+    tokens: &[(T, &'a str)],
+    keyword: T,
+    sequence: &[T],
+    lookahead: usize,
+) -> Option<Vec<&'a str>> {
+    let start = tokens.iter().position(|(token, _)| *token == keyword)? + 1;
+    (start..=start + lookahead).find_map(|position| {
+        let window = tokens.get(position..position + sequence.len())?;
+        window
+            .iter()
+            .zip(sequence)
+            .all(|((token, _), expected)| token == expected)
+            .then(|| window.iter().map(|(_, slice)| *slice).collect())
+    })
+}
+
 pub fn remove_first_and_last(value: &str) -> &str {
     let mut chars = value.chars();
     chars.next();
@@ -13,6 +13,7 @@ use crate::database::{
     queries::{
         composite::get_all_trades,
         instrument::{batch_get_instrument_names, batch_get_instrument_prices},
+        interest::get_bond_interest_totals,
         listing_change::get_listing_changes,
         stock_split::get_stock_splits,
     },
@@ -104,6 +105,22 @@ pub async fn get_performance() -> anyhow::Result<PortfolioPerformance> {
     // Load listing changes to handle ISIN changes (e.g., ADR discontinuations)
     let listing_changes = get_listing_changes().await?;
 
+    // coupons and accrued interest are part of a bond's return, just like its price
+    let bond_interest: HashMap<(String, String), Decimal> = get_bond_interest_totals()
+        .await?
+        .into_iter()
+        .map(|(broker, isin, amount)| {
+            (
+                (
+                    broker,
+                    get_changed_identifier(&isin, listing_changes.clone()),
+                ),
+                amount,
+            )
+        })
+        .into_grouping_map()
+        .sum();
+
     let grouped_trades: Vec<TradeGroup> = trades
         .iter()
         .map(|trade| {
@@ -162,8 +179,19 @@ pub async fn get_performance() -> anyhow::Result<PortfolioPerformance> {
     let mut trade_performance: Vec<TradePerformance> = vec![];
 
     for title_performance in title_performances {
-        let realized = round_to_decimals(title_performance.realized);
-        let unrealized = if title_performance.inventory > dec!(0.0) {
+        let realized = round_to_decimals(
+            title_performance.realized
+                + bond_interest
+                    .get(&(
+                        title_performance.broker.clone(),
+                        title_performance.isin.clone(),
+                    ))
+                    .copied()
+                    .unwrap_or(dec!(0.0)),
+        );
+        // short positions are valued at market as well, their unrealized P&L is the difference
+        // between the average sale price and the current price
+        let unrealized = if title_performance.inventory != dec!(0.0) {
             round_to_decimals(
                 (*price_map.get(&title_performance.isin).unwrap() * title_performance.inventory)
                     - title_performance.unit_price * title_performance.inventory,
@@ -322,52 +350,54 @@ pub fn get_title_performance(
             trade.date,
             stock_split_information,
         );
+        let split_adjusted_price = get_split_adjusted_price_per_unit(
+            &trade.isin,
+            trade.eur_avg_price_per_unit,
+            trade.date,
+            stock_split_information,
+        );
+        // sells without units held open a short position, buys cover it
+        let direction = if trade.direction == "Buy" {
+            dec!(1)
+        } else {
+            dec!(-1)
+        };
+        let position_direction = if inventory < dec!(0) {
+            dec!(-1)
+        } else {
+            dec!(1)
+        };
 
-        if trade.direction == "Buy" {
-            inventory += split_adjusted_units;
-            purchase_value += get_split_adjusted_price_per_unit(
-                &trade.isin,
-                trade.eur_avg_price_per_unit,
-                trade.date,
-                stock_split_information,
-            ) * split_adjusted_units;
-            invested_amount += get_split_adjusted_price_per_unit(
-                &trade.isin,
-                trade.eur_avg_price_per_unit,
-                trade.date,
-                stock_split_information,
-            ) * split_adjusted_units;
-        }
-        if trade.direction == "Sell" {
-            if inventory == dec!(0) {
-                log::warn!(
-                    "ISIN {} has no units in the portfolio at the point of sell event on {}. Skipping sell.",
-                    trade.isin, trade.date
-                );
-                continue;
-            }
+        let closed_units =
+            if is_position_size_over_threshold(inventory) && direction != position_direction {
+                split_adjusted_units.min(inventory.abs())
+            } else {
+                dec!(0)
+            };
+        let opened_units = split_adjusted_units - closed_units;
+
+        if closed_units > dec!(0) {
             let avg_purchase_price = purchase_value / inventory;
-            let actual_sell_price = get_split_adjusted_price_per_unit(
-                &trade.isin,
-                trade.eur_avg_price_per_unit,
-                trade.date,
-                stock_split_information,
-            );
 
             let realized_pnl_for_trade =
-                (actual_sell_price - avg_purchase_price) * split_adjusted_units;
+                (split_adjusted_price - avg_purchase_price) * closed_units * position_direction;
             realized += realized_pnl_for_trade;
-            inventory -= split_adjusted_units;
-            purchase_value -= avg_purchase_price * split_adjusted_units;
+            inventory -= closed_units * position_direction;
+            purchase_value -= avg_purchase_price * closed_units * position_direction;
             invested_amount -=
                 if !is_position_size_over_threshold(inventory) && queue_len != &(&i + 1) {
-                    realized_pnl_for_trade + avg_purchase_price * split_adjusted_units
+                    realized_pnl_for_trade + avg_purchase_price * closed_units
                 } else if is_position_size_over_threshold(inventory) && queue_len == &(&i + 1) {
                     realized_pnl_for_trade
                 } else {
                     dec!(0.0)
                 }
-        };
+        }
+        if opened_units > dec!(0) {
+            inventory += opened_units * direction;
+            purchase_value += split_adjusted_price * opened_units * direction;
+            invested_amount += split_adjusted_price * opened_units;
+        }
     }
 
     inventory = override_positions_below_threshold(inventory);
@@ -376,7 +406,7 @@ pub fn get_title_performance(
         isin: trade_group.isin.to_string(),
         broker: trade_group.broker.to_string(),
         inventory,
-        unit_price: if inventory > dec!(0.0) {
+        unit_price: if inventory != dec!(0.0) {
             purchase_value / inventory
         } else {
             dec!(0.0)
@@ -386,8 +416,9 @@ pub fn get_title_performance(
     }
 }
 
+// short positions have negative units
 pub fn is_position_size_over_threshold(units: Decimal) -> bool {
-    units > dec!(0.00000000000001)
+    units.abs() > dec!(0.00000000000001)
 }
 
 pub fn override_positions_below_threshold(units: Decimal) -> Decimal {
@@ -448,18 +479,35 @@ pub async fn simulate_alternate_purchase(
         let mut invested_amount = dec!(0.0);
 
         let queue_len = &queue_with_overrides.iter().clone().count();
+        // short positions aren't simulated, the index can't be sold short in their place
+        let mut real_short_units = dec!(0.0);
 
         for (i, trade) in queue_with_overrides.iter().enumerate() {
+            let real_units = queue_without_overrides.clone().collect_vec()[i].units;
+
             if trade.direction == "Buy" {
-                inventory += trade.units;
-                real_held_units += queue_without_overrides.clone().collect_vec()[i].units;
-                purchase_value += trade.eur_avg_price_per_unit * trade.units;
-                invested_amount += trade.eur_avg_price_per_unit * trade.units;
+                let covered_units = real_units.min(real_short_units);
+                real_short_units -= covered_units;
+                let bought_units = real_units - covered_units;
+                if bought_units == dec!(0) {
+                    continue;
+                }
+                let index_units = trade.units * bought_units / real_units;
+
+                inventory += index_units;
+                real_held_units += bought_units;
+                purchase_value += trade.eur_avg_price_per_unit * index_units;
+                invested_amount += trade.eur_avg_price_per_unit * index_units;
             }
 
-            if trade.direction == "Sell" {
-                let share_of_accrued_position =
-                    queue_without_overrides.clone().collect_vec()[i].units / real_held_units;
+            if trade.direction == "Sell" || trade.direction == "TransferOut" {
+                let sold_units = real_units.min(real_held_units.max(dec!(0)));
+                real_short_units += real_units - sold_units;
+                if sold_units == dec!(0) {
+                    continue;
+                }
+
+                let share_of_accrued_position = sold_units / real_held_units;
 
                 let normalized_unit_count = share_of_accrued_position * inventory;
 
@@ -474,7 +522,7 @@ pub async fn simulate_alternate_purchase(
                     (actual_sell_price - avg_purchase_price) * normalized_unit_count;
                 realized += realized_for_trade;
                 inventory -= normalized_unit_count;
-                real_held_units -= queue_without_overrides.clone().collect_vec()[i].units;
+                real_held_units -= sold_units;
                 purchase_value -= avg_purchase_price * normalized_unit_count;
                 invested_amount -=
                     if !is_position_size_over_threshold(inventory) && queue_len != &(&i + 1) {
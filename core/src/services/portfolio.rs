@@ -49,6 +49,7 @@ pub async fn get_portfolio_overview() -> anyhow::Result<PortfolioOverview> {
     let price_map: HashMap<_, _> = isins.iter().zip(prices.iter()).collect();
     let name_map: HashMap<_, _> = isins.iter().zip(names.iter()).collect();
 
+    // short positions are valued at market as well, as a negative value
     let mut positions_with_value: Vec<PositionWithValue> = current_positions
         .iter()
         .map(|position| {
@@ -62,8 +63,11 @@ pub async fn get_portfolio_overview() -> anyhow::Result<PortfolioOverview> {
         })
         .collect();
 
+    // shares are relative to the gross exposure, so short positions don't offset long ones
+    let mut gross_exposure = dec!(0.0);
     for position in &positions_with_value {
         total_position += position.value;
+        gross_exposure += position.value.abs();
     }
 
     positions_with_value.sort_by(|a, b| a.value.partial_cmp(&b.value).unwrap());
@@ -71,7 +75,11 @@ pub async fn get_portfolio_overview() -> anyhow::Result<PortfolioOverview> {
     let positions_with_allocation: Vec<PositionWithValueAndAllocation> = positions_with_value
         .iter()
         .map(|position| {
-            let position_share = position.value / total_position;
+            let position_share = if gross_exposure == dec!(0) {
+                dec!(0)
+            } else {
+                position.value / gross_exposure
+            };
             PositionWithValueAndAllocation {
                 isin: position.isin.clone(),
                 name: name_map
@@ -81,6 +89,7 @@ pub async fn get_portfolio_overview() -> anyhow::Result<PortfolioOverview> {
                 value: round_to_decimals(position.value),
                 units: round_to_decimals(position.units),
                 share: round_to_decimals(position_share * dec!(100.0)),
+                short: position.units < dec!(0),
             }
         })
         .collect();
@@ -101,7 +110,12 @@ pub async fn get_portfolio_overview() -> anyhow::Result<PortfolioOverview> {
     Ok(PortfolioOverview {
         generated_at: Utc::now().timestamp(),
         total_value: round_to_decimals(total_position),
-        total_return_rel: round_to_decimals(total_return_abs / invested * dec!(100.0)),
+        // e.g. a portfolio of short positions only has nothing invested
+        total_return_rel: if invested == dec!(0) {
+            dec!(0)
+        } else {
+            round_to_decimals(total_return_abs / invested * dec!(100.0))
+        },
         total_return_abs,
         realized: round_to_decimals(realized),
         positions: positions_with_allocation,
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use chrono::{DateTime, Utc};
+use rust_decimal_macros::dec;
 
 use crate::database::{
     models::position::PositionWithName,
@@ -32,6 +33,7 @@ pub async fn get_positions_overview(
                 isin: position.isin.clone(),
                 name,
                 units: position.units,
+                short: position.units < dec!(0),
             }
         })
         .collect();
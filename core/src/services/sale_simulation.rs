@@ -0,0 +1,190 @@
+use std::fmt;
+
+use chrono::{Datelike, Duration, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use typeshare::typeshare;
+
+use crate::{
+    database::queries::instrument::batch_get_instrument_prices,
+    services::{
+        events::{EventType, PortfolioEvent, TradeDirection},
+        market_data::fx_rates::convert_amount,
+        taxation::{
+            calculate_capital_gains_tax_report, replay_capital_gains_tax_report, SecWac,
+            TaxSettlement, TaxationReport,
+        },
+    },
+};
+
+#[typeshare]
+#[derive(Debug, Serialize)]
+pub struct SaleSimulation {
+    pub isin: String,
+    pub name: String,
+    pub broker: String,
+    pub units: Decimal,
+    pub currency: String,
+    // in the currency of the position
+    pub price: Decimal,
+    pub proceeds_eur: Decimal,
+    // the change of the price in its currency, the remainder of the gain is due to the currency
+    pub realised_gain_eur: Decimal,
+    pub fx_appreciation_eur: Decimal,
+    // by how much the balance of this year's settlement grows, negative if the sale saves tax
+    pub additional_tax_eur: Decimal,
+    pub settlement: TaxSettlement,
+    // `None` once all units are sold
+    pub new_wac: Option<SecWac>,
+    pub new_depot_wac: Option<SecWac>,
+}
+
+// the sale can't be simulated as requested, as opposed to a failing query or run of the engine
+#[derive(Debug)]
+pub struct InvalidSale(String);
+
+impl fmt::Display for InvalidSale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidSale {}
+
+fn invalid_sale(message: String) -> anyhow::Error {
+    InvalidSale(message).into()
+}
+
+fn realised_amounts(report: &TaxationReport, year: i32) -> (Decimal, Decimal) {
+    report
+        .unrounded_taxable_amounts
+        .get(&year)
+        .map_or((dec!(0), dec!(0)), |amounts| {
+            (
+                amounts.capital_gains - amounts.capital_losses,
+                amounts.fx_appreciation,
+            )
+        })
+}
+
+/// Replays the tax engine with a sale of the position at the current time appended, nothing is
+/// stored. The sale is made from the depot holding the most units unless a broker is given, at
+/// the current price unless one is given in the currency of the position.
+pub async fn simulate_sale(
+    isin: &str,
+    units: Decimal,
+    broker: Option<&str>,
+    price: Option<Decimal>,
+) -> anyhow::Result<SaleSimulation> {
+    if units <= dec!(0) {
+        return Err(invalid_sale(
+            "The units to sell need to be positive".to_string(),
+        ));
+    }
+    let baseline = calculate_capital_gains_tax_report(None, None, vec![]).await?;
+    replay_sale(&baseline, isin, units, broker, price).await
+}
+
+/// Like `simulate_sale`, against the report of the tax engine without the sale, e.g. to simulate
+/// several sales one at a time.
+pub async fn replay_sale(
+    baseline: &TaxationReport,
+    isin: &str,
+    units: Decimal,
+    broker: Option<&str>,
+    price: Option<Decimal>,
+) -> anyhow::Result<SaleSimulation> {
+    let now = Utc::now();
+    let year = now.year();
+
+    // unrounded, so a fractional position can be sold completely
+    let mut depots = baseline
+        .unrounded_depot_wacs
+        .iter()
+        .filter_map(|(broker, wacs)| wacs.get(isin).map(|sec_wac| (broker, sec_wac)))
+        .filter(|(_, sec_wac)| sec_wac.units + sec_wac.old_units > dec!(0));
+    let (broker, depot_wac) = match broker {
+        Some(broker) => depots
+            .find(|(depot, _)| depot.as_str() == broker)
+            .ok_or_else(|| invalid_sale(format!("{} isn't held at {}", isin, broker)))?,
+        None => depots
+            .max_by_key(|(_, sec_wac)| sec_wac.units + sec_wac.old_units)
+            .ok_or_else(|| invalid_sale(format!("{} isn't held", isin)))?,
+    };
+    if units > depot_wac.units + depot_wac.old_units {
+        return Err(invalid_sale(format!(
+            "Only {} units of {} are held at {}",
+            depot_wac.units + depot_wac.old_units,
+            isin,
+            broker
+        )));
+    }
+
+    // foreign currency per EUR, the way trades store their rate; the rates are looked up before
+    // the given date, so tomorrow includes today's rate
+    let fx_rate = convert_amount(
+        dec!(1.0),
+        &(now.date_naive() + Duration::days(1)),
+        "EUR",
+        &depot_wac.currency,
+    )
+    .await?;
+    let price = match price {
+        Some(price) if price > dec!(0) => price,
+        Some(_) => return Err(invalid_sale("The price needs to be positive".to_string())),
+        None => {
+            let price_eur = batch_get_instrument_prices(&[isin.to_string()]).await?[0];
+            if price_eur == dec!(0) {
+                return Err(invalid_sale(format!(
+                    "No current price of {}, please pass one",
+                    isin
+                )));
+            }
+            price_eur * fx_rate
+        }
+    };
+
+    let sale = PortfolioEvent {
+        date: now,
+        event_type: EventType::Trade,
+        currency: depot_wac.currency.clone(),
+        units,
+        price_unit: price,
+        identifier: Some(isin.to_string()),
+        name: Some(depot_wac.name.clone()),
+        direction: Some(TradeDirection::Sell),
+        applied_fx_rate: Some(fx_rate),
+        withholding_tax_percent: None,
+        total: units * price,
+        broker: broker.clone(),
+    };
+    let name = depot_wac.name.clone();
+    let currency = depot_wac.currency.clone();
+    let broker = broker.clone();
+
+    let mut simulated = replay_capital_gains_tax_report(baseline, vec![sale]).await?;
+    let (gain_before, fx_before) = realised_amounts(baseline, year);
+    let (gain_after, fx_after) = realised_amounts(&simulated, year);
+    let settlement_before = baseline.simulate_settlement(year, dec!(0), dec!(0));
+    let settlement = simulated.simulate_settlement(year, dec!(0), dec!(0));
+
+    Ok(SaleSimulation {
+        isin: isin.to_string(),
+        name,
+        units,
+        currency,
+        price,
+        proceeds_eur: (units * price / fx_rate).round_dp(2),
+        realised_gain_eur: (gain_after - gain_before).round_dp(2),
+        fx_appreciation_eur: (fx_after - fx_before).round_dp(2),
+        additional_tax_eur: settlement.balance - settlement_before.balance,
+        settlement,
+        new_wac: simulated.securities_wacs.remove(isin),
+        new_depot_wac: simulated
+            .depot_wacs
+            .get_mut(&broker)
+            .and_then(|wacs| wacs.remove(isin)),
+        broker,
+    })
+}
@@ -1,3 +1,4 @@
 pub const SESSION_TOKEN_KEY: &str = "role";
 pub const OUT_DIR: &str = "output";
 pub const IN_DIR: &str = "input";
+pub const PROFILES_DIR: &str = "profiles";
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+use log::warn;
+
+use crate::database::{models::trade::Trade, queries::trade::get_all_trades_with_hash};
+
+use super::{env::get_env_variable, util::hash_string};
+
+/// How much of a record's timestamp goes into its identity when the broker doesn't share a
+/// transaction ID. Revolut changed the timestamps in later versions of their statements, hence
+/// the default of only considering the day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateGranularity {
+    Day,
+    Minute,
+    Second,
+}
+
+impl DateGranularity {
+    pub fn from_env() -> DateGranularity {
+        match get_env_variable("RECORD_ID_DATE_GRANULARITY")
+            .map(|value| value.to_lowercase())
+            .as_deref()
+        {
+            Some("minute") => DateGranularity::Minute,
+            Some("second") => DateGranularity::Second,
+            _ => DateGranularity::Day,
+        }
+    }
+
+    fn format(&self, date: &DateTime<Utc>) -> String {
+        let format = match self {
+            DateGranularity::Day => "%Y-%m-%d",
+            DateGranularity::Minute => "%Y-%m-%d %H:%M",
+            DateGranularity::Second => "%Y-%m-%d %H:%M:%S",
+        };
+        date.format(format).to_string()
+    }
+}
+
+fn trade_identity_base(trade: &Trade, granularity: DateGranularity) -> String {
+    format!(
+        "{}|{}|{}|{}|{}",
+        trade.isin,
+        granularity.format(&trade.date),
+        trade.units.normalize(),
+        trade.direction,
+        trade.avg_price_per_unit.normalize()
+    )
+}
+
+/// Record ID for a trade without broker transaction ID, `occurrence` being the index of identical
+/// trades within the same source file.
+pub fn trade_record_id(trade: &Trade, granularity: DateGranularity, occurrence: u32) -> String {
+    format!("{}#{}", trade_identity_base(trade, granularity), occurrence)
+}
+
+pub fn trade_hash(broker: &str, id: &str) -> String {
+    hash_string(format!("{}{}", broker, id).as_str())
+}
+
+/// Hash the trade table used for trades without transaction ID before record IDs included the
+/// date, needed to find rows to re-key.
+pub fn legacy_trade_hash(trade: &Trade) -> String {
+    hash_string(
+        format!(
+            "{}{}{}{}",
+            trade.isin, trade.units, trade.direction, trade.avg_price_per_unit
+        )
+        .as_str(),
+    )
+}
+
+/// Refuses to import while trades are stored with their legacy hash, the same trades would be
+/// stored a second time under their new record ID.
+pub async fn ensure_trades_rekeyed() -> anyhow::Result<()> {
+    let legacy_trades = get_all_trades_with_hash()
+        .await?
+        .into_iter()
+        .filter(|stored_trade| stored_trade.hash == legacy_trade_hash(&stored_trade.to_trade()))
+        .count();
+    if legacy_trades > 0 {
+        bail!(
+            "{} trades are stored with a legacy ID, run `doughbox rekey` before importing",
+            legacy_trades
+        );
+    }
+    Ok(())
+}
+
+/// Hands out record IDs for the records of a single source file. Identical records (e.g. two
+/// savings plan executions at the same price on the same day) get increasing occurrence indexes
+/// instead of colliding.
+pub struct RecordIdentity {
+    granularity: DateGranularity,
+    occurrences: HashMap<String, u32>,
+}
+
+impl RecordIdentity {
+    pub fn new() -> RecordIdentity {
+        RecordIdentity {
+            granularity: DateGranularity::from_env(),
+            occurrences: HashMap::new(),
+        }
+    }
+
+    pub fn trade_id(&mut self, trade: &Trade) -> String {
+        let base = trade_identity_base(trade, self.granularity);
+        let occurrence = self.occurrences.entry(base).or_insert(0);
+        if *occurrence > 0 {
+            warn!(
+                "Identical trade found {} times in the same file, keeping it as separate record: {:?}",
+                *occurrence + 1,
+                trade
+            );
+        }
+        let id = trade_record_id(trade, self.granularity, *occurrence);
+        *occurrence += 1;
+        id
+    }
+}
+
+impl Default for RecordIdentity {
+    fn default() -> Self {
+        RecordIdentity::new()
+    }
+}
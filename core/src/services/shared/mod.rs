@@ -1,4 +1,5 @@
 pub mod constants;
 pub mod env;
+pub mod identity;
 pub mod logger;
 pub mod util;
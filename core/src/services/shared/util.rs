@@ -8,3 +8,7 @@ pub fn hash_string(input_string: &str) -> String {
 pub fn round_to_decimals(input: Decimal) -> Decimal {
     input.round_dp(2)
 }
+
+pub fn hash_bytes(input: &[u8]) -> String {
+    blake3::hash(input).to_string()
+}
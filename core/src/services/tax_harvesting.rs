@@ -0,0 +1,124 @@
+use chrono::{Datelike, Utc};
+use log::warn;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use tabled::Tabled;
+use typeshare::typeshare;
+
+use crate::{
+    database::queries::instrument::batch_get_instrument_prices,
+    services::{
+        sale_simulation::replay_sale,
+        taxation::{get_capital_gains_tax_report, TaxSettlement},
+    },
+};
+
+// a position that would realise a loss if it was sold at its current price
+#[typeshare]
+#[derive(Debug, Serialize, Tabled)]
+pub struct HarvestingCandidate {
+    pub broker: String,
+    pub isin: String,
+    pub name: String,
+    pub units: Decimal,
+    pub currency: String,
+    // in the currency of the position, the WAC across all depots the sale is taxed against
+    pub average_cost: Decimal,
+    pub current_price: Decimal,
+    // the change of the price in its currency, and of the currency against EUR since the purchase
+    pub price_gain_eur: Decimal,
+    pub fx_appreciation_eur: Decimal,
+    pub unrealised_loss_eur: Decimal,
+    // by how much the balance of this year's settlement would drop if the position was sold
+    pub tax_saving_eur: Decimal,
+}
+
+#[typeshare]
+#[derive(Debug, Serialize)]
+pub struct HarvestingReport {
+    pub year: i32,
+    // the settlement of the gains and income realised so far this year
+    pub settlement: TaxSettlement,
+    pub candidates: Vec<HarvestingCandidate>,
+}
+
+/// Lists the positions of every depot that are held at a loss, with the tax saved by selling them
+/// this year. The sale of each position is replayed in the tax engine on its own, over the events
+/// loaded for the report, so it's taxed against the WAC across all depots like an actual sale.
+/// Losses on Altbestand can't be offset, positions selling only Altbestand are left out.
+pub async fn get_harvesting_report() -> anyhow::Result<HarvestingReport> {
+    let year = Utc::now().year();
+    let taxation_report = get_capital_gains_tax_report(None, None).await?;
+    let settlement = taxation_report.simulate_settlement(year, dec!(0), dec!(0));
+
+    let mut candidates = vec![];
+    // unrounded, so fractional positions are sold completely
+    for (broker, wacs) in &taxation_report.unrounded_depot_wacs {
+        let isins: Vec<String> = wacs.keys().cloned().collect();
+        let prices = batch_get_instrument_prices(&isins).await?;
+
+        for ((isin, depot_wac), price_eur) in wacs.iter().zip(prices) {
+            // short positions aren't harvested
+            if depot_wac.units <= dec!(0) {
+                continue;
+            }
+            let Some(sec_wac) = taxation_report.securities_wacs.get(isin) else {
+                continue;
+            };
+            if price_eur == dec!(0) {
+                warn!(
+                    "No current price of {}, it's left out of the harvesting suggestions",
+                    isin
+                );
+                continue;
+            }
+            // only positions held at a loss against the overall WAC are replayed
+            let purchase_fx_rate = if sec_wac.weighted_avg_fx_rate == dec!(0) {
+                dec!(1.0)
+            } else {
+                sec_wac.weighted_avg_fx_rate
+            };
+            if price_eur >= sec_wac.average_cost / purchase_fx_rate {
+                continue;
+            }
+
+            let sale = replay_sale(
+                &taxation_report,
+                isin,
+                depot_wac.units + depot_wac.old_units,
+                Some(broker),
+                None,
+            )
+            .await?;
+            let realised_gain_eur = sale.realised_gain_eur + sale.fx_appreciation_eur;
+            if realised_gain_eur >= dec!(0) {
+                continue;
+            }
+            candidates.push(HarvestingCandidate {
+                broker: broker.clone(),
+                isin: isin.clone(),
+                name: sale.name,
+                units: sale.units.round_dp(4),
+                currency: sale.currency,
+                average_cost: sec_wac.average_cost,
+                current_price: sale.price.round_dp(2),
+                price_gain_eur: sale.realised_gain_eur.round_dp(2),
+                fx_appreciation_eur: sale.fx_appreciation_eur.round_dp(2),
+                unrealised_loss_eur: (-realised_gain_eur).round_dp(2),
+                tax_saving_eur: -sale.additional_tax_eur,
+            });
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.tax_saving_eur
+            .cmp(&a.tax_saving_eur)
+            .then(b.unrealised_loss_eur.cmp(&a.unrealised_loss_eur))
+    });
+
+    Ok(HarvestingReport {
+        year,
+        settlement,
+        candidates,
+    })
+}
@@ -1,45 +1,76 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::Datelike;
 use chrono::TimeZone;
-use chrono::{DateTime, Utc};
-use log::{debug, info, trace};
+use chrono::{DateTime, NaiveDate, Utc};
+use itertools::Itertools;
+use log::{debug, info, trace, warn};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::Serialize;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use tabled::Tabled;
 use typeshare::typeshare;
 
+use crate::database::models::crypto_swap::CryptoSwap;
+use crate::database::models::derivative::OptionExercise;
+use crate::database::models::fund::{FundClassification, FundPrice};
+use crate::database::queries::crypto_swap::get_crypto_swaps;
+use crate::database::queries::derivative::{get_derivatives, get_option_exercises};
+use crate::database::queries::fund::{get_fund_classifications, get_fund_prices};
 use crate::database::queries::stock_split::get_stock_splits;
 use crate::database::queries::tax_optimization::get_tax_optimizations_by_date_range;
 use crate::{
-    database::queries::{composite::get_active_years, fund_report::get_oekb_fund_report_by_id, fx_rate::get_exchange_rate},
+    database::queries::{
+        composite::{get_active_years, get_bond_isins},
+        fund_report::{get_oekb_fund_report_by_id, get_oekb_report_years},
+        fx_rate::get_exchange_rate,
+    },
     services::market_data::fx_rates::convert_amount,
     services::shared::constants::OUT_DIR,
 };
 
+use super::instruments::crypto::is_crypto_identifier;
+use super::instruments::derivatives::{is_derivative_identifier, is_option_identifier};
+use super::instruments::fund_data::{lump_sum_income_per_unit, REPORTING_FUND};
 use super::instruments::stock_splits::{
     get_split_adjusted_price_per_unit, get_split_adjusted_units, StockSplit,
 };
 use super::{
     events::{get_events, EventType, PortfolioEvent, TradeDirection},
     files::export_json,
+    withholding_tax::get_creditable_withholding_tax,
 };
 
 #[typeshare]
-#[derive(Debug, Serialize, Tabled)]
+#[derive(Debug, Default, Clone, Serialize, Tabled)]
 pub struct AnnualTaxableAmounts {
-    cash_interest: Decimal,
-    share_lending_interest: Decimal,
-    capital_gains: Decimal,
-    capital_losses: Decimal,
-    dividends: Decimal,
-    dividend_equivalents: Decimal,
-    fx_appreciation: Decimal,
-    withheld_tax_capital_gains: Decimal,
-    withheld_tax_dividends: Decimal,
-    withheld_tax_interest: Decimal,
-    tax_optimization_adjustment: Decimal,
+    pub cash_interest: Decimal,
+    pub share_lending_interest: Decimal,
+    // coupons and accrued interest of bonds, net of accrued interest paid on purchases
+    pub bond_interest: Decimal,
+    pub capital_gains: Decimal,
+    pub capital_losses: Decimal,
+    // gains and losses on the sale of Altbestand, i.e. securities acquired before 2011 (bonds before April 2012)
+    pub tax_free_gains: Decimal,
+    // options and futures, including premiums received for written options
+    pub derivative_gains: Decimal,
+    pub derivative_losses: Decimal,
+    pub dividends: Decimal,
+    pub dividend_equivalents: Decimal,
+    // deemed distributions of funds without an OeKB report for the year
+    pub non_reporting_fund_income: Decimal,
+    pub fx_appreciation: Decimal,
+    pub withheld_tax_capital_gains: Decimal,
+    pub withheld_tax_dividends: Decimal,
+    pub withheld_tax_interest: Decimal,
+    // KESt withheld on deemed distributions of funds according to their OeKB reports, it's
+    // included in the withheld tax on dividends and credited in full
+    pub domestic_tax_dividends: Decimal,
+    // the part of the withheld tax credited in Austria, at most at the treaty rate of the country
+    // it was withheld by
+    pub creditable_tax_dividends: Decimal,
+    pub creditable_tax_interest: Decimal,
+    pub tax_optimization_adjustment: Decimal,
 }
 
 impl AnnualTaxableAmounts {
@@ -48,14 +79,161 @@ impl AnnualTaxableAmounts {
         let fields = [
             &mut self.cash_interest,
             &mut self.share_lending_interest,
+            &mut self.bond_interest,
             &mut self.capital_gains,
+            &mut self.derivative_gains,
+            &mut self.derivative_losses,
             &mut self.dividends,
             &mut self.fx_appreciation,
             &mut self.dividend_equivalents,
+            &mut self.non_reporting_fund_income,
             &mut self.capital_losses,
+            &mut self.tax_free_gains,
             &mut self.withheld_tax_dividends,
             &mut self.withheld_tax_interest,
+            &mut self.domestic_tax_dividends,
+            &mut self.creditable_tax_dividends,
+            &mut self.creditable_tax_interest,
+            &mut self.tax_optimization_adjustment,
+        ];
+        for field in fields {
+            *field = field.round_dp(dp);
+        }
+    }
+}
+
+// crypto assets are taxed at the special rate since March 2022, and are reported separately from
+// other capital income
+#[typeshare]
+#[derive(Debug, Default, Clone, Serialize, Tabled)]
+pub struct AnnualCryptoAmounts {
+    gains: Decimal,
+    losses: Decimal,
+    // gains on "Altvermögen", i.e. crypto acquired before March 2021, aren't taxable anymore
+    tax_free_gains: Decimal,
+}
+
+impl AnnualCryptoAmounts {
+    fn round_all(&mut self, dp: u32) {
+        self.gains = self.gains.round_dp(dp);
+        self.losses = self.losses.round_dp(dp);
+        self.tax_free_gains = self.tax_free_gains.round_dp(dp);
+    }
+}
+
+// the tax owed for a year once losses are offset against income (Verlustausgleich) and withheld
+// tax is credited, losses can't be carried forward to the following years
+#[typeshare]
+#[derive(Debug, Serialize, Tabled)]
+pub struct TaxSettlement {
+    // realised gains of securities, derivatives, currencies and crypto
+    pub gains: Decimal,
+    // fund income and interest other than on bank deposits
+    pub other_income: Decimal,
+    // dividends, dividend equivalents and share lending interest
+    pub dividends: Decimal,
+    pub losses: Decimal,
+    pub offset_losses: Decimal,
+    pub forfeited_losses: Decimal,
+    // interest on bank deposits is taxed at 25% and can't be offset against losses
+    pub cash_interest: Decimal,
+    pub tax_due: Decimal,
+    pub withheld_tax: Decimal,
+    // withholding tax beyond the Austrian tax on the income it was withheld from
+    pub non_creditable_tax: Decimal,
+    pub tax_optimization_adjustment: Decimal,
+    // positive to be paid, negative to be refunded
+    pub balance: Decimal,
+}
+
+impl TaxSettlement {
+    fn calculate(
+        amounts: &AnnualTaxableAmounts,
+        crypto_amounts: &AnnualCryptoAmounts,
+        tax_rates: &TaxRates,
+    ) -> TaxSettlement {
+        // negative income, e.g. accrued interest paid on bond purchases, is offset like a loss
+        let gains = amounts.capital_gains
+            + amounts.derivative_gains
+            + amounts.fx_appreciation.max(dec!(0))
+            + crypto_amounts.gains;
+        let other_income = amounts.non_reporting_fund_income + amounts.bond_interest.max(dec!(0));
+        let dividends =
+            amounts.dividends + amounts.dividend_equivalents + amounts.share_lending_interest;
+        let losses = amounts.capital_losses
+            + amounts.derivative_losses
+            + (-amounts.fx_appreciation).max(dec!(0))
+            + (-amounts.bond_interest).max(dec!(0))
+            + crypto_amounts.losses;
+
+        // losses are offset against the income tax was withheld from last, so as little of the
+        // withheld tax as possible can't be credited anymore
+        let mut remaining_losses = losses;
+        let mut offset = |income: Decimal| {
+            let offset_losses = remaining_losses.min(income.max(dec!(0)));
+            remaining_losses -= offset_losses;
+            income - offset_losses
+        };
+        let taxable_gains = offset(gains);
+        let taxable_fund_income = offset(amounts.non_reporting_fund_income);
+        let taxable_bond_interest = offset(amounts.bond_interest.max(dec!(0)));
+        let taxable_dividends = offset(dividends);
+
+        let tax_due = (taxable_gains + taxable_fund_income + taxable_bond_interest)
+            * tax_rates.capital_gains
+            + taxable_dividends * tax_rates.dividends
+            + amounts.cash_interest * tax_rates.interest;
+
+        let creditable_dividend_tax = amounts
+            .creditable_tax_dividends
+            .min(taxable_dividends * tax_rates.dividends);
+        let creditable_interest_tax = amounts.creditable_tax_interest.min(
+            amounts.cash_interest * tax_rates.interest
+                + taxable_bond_interest * tax_rates.capital_gains,
+        );
+        let non_creditable_tax = amounts.withheld_tax_dividends
+            - amounts.domestic_tax_dividends
+            - creditable_dividend_tax
+            + amounts.withheld_tax_interest
+            - creditable_interest_tax;
+        // the withheld amounts already include the adjustments of tax optimizations
+        let withheld_tax = amounts.withheld_tax_capital_gains
+            + amounts.withheld_tax_dividends
+            + amounts.withheld_tax_interest
+            - amounts.tax_optimization_adjustment;
+
+        TaxSettlement {
+            gains,
+            other_income,
+            dividends,
+            losses,
+            offset_losses: losses - remaining_losses,
+            forfeited_losses: remaining_losses,
+            cash_interest: amounts.cash_interest,
+            tax_due,
+            withheld_tax,
+            non_creditable_tax,
+            tax_optimization_adjustment: amounts.tax_optimization_adjustment,
+            balance: tax_due
+                - (withheld_tax - non_creditable_tax)
+                - amounts.tax_optimization_adjustment,
+        }
+    }
+
+    fn round_all(&mut self, dp: u32) {
+        let fields = [
+            &mut self.gains,
+            &mut self.other_income,
+            &mut self.dividends,
+            &mut self.losses,
+            &mut self.offset_losses,
+            &mut self.forfeited_losses,
+            &mut self.cash_interest,
+            &mut self.tax_due,
+            &mut self.withheld_tax,
+            &mut self.non_creditable_tax,
             &mut self.tax_optimization_adjustment,
+            &mut self.balance,
         ];
         for field in fields {
             *field = field.round_dp(dp);
@@ -70,8 +248,61 @@ pub struct TaxationReport {
     pub from_date: Option<DateTime<Utc>>,
     pub until_date: Option<DateTime<Utc>>,
     pub taxable_amounts: BTreeMap<i32, AnnualTaxableAmounts>,
+    pub crypto_amounts: BTreeMap<i32, AnnualCryptoAmounts>,
     pub securities_wacs: BTreeMap<String, SecWac>,
+    // the WAC of the securities held in each depot, by broker
+    pub depot_wacs: BTreeMap<String, BTreeMap<String, SecWac>>,
     pub currency_wacs: BTreeMap<String, FxWac>,
+    pub crypto_wacs: BTreeMap<String, CryptoWac>,
+    pub derivative_wacs: BTreeMap<String, DerivativeWac>,
+    pub non_reporting_fund_income: Vec<NonReportingFundIncome>,
+    pub settlements: BTreeMap<i32, TaxSettlement>,
+    // the amounts before rounding, to settle them again without accumulating rounding errors
+    #[serde(skip)]
+    pub unrounded_taxable_amounts: BTreeMap<i32, AnnualTaxableAmounts>,
+    #[serde(skip)]
+    pub unrounded_crypto_amounts: BTreeMap<i32, AnnualCryptoAmounts>,
+    // including positions sold completely and short ones
+    #[serde(skip)]
+    pub unrounded_depot_wacs: BTreeMap<String, BTreeMap<String, SecWac>>,
+    // to run the engine again with additional events, without loading the stored ones again
+    #[serde(skip)]
+    pub stored_events: Vec<PortfolioEvent>,
+}
+
+impl TaxationReport {
+    /// The settlement of a year if an additional gain (a loss if negative) and FX appreciation
+    /// were realised.
+    pub fn simulate_settlement(
+        &self,
+        year: i32,
+        capital_gain: Decimal,
+        fx_appreciation: Decimal,
+    ) -> TaxSettlement {
+        let mut amounts = self
+            .unrounded_taxable_amounts
+            .get(&year)
+            .cloned()
+            .unwrap_or_default();
+        if capital_gain > dec!(0) {
+            amounts.capital_gains += capital_gain;
+        } else {
+            amounts.capital_losses -= capital_gain;
+        }
+        amounts.fx_appreciation += fx_appreciation;
+
+        let mut settlement = TaxSettlement::calculate(
+            &amounts,
+            &self
+                .unrounded_crypto_amounts
+                .get(&year)
+                .cloned()
+                .unwrap_or_default(),
+            &TaxRates::austrian(),
+        );
+        settlement.round_all(2);
+        settlement
+    }
 }
 
 #[typeshare]
@@ -98,25 +329,79 @@ impl FxWac {
     }
 }
 
+// units acquired before 2011, or before April 2012 for bonds, ("Altbestand") are kept apart from
+// the ones acquired since ("Neubestand"), their sale isn't taxed
 #[typeshare]
-#[derive(Debug, Tabled, Serialize)]
+#[derive(Debug, Clone, Tabled, Serialize)]
 pub struct SecWac {
     pub units: Decimal,
     pub average_cost: Decimal,
     pub weighted_avg_fx_rate: Decimal,
     pub name: String,
+    pub currency: String,
+    pub old_units: Decimal,
+    // in EUR, it only tells the tax-free gain of a sale
+    pub old_average_cost: Decimal,
 }
 
 impl SecWac {
+    // short positions have negative units and keep the average sale price as their cost
+    fn open_short(&mut self, units: Decimal, price: Decimal, fx_rate: Decimal) {
+        let short_units = -self.units.min(dec!(0));
+        let total_proceeds = short_units * self.average_cost + units * price;
+
+        if total_proceeds != dec!(0) {
+            self.weighted_avg_fx_rate =
+                (self.weighted_avg_fx_rate * short_units * self.average_cost
+                    + units * price * fx_rate)
+                    / total_proceeds;
+        }
+        self.average_cost = total_proceeds / (short_units + units);
+        self.units -= units;
+    }
+
     fn round_all(&mut self) {
         trace!(target: "tax_report", "Rounding SecWAC values");
 
         self.units = self.units.round_dp(4);
         self.average_cost = self.average_cost.round_dp(2);
         self.weighted_avg_fx_rate = self.weighted_avg_fx_rate.round_dp(2);
+        self.old_units = self.old_units.round_dp(4);
+        self.old_average_cost = self.old_average_cost.round_dp(2);
     }
 
-    fn update(&mut self, event: &PortfolioEvent) -> Result<()> {
+    fn adjust_for_splits(
+        &mut self,
+        identifier: &str,
+        date: DateTime<Utc>,
+        stock_split_information: &mut [StockSplit],
+    ) {
+        self.units =
+            get_split_adjusted_units(identifier, self.units, date, stock_split_information);
+        self.average_cost = get_split_adjusted_price_per_unit(
+            identifier,
+            self.average_cost,
+            date,
+            stock_split_information,
+        );
+        self.old_units =
+            get_split_adjusted_units(identifier, self.old_units, date, stock_split_information);
+        self.old_average_cost = get_split_adjusted_price_per_unit(
+            identifier,
+            self.old_average_cost,
+            date,
+            stock_split_information,
+        );
+    }
+
+    // Altbestand is treated as sold first, returns the units taken from it
+    fn remove_old_units(&mut self, units: Decimal) -> Decimal {
+        let old_units = units.min(self.old_units);
+        self.old_units -= old_units;
+        old_units
+    }
+
+    fn update(&mut self, event: &PortfolioEvent, altbestand_cutoff: DateTime<Utc>) -> Result<()> {
         debug!(target: "tax_report", "Updating security WAC for event: {:?}", event);
 
         let new_units = event.units;
@@ -125,6 +410,13 @@ impl SecWac {
             .applied_fx_rate
             .context("Missing FX rate for security trade")?;
 
+        if event.date < altbestand_cutoff {
+            let old_cost = self.old_units * self.old_average_cost + new_units * new_cost / fx_rate;
+            self.old_units += new_units;
+            self.old_average_cost = old_cost / self.old_units;
+            return Ok(());
+        }
+
         let total_cost = self.units * self.average_cost + new_units * new_cost;
 
         if total_cost != dec!(0) {
@@ -143,6 +435,243 @@ impl SecWac {
     }
 }
 
+// the average cost of a crypto asset is kept across all wallets and brokers, separately for
+// Altvermögen and assets acquired since March 2021 ("Neuvermögen")
+#[typeshare]
+#[derive(Debug, Default, Tabled, Serialize)]
+pub struct CryptoWac {
+    pub units: Decimal,
+    pub average_cost: Decimal,
+    pub old_units: Decimal,
+    pub old_average_cost: Decimal,
+}
+
+// units and their cost taken out of a crypto WAC, split into Altvermögen and Neuvermögen
+#[derive(Debug, Clone, Copy)]
+struct CryptoDisposal {
+    units: Decimal,
+    cost: Decimal,
+    old_units: Decimal,
+    old_cost: Decimal,
+}
+
+impl CryptoWac {
+    fn round_all(&mut self) {
+        self.units = self.units.round_dp(8);
+        self.average_cost = self.average_cost.round_dp(2);
+        self.old_units = self.old_units.round_dp(8);
+        self.old_average_cost = self.old_average_cost.round_dp(2);
+    }
+
+    fn add(&mut self, units: Decimal, cost: Decimal, old_units: Decimal, old_cost: Decimal) {
+        if self.units + units > dec!(0) {
+            self.average_cost = (self.units * self.average_cost + cost) / (self.units + units);
+        }
+        if self.old_units + old_units > dec!(0) {
+            self.old_average_cost =
+                (self.old_units * self.old_average_cost + old_cost) / (self.old_units + old_units);
+        }
+        self.units += units;
+        self.old_units += old_units;
+    }
+
+    // Altvermögen is treated as disposed of first
+    fn remove(&mut self, units: Decimal) -> CryptoDisposal {
+        let old_units = units.min(self.old_units);
+        let new_units = (units - old_units).min(self.units);
+        if new_units < units - old_units {
+            warn!(target: "tax_report",
+                "Selling {} units of crypto while only {} are held, the remainder has no acquisition cost",
+                units, self.units + self.old_units
+            );
+        }
+        self.old_units -= old_units;
+        self.units -= new_units;
+
+        CryptoDisposal {
+            units: units - old_units,
+            cost: new_units * self.average_cost,
+            old_units,
+            old_cost: old_units * self.old_average_cost,
+        }
+    }
+}
+
+// both legs of a crypto swap are stored as trades, the swap links them so the acquisition cost
+// carries over to the asset received instead of realizing a gain
+#[derive(Default)]
+struct CryptoSwapLegs {
+    // (date, broker, identifier) of a leg to the swap ID and whether it's the asset given up
+    legs: HashMap<(DateTime<Utc>, String, String), (String, bool)>,
+    disposals: HashMap<String, CryptoDisposal>,
+    pending_receipts: HashMap<String, PortfolioEvent>,
+}
+
+impl CryptoSwapLegs {
+    fn new(crypto_swaps: Vec<CryptoSwap>) -> CryptoSwapLegs {
+        let mut swap_legs = CryptoSwapLegs::default();
+        for swap in crypto_swaps {
+            swap_legs.legs.insert(
+                (swap.date, swap.broker.clone(), swap.from_identifier),
+                (swap.id.clone(), true),
+            );
+            swap_legs.legs.insert(
+                (swap.date, swap.broker, swap.to_identifier),
+                (swap.id, false),
+            );
+        }
+        swap_legs
+    }
+
+    fn find(&self, event: &PortfolioEvent, identifier: &str) -> Option<(String, bool)> {
+        self.legs
+            .get(&(event.date, event.broker.clone(), identifier.to_string()))
+            .cloned()
+    }
+}
+
+// open positions in options and futures, written options and short futures have negative units
+#[typeshare]
+#[derive(Debug, Default, Tabled, Serialize)]
+pub struct DerivativeWac {
+    pub units: Decimal,
+    // in EUR per contract, the purchase price of long and the sale price of short positions
+    pub average_cost: Decimal,
+    // units of the underlying per contract
+    pub multiplier: Decimal,
+}
+
+impl DerivativeWac {
+    fn round_all(&mut self) {
+        self.units = self.units.round_dp(4);
+        self.average_cost = self.average_cost.round_dp(2);
+    }
+
+    // units are negative when opening a short position
+    fn open(&mut self, units: Decimal, price: Decimal) {
+        let total_units = self.units.abs() + units.abs();
+        if total_units > dec!(0) {
+            self.average_cost =
+                (self.units.abs() * self.average_cost + units.abs() * price) / total_units;
+        }
+        self.units += units;
+    }
+
+    // returns the average cost of the units closed
+    fn close(&mut self, units: Decimal) -> Decimal {
+        if self.units > dec!(0) {
+            self.units -= units;
+        } else {
+            self.units += units;
+        }
+        units * self.average_cost
+    }
+}
+
+// the option leg of an exercise or assignment and the trade in the underlying are stored as
+// separate trades, the exercise links them so the cost of an exercised option carries over to
+// the underlying instead of being realized as a loss
+#[derive(Default)]
+struct OptionExerciseLegs {
+    // (day, broker, identifier) of a leg to the exercise
+    legs: HashMap<(NaiveDate, String, String), OptionExercise>,
+    carried_costs: HashMap<String, Decimal>,
+    pending_underlyings: HashMap<String, PortfolioEvent>,
+}
+
+impl OptionExerciseLegs {
+    fn new(option_exercises: Vec<OptionExercise>) -> OptionExerciseLegs {
+        let mut exercise_legs = OptionExerciseLegs::default();
+        for exercise in option_exercises {
+            let day = exercise.date.date_naive();
+            exercise_legs.legs.insert(
+                (
+                    day,
+                    exercise.broker.clone(),
+                    exercise.option_identifier.clone(),
+                ),
+                exercise.clone(),
+            );
+            exercise_legs.legs.insert(
+                (
+                    day,
+                    exercise.broker.clone(),
+                    exercise.underlying_identifier.clone(),
+                ),
+                exercise,
+            );
+        }
+        exercise_legs
+    }
+
+    fn find(&self, event: &PortfolioEvent, identifier: &str) -> Option<&OptionExercise> {
+        self.legs.get(&(
+            event.date.date_naive(),
+            event.broker.clone(),
+            identifier.to_string(),
+        ))
+    }
+}
+
+// funds held at the end of a year without an OeKB report for it are taxed with the lump-sum rule
+// for non-reporting funds
+struct NonReportingFunds {
+    funds: HashMap<String, String>,
+    report_years: HashSet<(String, i32)>,
+    prices: HashMap<String, Vec<FundPrice>>,
+}
+
+impl NonReportingFunds {
+    fn new(
+        classifications: Vec<FundClassification>,
+        report_years: Vec<(String, i32)>,
+        prices: Vec<FundPrice>,
+    ) -> NonReportingFunds {
+        NonReportingFunds {
+            funds: classifications
+                .into_iter()
+                .map(|classification| (classification.isin, classification.kind))
+                .collect(),
+            report_years: report_years.into_iter().collect(),
+            prices: prices
+                .into_iter()
+                .map(|price| (price.isin.clone(), price))
+                .into_group_map(),
+        }
+    }
+
+    // the first and the last price of the year, prices are sorted by date
+    fn prices_in_year(&self, isin: &str, year: i32) -> Option<(&FundPrice, &FundPrice)> {
+        let prices = self
+            .prices
+            .get(isin)?
+            .iter()
+            .filter(|price| price.date.year() == year)
+            .collect_vec();
+        Some((*prices.first()?, *prices.last()?))
+    }
+}
+
+#[typeshare]
+#[derive(Debug, Tabled, Serialize)]
+pub struct NonReportingFundIncome {
+    pub year: i32,
+    pub isin: String,
+    pub units: Decimal,
+    #[tabled(display = "display_option")]
+    pub first_price: Option<Decimal>,
+    #[tabled(display = "display_option")]
+    pub last_price: Option<Decimal>,
+    pub currency: String,
+    // in the currency of the prices
+    pub income_per_unit: Decimal,
+    pub income_eur: Decimal,
+}
+
+fn display_option(value: &Option<Decimal>) -> String {
+    value.map_or("missing".to_string(), |value| value.to_string())
+}
+
 #[derive(Debug, Serialize)]
 pub struct TaxRates {
     pub interest: Decimal,
@@ -150,10 +679,29 @@ pub struct TaxRates {
     pub dividends: Decimal,
 }
 
+impl TaxRates {
+    pub fn austrian() -> TaxRates {
+        TaxRates {
+            interest: dec!(0.25),
+            capital_gains: dec!(0.275),
+            dividends: dec!(0.275),
+        }
+    }
+}
+
 struct ProcessingContext<'a> {
     taxable_amounts: &'a mut BTreeMap<i32, AnnualTaxableAmounts>,
+    crypto_amounts: &'a mut BTreeMap<i32, AnnualCryptoAmounts>,
     currency_wacs: &'a mut BTreeMap<String, FxWac>,
     securities_wacs: &'a mut BTreeMap<String, SecWac>,
+    depot_wacs: &'a mut BTreeMap<String, BTreeMap<String, SecWac>>,
+    crypto_wacs: &'a mut BTreeMap<String, CryptoWac>,
+    crypto_swap_legs: &'a mut CryptoSwapLegs,
+    derivative_wacs: &'a mut BTreeMap<String, DerivativeWac>,
+    option_exercise_legs: &'a mut OptionExerciseLegs,
+    non_reporting_funds: &'a NonReportingFunds,
+    bonds: &'a HashSet<String>,
+    non_reporting_fund_income: &'a mut Vec<NonReportingFundIncome>,
     tax_rates: &'a TaxRates,
     year: i32,
     stock_split_information: &'a mut [StockSplit],
@@ -168,18 +716,36 @@ impl ProcessingContext<'_> {
             .or_insert_with(|| AnnualTaxableAmounts {
                 cash_interest: dec!(0.0),
                 share_lending_interest: dec!(0.0),
+                bond_interest: dec!(0.0),
                 capital_gains: dec!(0.0),
+                derivative_gains: dec!(0.0),
+                derivative_losses: dec!(0.0),
                 dividends: dec!(0.0),
                 fx_appreciation: dec!(0.0),
                 dividend_equivalents: dec!(0.0),
+                non_reporting_fund_income: dec!(0.0),
                 capital_losses: dec!(0.0),
+                tax_free_gains: dec!(0.0),
                 withheld_tax_capital_gains: dec!(0.0),
                 withheld_tax_dividends: dec!(0.0),
                 withheld_tax_interest: dec!(0.0),
+                domestic_tax_dividends: dec!(0.0),
+                creditable_tax_dividends: dec!(0.0),
+                creditable_tax_interest: dec!(0.0),
                 tax_optimization_adjustment: dec!(0.0),
             })
     }
 
+    fn get_crypto_year_entry(&mut self) -> &mut AnnualCryptoAmounts {
+        self.crypto_amounts
+            .entry(self.year)
+            .or_insert_with(|| AnnualCryptoAmounts {
+                gains: dec!(0.0),
+                losses: dec!(0.0),
+                tax_free_gains: dec!(0.0),
+            })
+    }
+
     fn should_count_taxable(&self, event_date: DateTime<Utc>) -> bool {
         if let Some(from) = self.from_date {
             if event_date < from {
@@ -199,9 +765,10 @@ async fn process_event(event: PortfolioEvent, ctx: &mut ProcessingContext<'_>) -
     info!(target: "tax_report", "Processing event: {:?} ({:?}) on {:?}", event.identifier.clone().unwrap_or("No identifier".to_string()), event.event_type, event.date);
 
     match event.event_type {
-        EventType::CashInterest | EventType::ShareInterest | EventType::Dividend => {
-            process_interest_or_dividend(event, ctx).await
-        }
+        EventType::CashInterest
+        | EventType::ShareInterest
+        | EventType::BondInterest
+        | EventType::Dividend => process_interest_or_dividend(event, ctx).await,
         EventType::Trade => process_trade(event, ctx).await,
         EventType::FxConversion => process_fx_conversion(event, ctx).await,
         EventType::DividendAequivalent => process_dividend_aequivalent(event, ctx).await,
@@ -236,7 +803,8 @@ async fn process_interest_or_dividend(
         }
     };
 
-    if currency != "EUR" {
+    // accrued interest paid on bond purchases is negative and doesn't add to the currency held
+    if currency != "EUR" && event.units > dec!(0) {
         ctx.currency_wacs
             .entry(currency.clone())
             .and_modify(|wac| wac.update(event.units, fx_rate))
@@ -250,6 +818,7 @@ async fn process_interest_or_dividend(
     let tax_type = match event.event_type {
         EventType::CashInterest => "interest",
         EventType::ShareInterest => "capital_gains",
+        EventType::BondInterest => "bond_interest",
         EventType::Dividend => "dividends",
         _ => unreachable!(),
     };
@@ -282,7 +851,7 @@ fn calculate_taxable_values(
                 withheld_tax_percent
             }
         }
-        EventType::ShareInterest => {
+        EventType::ShareInterest | EventType::BondInterest => {
             if withheld_tax_percent > ctx.tax_rates.capital_gains {
                 ctx.tax_rates.capital_gains
             } else {
@@ -331,6 +900,11 @@ fn apply_taxation(
             year_entry.share_lending_interest += taxed_amount + withheld_tax;
             year_entry.withheld_tax_dividends += withheld_tax;
         }
+        // taxed at the special rate like other capital income, unlike interest on cash
+        "bond_interest" => {
+            year_entry.bond_interest += taxed_amount + withheld_tax;
+            year_entry.withheld_tax_interest += withheld_tax;
+        }
         "dividends" => {
             year_entry.dividends += taxed_amount + withheld_tax;
             year_entry.withheld_tax_dividends += withheld_tax;
@@ -346,15 +920,57 @@ async fn process_trade(event: PortfolioEvent, ctx: &mut ProcessingContext<'_>) -
 
     let direction = event.clone().direction.context("Missing trade direction")?;
 
+    if event
+        .identifier
+        .as_deref()
+        .is_some_and(is_crypto_identifier)
+    {
+        return process_crypto_trade(event, direction, ctx);
+    }
+    if event
+        .identifier
+        .as_deref()
+        .is_some_and(is_derivative_identifier)
+    {
+        return process_derivative_trade(event, direction, ctx).await;
+    }
+    let event = match link_exercised_underlying(event, ctx) {
+        Some(event) => event,
+        None => return Ok(()),
+    };
+
     match direction {
         TradeDirection::Buy => process_buy(event, ctx).await,
         TradeDirection::Sell => process_sell(event, ctx).await,
+        TradeDirection::TransferOut => process_transfer_out(event, ctx),
     }
 }
 
-async fn process_buy(event: PortfolioEvent, ctx: &mut ProcessingContext<'_>) -> Result<()> {
+async fn process_buy(mut event: PortfolioEvent, ctx: &mut ProcessingContext<'_>) -> Result<()> {
     info!(target: "tax_report", "Processing BUY transaction for {:?}", event.identifier);
 
+    let identifier = event
+        .identifier
+        .clone()
+        .context("Missing security identifier")?;
+    let covered_units = cover_short(&event, &identifier, ctx)?;
+    if covered_units >= event.units {
+        return Ok(());
+    }
+    event.units -= covered_units;
+
+    let altbestand_cutoff = altbestand_cutoff(&identifier, ctx.bonds);
+    if event.date >= altbestand_cutoff
+        && event.date < bond_altbestand_cutoff()
+        && ctx.non_reporting_funds.funds.contains_key(&identifier)
+    {
+        warn!(
+            "{} was bought on {} and is taxed as Neubestand, please check whether it's a bond fund, those are Altbestand if bought before April 2012",
+            identifier,
+            event.date.date_naive()
+        );
+    }
+
     ctx.securities_wacs
         .entry(
             event
@@ -364,7 +980,7 @@ async fn process_buy(event: PortfolioEvent, ctx: &mut ProcessingContext<'_>) ->
         )
         .and_modify(|sec_wac| {
             sec_wac
-                .update(&event)
+                .update(&event, altbestand_cutoff)
                 .expect("Failed to update security WAC")
         })
         .or_insert({
@@ -376,11 +992,30 @@ async fn process_buy(event: PortfolioEvent, ctx: &mut ProcessingContext<'_>) ->
                     .name
                     .clone()
                     .unwrap_or(event.identifier.clone().unwrap()),
+                currency: event.currency.clone(),
+                old_units: dec!(0.0),
+                old_average_cost: dec!(0.0),
             };
-            sec_wac.update(&event)?;
+            sec_wac.update(&event, altbestand_cutoff)?;
             sec_wac
         });
 
+    // each depot keeps its own WAC of the units bought there
+    ctx.depot_wacs
+        .entry(event.broker.clone())
+        .or_default()
+        .entry(identifier.clone())
+        .or_insert(SecWac {
+            units: dec!(0.0),
+            average_cost: dec!(0.0),
+            weighted_avg_fx_rate: dec!(0.0),
+            name: event.name.clone().unwrap_or(identifier.clone()),
+            currency: event.currency.clone(),
+            old_units: dec!(0.0),
+            old_average_cost: dec!(0.0),
+        })
+        .update(&event, altbestand_cutoff)?;
+
     if event.currency != "EUR" {
         process_fx_buy(event, ctx).await?;
     }
@@ -388,29 +1023,68 @@ async fn process_buy(event: PortfolioEvent, ctx: &mut ProcessingContext<'_>) ->
     Ok(())
 }
 
-async fn process_sell(event: PortfolioEvent, ctx: &mut ProcessingContext<'_>) -> Result<()> {
+async fn process_sell(mut event: PortfolioEvent, ctx: &mut ProcessingContext<'_>) -> Result<()> {
     info!(target: "tax_report", "Processing SELL transaction for {:?}", event.identifier);
 
     let identifier = event
         .identifier
         .clone()
         .context("Missing security identifier")?;
-    let units = event.units;
 
     if let Some(sec_wac) = ctx.securities_wacs.get_mut(&identifier) {
-        sec_wac.units = get_split_adjusted_units(
-            &identifier,
-            sec_wac.units,
-            event.date,
-            ctx.stock_split_information,
-        );
-        sec_wac.units -= units;
-        sec_wac.average_cost = get_split_adjusted_price_per_unit(
-            &identifier,
-            sec_wac.average_cost,
-            event.date,
-            ctx.stock_split_information,
-        )
+        sec_wac.adjust_for_splits(&identifier, event.date, ctx.stock_split_information);
+    }
+
+    // units sold beyond the ones held are sold short, once the held ones are sold
+    let held_units = ctx
+        .securities_wacs
+        .get(&identifier)
+        .map_or(dec!(0), |sec_wac| {
+            sec_wac.units.max(dec!(0)) + sec_wac.old_units
+        });
+    let short_units = (event.units - held_units).max(dec!(0));
+    event.units -= short_units;
+    let short_sale = event.clone();
+
+    if event.units > dec!(0) {
+        sell_held_units(event, &identifier, ctx).await?;
+    }
+    if short_units > dec!(0) {
+        open_short(&short_sale, &identifier, short_units, ctx);
+    }
+    Ok(())
+}
+
+// the withholding tax only counts for the units of the Neubestand disposed of, not for the ones
+// sold short or from the Altbestand
+async fn sell_held_units(
+    mut event: PortfolioEvent,
+    identifier: &str,
+    ctx: &mut ProcessingContext<'_>,
+) -> Result<()> {
+    sell_from_depot(&event, identifier, ctx);
+
+    let (old_units, tax_free_gain) = match ctx.securities_wacs.get_mut(identifier) {
+        Some(sec_wac) => {
+            let old_units = sec_wac.remove_old_units(event.units);
+            sec_wac.units -= event.units - old_units;
+            let eur_price = event.price_unit / event.applied_fx_rate.unwrap_or(dec!(1.0));
+            (
+                old_units,
+                old_units * (eur_price - sec_wac.old_average_cost),
+            )
+        }
+        None => (dec!(0), dec!(0)),
+    };
+    if old_units > dec!(0) {
+        info!(target: "tax_report", "Selling {} units of {} from Altbestand tax-free", old_units, identifier);
+        if ctx.should_count_taxable(event.date) {
+            ctx.get_year_entry().tax_free_gains += tax_free_gain;
+        }
+        event.units -= old_units;
+        if event.units == dec!(0) {
+            return Ok(());
+        }
     }
 
     if ctx.should_count_taxable(event.date) {
@@ -427,15 +1101,176 @@ async fn process_sell(event: PortfolioEvent, ctx: &mut ProcessingContext<'_>) ->
         }
 
         if event.currency == "EUR" {
-            process_eur_sell(event, ctx, &identifier)?;
+            process_eur_sell(event, ctx, identifier)?;
         } else {
-            process_fx_sell(event, ctx, &identifier).await?;
+            process_fx_sell(event, ctx, identifier).await?;
         }
     }
 
     Ok(())
 }
 
+// units leave the depot and the overall WAC at their average cost without being taxed, the receiving
+// depot books them as a buy, so they're held at their original cost again
+fn process_transfer_out(event: PortfolioEvent, ctx: &mut ProcessingContext<'_>) -> Result<()> {
+    info!(target: "tax_report", "Processing TRANSFER OUT for {:?}", event.identifier);
+
+    let identifier = event
+        .identifier
+        .clone()
+        .context("Missing security identifier")?;
+
+    if let Some(sec_wac) = ctx.securities_wacs.get_mut(&identifier) {
+        sec_wac.adjust_for_splits(&identifier, event.date, ctx.stock_split_information);
+        let old_units = sec_wac.remove_old_units(event.units);
+        sec_wac.units -= event.units - old_units;
+    }
+    sell_from_depot(&event, &identifier, ctx);
+    Ok(())
+}
+
+// units transferred between depots aren't followed, so selling held units never takes a depot
+// below zero units, nor deeper into a short position
+fn sell_from_depot(event: &PortfolioEvent, identifier: &str, ctx: &mut ProcessingContext<'_>) {
+    let Some(depot_wac) = ctx
+        .depot_wacs
+        .get_mut(&event.broker)
+        .and_then(|wacs| wacs.get_mut(identifier))
+    else {
+        return;
+    };
+    depot_wac.adjust_for_splits(identifier, event.date, ctx.stock_split_information);
+    let old_units = depot_wac.remove_old_units(event.units);
+    depot_wac.units =
+        (depot_wac.units - (event.units - old_units)).max(depot_wac.units.min(dec!(0)));
+}
+
+// the WAC across all depots and the ones of each depot, for adjustments per unit held
+fn wacs_of_security<'b>(
+    identifier: &'b str,
+    ctx: &'b mut ProcessingContext<'_>,
+) -> impl Iterator<Item = &'b mut SecWac> {
+    ctx.securities_wacs.get_mut(identifier).into_iter().chain(
+        ctx.depot_wacs
+            .values_mut()
+            .filter_map(move |wacs| wacs.get_mut(identifier)),
+    )
+}
+
+// a short sale isn't taxed when it's opened, its proceeds are kept as the average cost of the
+// negative units instead, overall and in the depot it's sold from
+fn open_short(
+    event: &PortfolioEvent,
+    identifier: &str,
+    units: Decimal,
+    ctx: &mut ProcessingContext<'_>,
+) {
+    info!(target: "tax_report", "Opening short position of {} units in {}", units, identifier);
+
+    let empty_wac = || SecWac {
+        units: dec!(0.0),
+        average_cost: dec!(0.0),
+        weighted_avg_fx_rate: dec!(0.0),
+        name: event.name.clone().unwrap_or(identifier.to_string()),
+        currency: event.currency.clone(),
+        old_units: dec!(0.0),
+        old_average_cost: dec!(0.0),
+    };
+    let fx_rate = event.applied_fx_rate.unwrap_or(dec!(1.0));
+    ctx.securities_wacs
+        .entry(identifier.to_string())
+        .or_insert_with(empty_wac)
+        .open_short(units, event.price_unit, fx_rate);
+    ctx.depot_wacs
+        .entry(event.broker.clone())
+        .or_default()
+        .entry(identifier.to_string())
+        .or_insert_with(empty_wac)
+        .open_short(units, event.price_unit, fx_rate);
+}
+
+// a short sale and the purchase covering it are taxed as one disposal once the short is covered,
+// returns the units covered
+fn cover_short(
+    event: &PortfolioEvent,
+    identifier: &str,
+    ctx: &mut ProcessingContext<'_>,
+) -> Result<Decimal> {
+    let Some(sec_wac) = ctx.securities_wacs.get_mut(identifier) else {
+        return Ok(dec!(0));
+    };
+    if sec_wac.units >= dec!(0) {
+        return Ok(dec!(0));
+    }
+    sec_wac.units = get_split_adjusted_units(
+        identifier,
+        sec_wac.units,
+        event.date,
+        ctx.stock_split_information,
+    );
+    sec_wac.average_cost = get_split_adjusted_price_per_unit(
+        identifier,
+        sec_wac.average_cost,
+        event.date,
+        ctx.stock_split_information,
+    );
+
+    let covered_units = event.units.min(-sec_wac.units);
+    let short_fx_rate = if sec_wac.weighted_avg_fx_rate == dec!(0) {
+        dec!(1.0)
+    } else {
+        sec_wac.weighted_avg_fx_rate
+    };
+    let fx_rate = event.applied_fx_rate.unwrap_or(dec!(1.0));
+    let gain = covered_units * (sec_wac.average_cost / short_fx_rate - event.price_unit / fx_rate);
+    sec_wac.units += covered_units;
+    info!(target: "tax_report", "Covering {} units of short position in {}", covered_units, identifier);
+    cover_depot_shorts(event, identifier, covered_units, ctx);
+
+    if ctx.should_count_taxable(event.date) {
+        let year_entry = ctx.get_year_entry();
+        if gain > dec!(0.0) {
+            year_entry.capital_gains += gain;
+        } else {
+            year_entry.capital_losses -= gain;
+        }
+    }
+    Ok(covered_units)
+}
+
+// the short positions of the depots are covered along with the overall one, the one of the buying
+// depot first
+fn cover_depot_shorts(
+    event: &PortfolioEvent,
+    identifier: &str,
+    units: Decimal,
+    ctx: &mut ProcessingContext<'_>,
+) {
+    let mut brokers: Vec<String> = ctx.depot_wacs.keys().cloned().collect();
+    brokers.sort_by_key(|broker| *broker != event.broker);
+
+    let mut remaining = units;
+    for broker in brokers {
+        let Some(depot_wac) = ctx
+            .depot_wacs
+            .get_mut(&broker)
+            .and_then(|wacs| wacs.get_mut(identifier))
+        else {
+            continue;
+        };
+        if remaining <= dec!(0) {
+            break;
+        }
+        if depot_wac.units >= dec!(0) {
+            continue;
+        }
+        depot_wac.adjust_for_splits(identifier, event.date, ctx.stock_split_information);
+        let covered_units = remaining.min(-depot_wac.units);
+        depot_wac.units += covered_units;
+        remaining -= covered_units;
+    }
+}
+
 fn process_eur_sell(
     event: PortfolioEvent,
     ctx: &mut ProcessingContext<'_>,
@@ -503,6 +1338,271 @@ async fn process_fx_sell(
     Ok(())
 }
 
+// shares and funds acquired before 2011 fall under the former speculation period, bonds acquired
+// before April 2012 as well; bond funds aren't told apart from other funds
+fn altbestand_cutoff(identifier: &str, bonds: &HashSet<String>) -> DateTime<Utc> {
+    if bonds.contains(identifier) {
+        bond_altbestand_cutoff()
+    } else {
+        Utc.with_ymd_and_hms(2011, 1, 1, 0, 0, 0).unwrap()
+    }
+}
+
+fn bond_altbestand_cutoff() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2012, 4, 1, 0, 0, 0).unwrap()
+}
+
+fn crypto_altvermoegen_cutoff() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2021, 3, 1, 0, 0, 0).unwrap()
+}
+
+fn crypto_special_rate_start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2022, 3, 1, 0, 0, 0).unwrap()
+}
+
+fn process_crypto_trade(
+    event: PortfolioEvent,
+    direction: TradeDirection,
+    ctx: &mut ProcessingContext<'_>,
+) -> Result<()> {
+    let identifier = event
+        .identifier
+        .clone()
+        .context("Missing crypto identifier")?;
+    let eur_price = event.price_unit / event.applied_fx_rate.unwrap_or(dec!(1.0));
+    let swap_leg = ctx.crypto_swap_legs.find(&event, &identifier);
+
+    match (direction, swap_leg) {
+        // the asset given up in a swap passes its acquisition cost on to the one received
+        (TradeDirection::Sell, Some((swap_id, true))) => {
+            let disposal = ctx
+                .crypto_wacs
+                .entry(identifier)
+                .or_default()
+                .remove(event.units);
+            ctx.crypto_swap_legs
+                .disposals
+                .insert(swap_id.clone(), disposal);
+            if let Some(receipt) = ctx.crypto_swap_legs.pending_receipts.remove(&swap_id) {
+                add_crypto_swap_receipt(receipt, disposal, ctx)?;
+            }
+        }
+        (TradeDirection::Buy, Some((swap_id, false))) => {
+            match ctx.crypto_swap_legs.disposals.remove(&swap_id) {
+                Some(disposal) => add_crypto_swap_receipt(event, disposal, ctx)?,
+                // both legs share the same timestamp, so the receipt may come first
+                None => {
+                    ctx.crypto_swap_legs.pending_receipts.insert(swap_id, event);
+                }
+            }
+        }
+        (TradeDirection::Buy, _) => {
+            let wac = ctx.crypto_wacs.entry(identifier).or_default();
+            let cost = event.units * eur_price;
+            if event.date < crypto_altvermoegen_cutoff() {
+                wac.add(dec!(0), dec!(0), event.units, cost);
+            } else {
+                wac.add(event.units, cost, dec!(0), dec!(0));
+            }
+        }
+        (TradeDirection::TransferOut, _) => {
+            bail!("Transfers out of {} aren't supported", identifier)
+        }
+        (TradeDirection::Sell, _) => {
+            let disposal = ctx
+                .crypto_wacs
+                .entry(identifier.clone())
+                .or_default()
+                .remove(event.units);
+
+            if !ctx.should_count_taxable(event.date) {
+                return Ok(());
+            }
+            if event.date < crypto_special_rate_start() {
+                warn!(target: "tax_report",
+                    "Sale of {} on {} predates the taxation of crypto as capital income and isn't included",
+                    identifier, event.date
+                );
+                return Ok(());
+            }
+
+            let gain = disposal.units * eur_price - disposal.cost;
+            let tax_free_gain = disposal.old_units * eur_price - disposal.old_cost;
+            let year_entry = ctx.get_crypto_year_entry();
+            if gain > dec!(0.0) {
+                year_entry.gains += gain;
+            } else {
+                year_entry.losses -= gain;
+            }
+            year_entry.tax_free_gains += tax_free_gain;
+        }
+    }
+
+    Ok(())
+}
+
+fn add_crypto_swap_receipt(
+    event: PortfolioEvent,
+    disposal: CryptoDisposal,
+    ctx: &mut ProcessingContext<'_>,
+) -> Result<()> {
+    let identifier = event
+        .identifier
+        .clone()
+        .context("Missing crypto identifier")?;
+    let units_given = disposal.units + disposal.old_units;
+    // the units received keep the share of Altvermögen of the units given up
+    let old_share = if units_given > dec!(0) {
+        disposal.old_units / units_given
+    } else {
+        dec!(0)
+    };
+
+    ctx.crypto_wacs.entry(identifier).or_default().add(
+        event.units * (dec!(1) - old_share),
+        disposal.cost,
+        event.units * old_share,
+        disposal.old_cost,
+    );
+    Ok(())
+}
+
+// Gains on options and futures are realized when a position is closed. Premiums received for
+// written options are taxed when received though, buying them back is a loss.
+async fn process_derivative_trade(
+    event: PortfolioEvent,
+    direction: TradeDirection,
+    ctx: &mut ProcessingContext<'_>,
+) -> Result<()> {
+    let identifier = event
+        .identifier
+        .clone()
+        .context("Missing derivative identifier")?;
+    let eur_price = event.price_unit / event.applied_fx_rate.unwrap_or(dec!(1.0));
+    let is_option = is_option_identifier(&identifier);
+    let exercise = ctx
+        .option_exercise_legs
+        .find(&event, &identifier)
+        .filter(|exercise| exercise.kind == "Exercise")
+        .cloned();
+
+    let wac = ctx.derivative_wacs.entry(identifier.clone()).or_default();
+    let held = wac.units;
+    let closing_units = match direction {
+        TradeDirection::Buy if held < dec!(0) => event.units.min(-held),
+        TradeDirection::Sell if held > dec!(0) => event.units.min(held),
+        _ => dec!(0),
+    };
+    let opening_units = event.units - closing_units;
+
+    let mut gain = dec!(0);
+    match direction {
+        TradeDirection::Sell => {
+            let cost = wac.close(closing_units);
+            wac.open(-opening_units, eur_price);
+            if is_option {
+                // premium of a written option
+                gain += opening_units * eur_price;
+            }
+            match exercise {
+                Some(exercise) => {
+                    ctx.option_exercise_legs
+                        .carried_costs
+                        .insert(exercise.id.clone(), cost);
+                    if let Some(underlying) = ctx
+                        .option_exercise_legs
+                        .pending_underlyings
+                        .remove(&exercise.id)
+                    {
+                        process_exercised_underlying(underlying, cost, ctx).await?;
+                    }
+                }
+                None => gain += closing_units * eur_price - cost,
+            }
+        }
+        TradeDirection::TransferOut => bail!("Transfers out of {} aren't supported", identifier),
+        TradeDirection::Buy => {
+            let proceeds = wac.close(closing_units);
+            wac.open(opening_units, eur_price);
+            gain += if is_option {
+                -closing_units * eur_price
+            } else {
+                proceeds - closing_units * eur_price
+            };
+        }
+    }
+
+    if !ctx.should_count_taxable(event.date) || gain == dec!(0) {
+        return Ok(());
+    }
+    let year_entry = ctx.get_year_entry();
+    if gain > dec!(0.0) {
+        year_entry.derivative_gains += gain;
+    } else {
+        year_entry.derivative_losses -= gain;
+    }
+    Ok(())
+}
+
+// returns the trade in the underlying of an exercised option once the option's cost is known
+fn link_exercised_underlying(
+    event: PortfolioEvent,
+    ctx: &mut ProcessingContext<'_>,
+) -> Option<PortfolioEvent> {
+    let identifier = event.identifier.clone()?;
+    let Some(exercise) = ctx.option_exercise_legs.find(&event, &identifier).cloned() else {
+        return Some(event);
+    };
+    if exercise.kind != "Exercise" {
+        // premiums of written options are taxed when received, so an assignment doesn't affect
+        // the trade in the underlying
+        info!(target: "tax_report",
+            "{} of {} assigned, traded at the strike price",
+            identifier, exercise.option_identifier
+        );
+        return Some(event);
+    }
+    match ctx.option_exercise_legs.carried_costs.remove(&exercise.id) {
+        Some(cost) => Some(adjust_exercised_underlying(event, cost)),
+        // both legs are booked on the same day, so the underlying may come first
+        None => {
+            ctx.option_exercise_legs
+                .pending_underlyings
+                .insert(exercise.id, event);
+            None
+        }
+    }
+}
+
+// the cost of an exercised call adds to the purchase price of the underlying, the one of a put
+// reduces the proceeds of its sale
+fn adjust_exercised_underlying(mut event: PortfolioEvent, cost: Decimal) -> PortfolioEvent {
+    if event.units == dec!(0) {
+        return event;
+    }
+    let cost_per_unit = cost * event.applied_fx_rate.unwrap_or(dec!(1.0)) / event.units;
+    match event.direction {
+        Some(TradeDirection::Buy) => event.price_unit += cost_per_unit,
+        Some(TradeDirection::Sell) => event.price_unit -= cost_per_unit,
+        Some(TradeDirection::TransferOut) | None => {}
+    }
+    event
+}
+
+async fn process_exercised_underlying(
+    event: PortfolioEvent,
+    cost: Decimal,
+    ctx: &mut ProcessingContext<'_>,
+) -> Result<()> {
+    let event = adjust_exercised_underlying(event, cost);
+    match event.direction {
+        Some(TradeDirection::Buy) => process_buy(event, ctx).await,
+        Some(TradeDirection::Sell) => process_sell(event, ctx).await,
+        Some(TradeDirection::TransferOut) => process_transfer_out(event, ctx),
+        None => Ok(()),
+    }
+}
+
 async fn process_fx_conversion(
     event: PortfolioEvent,
     ctx: &mut ProcessingContext<'_>,
@@ -515,6 +1615,7 @@ async fn process_fx_conversion(
     match direction {
         TradeDirection::Buy => process_fx_buy(event, ctx).await,
         TradeDirection::Sell => process_fx_sell_conversion(event, ctx).await,
+        TradeDirection::TransferOut => bail!("FX conversions can't be transfers"),
     }
 }
 
@@ -602,8 +1703,12 @@ async fn process_dividend_aequivalent(
                 average_cost: dec!(0.0),
                 weighted_avg_fx_rate: dec!(1.0),
                 name: full_report.isin.clone(),
+                currency: full_report.currency.clone(),
+                old_units: dec!(0.0),
+                old_average_cost: dec!(0.0),
             });
-        wacs.units
+        // distributions of funds are taxed for Altbestand as well
+        wacs.units + wacs.old_units
     };
 
     let cost_adjustment = convert_amount(
@@ -614,7 +1719,7 @@ async fn process_dividend_aequivalent(
     )
     .await?;
 
-    if let Some(sec_wac) = ctx.securities_wacs.get_mut(&full_report.isin) {
+    for sec_wac in wacs_of_security(&full_report.isin, ctx) {
         sec_wac.average_cost += cost_adjustment;
     }
 
@@ -642,6 +1747,106 @@ async fn process_dividend_aequivalent(
         let year_entry = ctx.get_year_entry();
         year_entry.dividend_equivalents += taxed_eur;
         year_entry.withheld_tax_dividends += withheld_eur;
+        year_entry.domestic_tax_dividends += withheld_eur;
+    }
+
+    Ok(())
+}
+
+async fn process_non_reporting_funds(
+    year_end: DateTime<Utc>,
+    ctx: &mut ProcessingContext<'_>,
+) -> Result<()> {
+    let year = year_end.year();
+    let held_funds = ctx
+        .securities_wacs
+        .iter()
+        .filter(|(isin, sec_wac)| {
+            sec_wac.units + sec_wac.old_units > dec!(0)
+                && ctx.non_reporting_funds.funds.contains_key(*isin)
+                && !ctx
+                    .non_reporting_funds
+                    .report_years
+                    .contains(&(isin.to_string(), year))
+        })
+        // the lump-sum income is taxed for Altbestand as well
+        .map(|(isin, sec_wac)| (isin.clone(), sec_wac.units + sec_wac.old_units))
+        .collect_vec();
+
+    for (isin, units) in held_funds {
+        if ctx.non_reporting_funds.funds.get(&isin).map(String::as_str) == Some(REPORTING_FUND) {
+            warn!(target: "tax_report", "No OeKB report for {} in {}, it's taxed as non-reporting fund. Run housekeeping to fetch the latest reports.", isin, year);
+        }
+
+        let Some((first_price, last_price)) = ctx.non_reporting_funds.prices_in_year(&isin, year)
+        else {
+            warn!(target: "tax_report", "No prices of {} in {}, its lump-sum income can't be calculated. Add them with `funds price`.", isin, year);
+            ctx.non_reporting_fund_income.push(NonReportingFundIncome {
+                year,
+                isin,
+                units,
+                first_price: None,
+                last_price: None,
+                currency: "-".to_string(),
+                income_per_unit: dec!(0),
+                income_eur: dec!(0),
+            });
+            continue;
+        };
+        if first_price.currency != last_price.currency {
+            warn!(target: "tax_report", "Prices of {} in {} are in different currencies, its lump-sum income can't be calculated.", isin, year);
+            continue;
+        }
+
+        let income_per_unit = lump_sum_income_per_unit(first_price.price, last_price.price);
+        let income_per_unit_eur = convert_amount(
+            income_per_unit,
+            &last_price.date,
+            &last_price.currency,
+            "EUR",
+        )
+        .await?;
+        info!(target: "tax_report", "Lump-sum income of {} in {}: {} {} per unit", isin, year, income_per_unit, last_price.currency);
+
+        // the deemed distribution is added to the cost in the currency of the position, so it
+        // isn't taxed again on sale
+        let mut cost_increase = HashMap::new();
+        let currencies = wacs_of_security(&isin, ctx)
+            .map(|sec_wac| sec_wac.currency.clone())
+            .unique()
+            .collect_vec();
+        for currency in currencies {
+            let increase = if currency == last_price.currency {
+                income_per_unit
+            } else {
+                convert_amount(
+                    income_per_unit,
+                    &last_price.date,
+                    &last_price.currency,
+                    &currency,
+                )
+                .await?
+            };
+            cost_increase.insert(currency, increase);
+        }
+        for sec_wac in wacs_of_security(&isin, ctx) {
+            sec_wac.average_cost += cost_increase[&sec_wac.currency];
+        }
+
+        let income_eur = income_per_unit_eur * units;
+        if ctx.should_count_taxable(year_end) {
+            ctx.get_year_entry().non_reporting_fund_income += income_eur;
+        }
+        ctx.non_reporting_fund_income.push(NonReportingFundIncome {
+            year,
+            isin,
+            units,
+            first_price: Some(first_price.price),
+            last_price: Some(last_price.price),
+            currency: last_price.currency.clone(),
+            income_per_unit: income_per_unit.round_dp(4),
+            income_eur: income_eur.round_dp(2),
+        });
     }
 
     Ok(())
@@ -650,6 +1855,53 @@ async fn process_dividend_aequivalent(
 pub async fn get_capital_gains_tax_report(
     from_date: Option<DateTime<Utc>>,
     until_date: Option<DateTime<Utc>>,
+) -> Result<TaxationReport> {
+    let report = calculate_capital_gains_tax_report(from_date, until_date, vec![]).await?;
+
+    if from_date.is_none() && until_date.is_none() {
+        info!(target: "tax_report", "Exporting taxation report to JSON");
+        export_json(&report, "taxation")?;
+    }
+
+    Ok(report)
+}
+
+/// Runs the tax engine over the stored events and additional ones that aren't stored, e.g. a
+/// sale that's only simulated.
+pub async fn calculate_capital_gains_tax_report(
+    from_date: Option<DateTime<Utc>>,
+    until_date: Option<DateTime<Utc>>,
+    additional_events: Vec<PortfolioEvent>,
+) -> Result<TaxationReport> {
+    let mut stored_events = vec![];
+    for year in get_active_years().await? {
+        let start_date = Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+        let end_date = Utc.with_ymd_and_hms(year, 12, 31, 23, 59, 59).unwrap();
+        stored_events.extend(get_events(start_date, end_date).await?);
+    }
+    run_tax_engine(from_date, until_date, stored_events, additional_events).await
+}
+
+/// Runs the tax engine again over the stored events of an earlier report and additional ones,
+/// e.g. to simulate several sales one at a time.
+pub async fn replay_capital_gains_tax_report(
+    baseline: &TaxationReport,
+    additional_events: Vec<PortfolioEvent>,
+) -> Result<TaxationReport> {
+    run_tax_engine(
+        baseline.from_date,
+        baseline.until_date,
+        baseline.stored_events.clone(),
+        additional_events,
+    )
+    .await
+}
+
+async fn run_tax_engine(
+    from_date: Option<DateTime<Utc>>,
+    until_date: Option<DateTime<Utc>>,
+    stored_events: Vec<PortfolioEvent>,
+    additional_events: Vec<PortfolioEvent>,
 ) -> Result<TaxationReport> {
     info!(target: "tax_report", "Starting capital gains tax report generation (from={:?}, until={:?})", from_date, until_date);
 
@@ -657,11 +1909,7 @@ pub async fn get_capital_gains_tax_report(
 
     debug!(target: "tax_report", "Loaded {} stock splits", stock_split_information.len());
 
-    let tax_rates = TaxRates {
-        interest: dec!(0.25),
-        capital_gains: dec!(0.275),
-        dividends: dec!(0.275),
-    };
+    let tax_rates = TaxRates::austrian();
 
     info!(target: "tax_report", "Using tax rates: Interest {}%, Capital Gains {}%, Dividends {}%",
         tax_rates.interest * dec!(100),
@@ -669,16 +1917,41 @@ pub async fn get_capital_gains_tax_report(
         tax_rates.dividends * dec!(100)
     );
 
-    let tax_relevant_years = get_active_years().await?;
+    let mut tax_relevant_years = get_active_years().await?;
+    tax_relevant_years.extend(additional_events.iter().map(|event| event.date.year()));
+    tax_relevant_years.sort();
+    tax_relevant_years.dedup();
     let mut taxable_amounts = BTreeMap::new();
+    let mut crypto_amounts = BTreeMap::new();
     let mut currency_wacs = BTreeMap::new();
     let mut securities_wacs = BTreeMap::new();
+    let mut depot_wacs = BTreeMap::new();
+    let mut crypto_wacs = BTreeMap::new();
+    let mut crypto_swap_legs = CryptoSwapLegs::new(get_crypto_swaps().await?);
+    let mut derivative_wacs = BTreeMap::new();
+    let mut option_exercise_legs = OptionExerciseLegs::new(get_option_exercises().await?);
+    let non_reporting_funds = NonReportingFunds::new(
+        get_fund_classifications().await?,
+        get_oekb_report_years().await?,
+        get_fund_prices().await?,
+    );
+    let bonds = get_bond_isins().await?;
+    let mut non_reporting_fund_income = vec![];
 
     for year in tax_relevant_years {
         let mut ctx = ProcessingContext {
             taxable_amounts: &mut taxable_amounts,
+            crypto_amounts: &mut crypto_amounts,
             currency_wacs: &mut currency_wacs,
             securities_wacs: &mut securities_wacs,
+            depot_wacs: &mut depot_wacs,
+            crypto_wacs: &mut crypto_wacs,
+            crypto_swap_legs: &mut crypto_swap_legs,
+            derivative_wacs: &mut derivative_wacs,
+            option_exercise_legs: &mut option_exercise_legs,
+            non_reporting_funds: &non_reporting_funds,
+            bonds: &bonds,
+            non_reporting_fund_income: &mut non_reporting_fund_income,
             tax_rates: &tax_rates,
             year,
             stock_split_information: &mut stock_split_information,
@@ -688,10 +1961,20 @@ pub async fn get_capital_gains_tax_report(
 
         let start_date = Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
         let end_date = Utc.with_ymd_and_hms(year, 12, 31, 23, 59, 59).unwrap();
-        let events = get_events(start_date, end_date).await?;
+        let mut events: Vec<PortfolioEvent> = stored_events
+            .iter()
+            .chain(additional_events.iter())
+            .filter(|event| event.date.year() == year)
+            .cloned()
+            .collect();
+        events.sort_by_key(|event| event.date);
         for event in events {
             process_event(event, &mut ctx).await?;
         }
+        // the lump-sum income is deemed received at the end of the year
+        if end_date < Utc::now() {
+            process_non_reporting_funds(end_date, &mut ctx).await?;
+        }
 
         // Apply tax optimizations for this year
         let tax_optimizations = get_tax_optimizations_by_date_range(start_date, end_date).await?;
@@ -741,25 +2024,82 @@ pub async fn get_capital_gains_tax_report(
         }
     }
 
+    let creditable_tax = get_creditable_withholding_tax(from_date, until_date).await?;
+    for (year, (dividends, interest)) in creditable_tax {
+        if let Some(amounts) = taxable_amounts.get_mut(&year) {
+            amounts.creditable_tax_dividends = dividends;
+            amounts.creditable_tax_interest = interest;
+        }
+    }
+
+    // calculated from the unrounded amounts of every year with taxable income
+    let years: BTreeSet<i32> = taxable_amounts
+        .keys()
+        .chain(crypto_amounts.keys())
+        .copied()
+        .collect();
+    let settlements: BTreeMap<i32, TaxSettlement> = years
+        .into_iter()
+        .map(|year| {
+            let mut settlement = TaxSettlement::calculate(
+                taxable_amounts
+                    .get(&year)
+                    .unwrap_or(&AnnualTaxableAmounts::default()),
+                crypto_amounts
+                    .get(&year)
+                    .unwrap_or(&AnnualCryptoAmounts::default()),
+                &tax_rates,
+            );
+            settlement.round_all(2);
+            (year, settlement)
+        })
+        .collect();
+    let unrounded_taxable_amounts = taxable_amounts.clone();
+    let unrounded_crypto_amounts = crypto_amounts.clone();
+    let unrounded_depot_wacs = depot_wacs.clone();
+
     post_process(
         &mut taxable_amounts,
         &mut currency_wacs,
         &mut securities_wacs,
+        &mut depot_wacs,
     );
+    for amounts in crypto_amounts.values_mut() {
+        amounts.round_all(2);
+    }
+    crypto_wacs.retain(|_, wac: &mut CryptoWac| wac.units + wac.old_units > dec!(0));
+    for wac in crypto_wacs.values_mut() {
+        wac.round_all();
+    }
+    derivative_wacs.retain(|_, wac: &mut DerivativeWac| wac.units != dec!(0));
+    let multipliers: HashMap<String, Decimal> = get_derivatives()
+        .await?
+        .into_iter()
+        .map(|derivative| (derivative.id, derivative.multiplier))
+        .collect();
+    for (identifier, wac) in derivative_wacs.iter_mut() {
+        wac.multiplier = multipliers.get(identifier).copied().unwrap_or(dec!(1));
+        wac.round_all();
+    }
 
     let report = TaxationReport {
         created_at: Utc::now(),
         from_date,
         until_date,
         taxable_amounts,
+        crypto_amounts,
         securities_wacs,
+        depot_wacs,
         currency_wacs,
+        crypto_wacs,
+        derivative_wacs,
+        non_reporting_fund_income,
+        settlements,
+        unrounded_taxable_amounts,
+        unrounded_crypto_amounts,
+        unrounded_depot_wacs,
+        stored_events,
     };
-
-    if from_date.is_none() && until_date.is_none() {
-        info!(target: "tax_report", "Exporting taxation report to JSON");
-        export_json(&report, "taxation")?;
-    }
     info!(target: "tax_report", "Tax report generated successfully");
 
     Ok(report)
@@ -769,13 +2109,15 @@ fn post_process(
     taxable_amounts: &mut BTreeMap<i32, AnnualTaxableAmounts>,
     currency_wacs: &mut BTreeMap<String, FxWac>,
     securities_wacs: &mut BTreeMap<String, SecWac>,
+    depot_wacs: &mut BTreeMap<String, BTreeMap<String, SecWac>>,
 ) {
     for amounts in taxable_amounts.values_mut() {
         amounts.round_all(2);
     }
 
     currency_wacs.retain(|_, wac| wac.units > dec!(0));
-    securities_wacs.retain(|_, sec_wac| sec_wac.units > dec!(0));
+    // open short positions are kept with their negative units
+    securities_wacs.retain(|_, sec_wac| sec_wac.units != dec!(0) || sec_wac.old_units > dec!(0));
 
     for wac in currency_wacs.values_mut() {
         wac.round_all();
@@ -783,6 +2125,13 @@ fn post_process(
     for sec_wac in securities_wacs.values_mut() {
         sec_wac.round_all();
     }
+    for wacs in depot_wacs.values_mut() {
+        wacs.retain(|_, sec_wac| sec_wac.units + sec_wac.old_units > dec!(0));
+        for sec_wac in wacs.values_mut() {
+            sec_wac.round_all();
+        }
+    }
+    depot_wacs.retain(|_, wacs| !wacs.is_empty());
     info!(target: "tax_report", "Post-processing report data");
 }
 
@@ -862,11 +2211,7 @@ pub async fn get_detailed_capital_gains_tax_report(
         unique_currencies: unique_currencies.into_iter().collect(),
     };
 
-    let tax_rates = TaxRates {
-        interest: dec!(0.25),
-        capital_gains: dec!(0.275),
-        dividends: dec!(0.275),
-    };
+    let tax_rates = TaxRates::austrian();
 
     let detailed_report = DetailedTaxationReport {
         report,
@@ -890,3 +2235,121 @@ pub async fn export_detailed_capital_gains_tax_report(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settle(amounts: AnnualTaxableAmounts) -> TaxSettlement {
+        TaxSettlement::calculate(
+            &amounts,
+            &AnnualCryptoAmounts::default(),
+            &TaxRates::austrian(),
+        )
+    }
+
+    #[test]
+    fn losses_are_offset_against_gains_and_other_income_before_dividends() {
+        let settlement = settle(AnnualTaxableAmounts {
+            capital_gains: dec!(500),
+            non_reporting_fund_income: dec!(500),
+            bond_interest: dec!(500),
+            dividends: dec!(1000),
+            capital_losses: dec!(1500),
+            ..Default::default()
+        });
+
+        assert_eq!(settlement.offset_losses, dec!(1500));
+        assert_eq!(settlement.forfeited_losses, dec!(0));
+        assert_eq!(settlement.tax_due, dec!(275));
+    }
+
+    #[test]
+    fn losses_beyond_gains_are_offset_against_dividends() {
+        let settlement = settle(AnnualTaxableAmounts {
+            capital_gains: dec!(1000),
+            dividends: dec!(1000),
+            capital_losses: dec!(1500),
+            withheld_tax_dividends: dec!(150),
+            creditable_tax_dividends: dec!(150),
+            ..Default::default()
+        });
+
+        assert_eq!(settlement.offset_losses, dec!(1500));
+        assert_eq!(settlement.tax_due, dec!(137.5));
+        // the credit is capped at the Austrian tax on the dividends left after the offset
+        assert_eq!(settlement.non_creditable_tax, dec!(12.5));
+        assert_eq!(settlement.balance, dec!(0));
+    }
+
+    #[test]
+    fn cash_interest_is_not_offset_against_losses() {
+        let settlement = settle(AnnualTaxableAmounts {
+            cash_interest: dec!(1000),
+            capital_losses: dec!(500),
+            withheld_tax_interest: dec!(250),
+            creditable_tax_interest: dec!(250),
+            ..Default::default()
+        });
+
+        assert_eq!(settlement.offset_losses, dec!(0));
+        assert_eq!(settlement.forfeited_losses, dec!(500));
+        assert_eq!(settlement.tax_due, dec!(250));
+        assert_eq!(settlement.balance, dec!(0));
+    }
+
+    #[test]
+    fn negative_bond_interest_is_offset_like_a_loss() {
+        let settlement = settle(AnnualTaxableAmounts {
+            capital_gains: dec!(1000),
+            bond_interest: dec!(-100),
+            ..Default::default()
+        });
+
+        assert_eq!(settlement.losses, dec!(100));
+        assert_eq!(settlement.tax_due, dec!(247.5));
+    }
+
+    #[test]
+    fn dividend_tax_is_credited_up_to_the_treaty_rate() {
+        // 27.5% withheld, of which the 15% of the treaty are credited
+        let settlement = settle(AnnualTaxableAmounts {
+            dividends: dec!(1000),
+            withheld_tax_dividends: dec!(275),
+            creditable_tax_dividends: dec!(150),
+            ..Default::default()
+        });
+
+        assert_eq!(settlement.tax_due, dec!(275));
+        assert_eq!(settlement.withheld_tax, dec!(275));
+        assert_eq!(settlement.non_creditable_tax, dec!(125));
+        assert_eq!(settlement.balance, dec!(125));
+    }
+
+    #[test]
+    fn tax_withheld_on_deemed_fund_distributions_is_credited_in_full() {
+        let settlement = settle(AnnualTaxableAmounts {
+            dividend_equivalents: dec!(100),
+            withheld_tax_dividends: dec!(27.5),
+            domestic_tax_dividends: dec!(27.5),
+            ..Default::default()
+        });
+
+        assert_eq!(settlement.tax_due, dec!(27.5));
+        assert_eq!(settlement.non_creditable_tax, dec!(0));
+        assert_eq!(settlement.balance, dec!(0));
+    }
+
+    #[test]
+    fn interest_tax_is_credited_up_to_the_treaty_rate() {
+        let settlement = settle(AnnualTaxableAmounts {
+            bond_interest: dec!(1000),
+            withheld_tax_interest: dec!(275),
+            creditable_tax_interest: dec!(150),
+            ..Default::default()
+        });
+
+        assert_eq!(settlement.non_creditable_tax, dec!(125));
+        assert_eq!(settlement.balance, dec!(125));
+    }
+}
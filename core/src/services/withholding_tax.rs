@@ -0,0 +1,403 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::bail;
+use chrono::{DateTime, Datelike, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use tabled::Tabled;
+use typeshare::typeshare;
+
+use crate::{
+    database::queries::{
+        dividend::get_dividends_with_withholding_tax,
+        instrument::batch_get_instrument_names,
+        interest::get_interest_with_withholding_tax,
+        withholding_tax::{
+            get_treaty_rates, get_withholding_tax_domiciles, upsert_treaty_rate,
+            upsert_withholding_tax_domicile,
+        },
+    },
+    services::{
+        importers::validation::is_valid_isin, instruments::bonds::bond_isin_from_principal,
+        market_data::fx_rates::convert_amount, taxation::TaxRates,
+    },
+};
+
+// rates of Austria's double taxation agreements for portfolio investors, tax withheld beyond them
+// can be reclaimed from the source country. Please check them against the current agreements,
+// they can be overridden with `reclaims treaty-rate`.
+const DIVIDEND_TREATY_RATES: &[(&str, Decimal)] = &[
+    ("BE", dec!(0.15)),
+    ("CA", dec!(0.15)),
+    ("CH", dec!(0.15)),
+    ("DE", dec!(0.15)),
+    ("DK", dec!(0.15)),
+    ("ES", dec!(0.15)),
+    ("FR", dec!(0.15)),
+    ("IT", dec!(0.15)),
+    ("NL", dec!(0.15)),
+    ("NO", dec!(0.15)),
+    ("US", dec!(0.15)),
+];
+const INTEREST_TREATY_RATES: &[(&str, Decimal)] = &[("BE", dec!(0.15))];
+
+const UNKNOWN_COUNTRY: &str = "Unknown";
+
+#[typeshare]
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WithholdingTaxKind {
+    Dividend,
+    Interest,
+}
+
+// a payment tax beyond the treaty rate was withheld from, to be listed in the reclaim form
+#[typeshare]
+#[derive(Debug, Serialize, Tabled)]
+pub struct ReclaimablePayment {
+    pub date: DateTime<Utc>,
+    pub identifier: String,
+    pub name: String,
+    pub broker: String,
+    pub gross_amount: Decimal,
+    pub currency: String,
+    pub withheld_tax: Decimal,
+    pub withholding_tax_currency: String,
+    pub withholding_rate: Decimal,
+    // in the currency the tax was withheld in
+    pub reclaimable_tax: Decimal,
+    pub reclaimable_tax_eur: Decimal,
+}
+
+// the tax withheld by a country in a year, and how much of it is credited in Austria or can be
+// reclaimed from the country
+#[typeshare]
+#[derive(Debug, Serialize, Tabled)]
+pub struct WithholdingTaxReclaim {
+    pub year: i32,
+    pub country: String,
+    #[tabled(display = "display_kind")]
+    pub kind: WithholdingTaxKind,
+    pub withheld_tax_eur: Decimal,
+    #[tabled(display = "display_rate")]
+    pub treaty_rate: Option<Decimal>,
+    pub creditable_tax_eur: Decimal,
+    pub reclaimable_tax_eur: Decimal,
+    #[tabled(skip)]
+    pub payments: Vec<ReclaimablePayment>,
+}
+
+fn display_kind(kind: &WithholdingTaxKind) -> String {
+    format!("{:?}", kind)
+}
+
+fn display_rate(rate: &Option<Decimal>) -> String {
+    rate.map_or("unknown".to_string(), |rate| {
+        format!("{}%", (rate * dec!(100)).normalize())
+    })
+}
+
+struct WithheldPayment {
+    kind: WithholdingTaxKind,
+    // share lending interest is credited along with dividends, the way the tax engine books it
+    credited_with_dividends: bool,
+    date: DateTime<Utc>,
+    identifier: String,
+    broker: String,
+    amount: Decimal,
+    currency: String,
+    amount_eur: Decimal,
+    withholding_tax: Decimal,
+    withholding_tax_currency: String,
+}
+
+pub async fn set_withholding_tax_domicile(identifier: &str, country: &str) -> anyhow::Result<()> {
+    let country = parse_country(country)?;
+    if identifier.trim().is_empty() {
+        bail!("The identifier can't be empty");
+    }
+    upsert_withholding_tax_domicile(identifier.trim(), &country).await
+}
+
+// the rate is given in percent, e.g. 15 for 15%
+pub async fn set_treaty_rate(
+    country: &str,
+    rate_percent: Decimal,
+    kind: WithholdingTaxKind,
+) -> anyhow::Result<()> {
+    let country = parse_country(country)?;
+    if rate_percent < dec!(0) || rate_percent > dec!(100) {
+        bail!("The treaty rate needs to be between 0 and 100%");
+    }
+    let rate = rate_percent / dec!(100);
+    match kind {
+        WithholdingTaxKind::Dividend => upsert_treaty_rate(&country, Some(rate), None).await,
+        WithholdingTaxKind::Interest => upsert_treaty_rate(&country, None, Some(rate)).await,
+    }
+}
+
+fn parse_country(country: &str) -> anyhow::Result<String> {
+    let country = country.trim().to_uppercase();
+    if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+        bail!("{} is not a two-letter country code", country);
+    }
+    Ok(country)
+}
+
+// the country of the ISIN unless a domicile is configured, cash interest is attributed to the
+// domicile configured for the broker paying it
+fn source_country(
+    identifier: &str,
+    broker: &str,
+    domiciles: &HashMap<String, String>,
+) -> Option<String> {
+    if let Some(country) = domiciles.get(identifier) {
+        return Some(country.clone());
+    }
+    if is_valid_isin(identifier) {
+        return Some(identifier[..2].to_string());
+    }
+    domiciles.get(broker).cloned()
+}
+
+async fn withheld_tax_eur(payment: &WithheldPayment) -> anyhow::Result<Decimal> {
+    if payment.withholding_tax_currency == "EUR" {
+        Ok(payment.withholding_tax)
+    } else if payment.withholding_tax_currency == payment.currency && payment.amount != dec!(0) {
+        Ok(payment.withholding_tax * payment.amount_eur / payment.amount)
+    } else {
+        convert_amount(
+            payment.withholding_tax,
+            &payment.date.date_naive(),
+            &payment.withholding_tax_currency,
+            "EUR",
+        )
+        .await
+    }
+}
+
+// a payment along with the country that withheld tax from it, and the shares of it credited in
+// Austria and reclaimable from that country
+struct AssessedPayment {
+    payment: WithheldPayment,
+    country: String,
+    treaty_rate: Option<Decimal>,
+    withheld_eur: Decimal,
+    withholding_rate: Decimal,
+    creditable_rate: Decimal,
+    reclaimable_rate: Decimal,
+}
+
+async fn assess_payments(year: Option<i32>) -> anyhow::Result<Vec<AssessedPayment>> {
+    let tax_rates = TaxRates::austrian();
+    let domiciles: HashMap<String, String> = get_withholding_tax_domiciles()
+        .await?
+        .into_iter()
+        .map(|domicile| (domicile.identifier, domicile.country))
+        .collect();
+    let mut dividend_treaty_rates: HashMap<String, Decimal> = DIVIDEND_TREATY_RATES
+        .iter()
+        .map(|(country, rate)| (country.to_string(), *rate))
+        .collect();
+    let mut interest_treaty_rates: HashMap<String, Decimal> = INTEREST_TREATY_RATES
+        .iter()
+        .map(|(country, rate)| (country.to_string(), *rate))
+        .collect();
+    for treaty_rate in get_treaty_rates().await? {
+        if let Some(rate) = treaty_rate.dividend_rate {
+            dividend_treaty_rates.insert(treaty_rate.country.clone(), rate);
+        }
+        if let Some(rate) = treaty_rate.interest_rate {
+            interest_treaty_rates.insert(treaty_rate.country, rate);
+        }
+    }
+
+    let mut payments: Vec<WithheldPayment> = get_dividends_with_withholding_tax()
+        .await?
+        .into_iter()
+        .map(|dividend| WithheldPayment {
+            kind: WithholdingTaxKind::Dividend,
+            credited_with_dividends: true,
+            date: dividend.date,
+            identifier: dividend.isin,
+            broker: dividend.broker,
+            amount: dividend.amount,
+            currency: dividend.currency,
+            amount_eur: dividend.amount_eur,
+            withholding_tax: dividend.withholding_tax,
+            withholding_tax_currency: dividend.withholding_tax_currency,
+        })
+        .collect();
+    payments.extend(
+        get_interest_with_withholding_tax()
+            .await?
+            .into_iter()
+            .map(|interest| {
+                let bond_isin = bond_isin_from_principal(&interest.principal);
+                WithheldPayment {
+                    kind: WithholdingTaxKind::Interest,
+                    credited_with_dividends: interest.principal != "Cash" && bond_isin.is_none(),
+                    date: interest.date,
+                    // coupons are attributed to the bond they're paid on
+                    identifier: bond_isin.map_or(interest.principal.clone(), str::to_string),
+                    broker: interest.broker,
+                    amount: interest.amount,
+                    currency: interest.currency,
+                    amount_eur: interest.amount_eur,
+                    withholding_tax: interest.withholding_tax,
+                    withholding_tax_currency: interest.withholding_tax_currency,
+                }
+            }),
+    );
+    payments.retain(|payment| {
+        payment.amount_eur != dec!(0) && year.is_none_or(|year| payment.date.year() == year)
+    });
+
+    let mut assessed = vec![];
+    for payment in payments {
+        let country = source_country(&payment.identifier, &payment.broker, &domiciles)
+            .unwrap_or(UNKNOWN_COUNTRY.to_string());
+        let (treaty_rate, austrian_rate) = match payment.kind {
+            WithholdingTaxKind::Dividend => (
+                dividend_treaty_rates.get(&country).copied(),
+                tax_rates.dividends,
+            ),
+            WithholdingTaxKind::Interest => (
+                interest_treaty_rates.get(&country).copied(),
+                // coupons and share lending interest are taxed like dividends
+                if payment.identifier == "Cash" {
+                    tax_rates.interest
+                } else {
+                    tax_rates.capital_gains
+                },
+            ),
+        };
+
+        let withheld_eur = withheld_tax_eur(&payment).await?;
+        let withholding_rate = withheld_eur / payment.amount_eur;
+        // at most the treaty rate is credited, and never more than the Austrian tax
+        let creditable_rate = treaty_rate
+            .unwrap_or(austrian_rate)
+            .min(austrian_rate)
+            .min(withholding_rate);
+        let reclaimable_rate = treaty_rate.map_or(dec!(0), |treaty_rate| {
+            (withholding_rate - treaty_rate).max(dec!(0))
+        });
+        assessed.push(AssessedPayment {
+            payment,
+            country,
+            treaty_rate,
+            withheld_eur,
+            withholding_rate,
+            creditable_rate,
+            reclaimable_rate,
+        });
+    }
+    Ok(assessed)
+}
+
+/// Sums up the foreign tax withheld per year, source country and kind of payment, listing the
+/// payments tax can be reclaimed for.
+pub async fn get_withholding_tax_reclaims(
+    year: Option<i32>,
+) -> anyhow::Result<Vec<WithholdingTaxReclaim>> {
+    let assessed = assess_payments(year).await?;
+
+    let isins: Vec<String> = assessed
+        .iter()
+        .map(|assessed| assessed.payment.identifier.clone())
+        .filter(|identifier| is_valid_isin(identifier))
+        .collect();
+    let names: HashMap<String, String> = isins
+        .iter()
+        .cloned()
+        .zip(batch_get_instrument_names(&isins).await?)
+        .collect();
+
+    let mut reclaims: BTreeMap<(i32, String, WithholdingTaxKind), WithholdingTaxReclaim> =
+        BTreeMap::new();
+    for AssessedPayment {
+        payment,
+        country,
+        treaty_rate,
+        withheld_eur,
+        withholding_rate,
+        creditable_rate,
+        reclaimable_rate,
+    } in assessed
+    {
+        let reclaimable_eur = reclaimable_rate * payment.amount_eur;
+
+        let reclaim = reclaims
+            .entry((payment.date.year(), country.clone(), payment.kind))
+            .or_insert(WithholdingTaxReclaim {
+                year: payment.date.year(),
+                country,
+                kind: payment.kind,
+                withheld_tax_eur: dec!(0),
+                treaty_rate,
+                creditable_tax_eur: dec!(0),
+                reclaimable_tax_eur: dec!(0),
+                payments: vec![],
+            });
+        reclaim.withheld_tax_eur += withheld_eur;
+        reclaim.creditable_tax_eur += creditable_rate * payment.amount_eur;
+        reclaim.reclaimable_tax_eur += reclaimable_eur;
+
+        if reclaimable_eur > dec!(0) {
+            reclaim.payments.push(ReclaimablePayment {
+                date: payment.date,
+                name: names
+                    .get(&payment.identifier)
+                    .cloned()
+                    .unwrap_or(payment.identifier.clone()),
+                identifier: payment.identifier,
+                broker: payment.broker,
+                gross_amount: payment.amount,
+                currency: payment.currency,
+                withheld_tax: payment.withholding_tax,
+                withholding_tax_currency: payment.withholding_tax_currency,
+                withholding_rate: withholding_rate.round_dp(4),
+                reclaimable_tax: (payment.withholding_tax * reclaimable_rate / withholding_rate)
+                    .round_dp(2),
+                reclaimable_tax_eur: reclaimable_eur.round_dp(2),
+            });
+        }
+    }
+
+    Ok(reclaims
+        .into_values()
+        .map(|mut reclaim| {
+            reclaim.withheld_tax_eur = reclaim.withheld_tax_eur.round_dp(2);
+            reclaim.creditable_tax_eur = reclaim.creditable_tax_eur.round_dp(2);
+            reclaim.reclaimable_tax_eur = reclaim.reclaimable_tax_eur.round_dp(2);
+            reclaim
+        })
+        .collect())
+}
+
+/// The foreign tax credited in Austria per year for the payments within the range of a tax report,
+/// unrounded, split like the withheld tax the tax engine books: on dividends and share lending
+/// interest, and on interest of cash and bonds.
+pub async fn get_creditable_withholding_tax(
+    from_date: Option<DateTime<Utc>>,
+    until_date: Option<DateTime<Utc>>,
+) -> anyhow::Result<BTreeMap<i32, (Decimal, Decimal)>> {
+    let mut creditable: BTreeMap<i32, (Decimal, Decimal)> = BTreeMap::new();
+    for assessed in assess_payments(None).await? {
+        let payment = &assessed.payment;
+        if from_date.is_some_and(|from| payment.date < from)
+            || until_date.is_some_and(|until| payment.date > until)
+        {
+            continue;
+        }
+        let (dividends, interest) = creditable.entry(payment.date.year()).or_default();
+        let creditable_eur = assessed.creditable_rate * payment.amount_eur;
+        if payment.credited_with_dividends {
+            *dividends += creditable_eur;
+        } else {
+            *interest += creditable_eur;
+        }
+    }
+    Ok(creditable)
+}
@@ -63,7 +63,11 @@ const InterestCard: React.FC<TimelineCardProps> = ({ timelineEvent }) => {
           Interest
         </CardTitle>
         <CardDescription>
-          {timelineEvent.event_type === EventType.ShareInterest ? 'Share Lending Interest' : 'Cash Interest'}
+          {timelineEvent.event_type === EventType.ShareInterest
+            ? 'Share Lending Interest'
+            : timelineEvent.event_type === EventType.BondInterest
+              ? `Bond Interest (${timelineEvent.identifier})`
+              : 'Cash Interest'}
         </CardDescription>
       </CardHeader>
       <CardContent>
@@ -132,7 +132,12 @@ const Positions = () => {
                           {item?.name}
                         </a>
                       </TableCell>
-                      <TableCell>{item?.units}</TableCell>
+                      <TableCell>
+                        {item?.units}
+                        {item?.short && (
+                          <span className="text-muted-foreground"> (short)</span>
+                        )}
+                      </TableCell>
                     </TableRow>
                   ))}
                 </TableBody>
@@ -1,7 +1,7 @@
 import useSwr from "swr";
 import { useState } from "react";
 import { format } from "date-fns";
-import { AnnualTaxableAmounts, SecWac, TaxationReport, FxWac } from "@/types/core";
+import { AnnualTaxableAmounts, SecWac, TaxationReport, TaxSettlement, FxWac } from "@/types/core";
 import EmptyState, { EmptyStateVariants } from "@/components/composite/empty-state";
 import { Skeleton } from "@/components/ui/skeleton";
 import { Disclaimer } from "@/components/composite/disclaimer";
@@ -22,17 +22,40 @@ import { formatCurrency } from "@/lib/utils";
 const labelMap: Record<keyof AnnualTaxableAmounts, string> = {
   cash_interest: "Cash Interest",
   share_lending_interest: "Share Lending Interest",
+  bond_interest: "Bond Interest",
   capital_gains: "Capital Gains",
   capital_losses: "Capital Losses",
+  tax_free_gains: "Tax-Free Gains (Altbestand)",
+  derivative_gains: "Derivative Gains",
+  derivative_losses: "Derivative Losses",
   dividends: "Dividends",
   dividend_equivalents: "Dividend Equivalents",
+  non_reporting_fund_income: "Non-Reporting Fund Income",
   fx_appreciation: "FX Appreciation",
   withheld_tax_capital_gains: "Withheld Tax (Capital Gains)",
   withheld_tax_dividends: "Withheld Tax (Dividends)",
   withheld_tax_interest: "Withheld Tax (Interest)",
+  domestic_tax_dividends: "Domestic Tax (Fund Distributions)",
+  creditable_tax_dividends: "Creditable Tax (Dividends)",
+  creditable_tax_interest: "Creditable Tax (Interest)",
   tax_optimization_adjustment: "Tax Optimization",
 };
 
+const settlementLabelMap: Record<keyof TaxSettlement, string> = {
+  gains: "Gains",
+  other_income: "Other Income",
+  dividends: "Dividends",
+  losses: "Losses",
+  offset_losses: "Offset Losses",
+  forfeited_losses: "Forfeited Losses",
+  cash_interest: "Cash Interest",
+  tax_due: "Tax Due",
+  withheld_tax: "Withheld Tax",
+  non_creditable_tax: "Non-Creditable Withheld Tax",
+  tax_optimization_adjustment: "Tax Optimization",
+  balance: "Balance (to pay / refund)",
+};
+
 const Taxation = () => {
   const [fromDate, setFromDate] = useState<Date | undefined>(undefined);
   const [untilDate, setUntilDate] = useState<Date | undefined>(undefined);
@@ -243,6 +266,24 @@ const Taxation = () => {
                     ))}
                   </TableBody>
                 </Table>
+                {data.settlements?.[year] && (
+                  <Table className="mt-4">
+                    <TableHeader>
+                      <TableRow>
+                        <TableHead>Expected Settlement</TableHead>
+                        <TableHead>Amount</TableHead>
+                      </TableRow>
+                    </TableHeader>
+                    <TableBody>
+                      {Object.entries(data.settlements[year] as TaxSettlement).map(([key, value]) => (
+                        <TableRow key={key}>
+                          <TableCell>{settlementLabelMap[key as keyof TaxSettlement]}</TableCell>
+                          <TableCell>{formatCurrency(value)}</TableCell>
+                        </TableRow>
+                      ))}
+                    </TableBody>
+                  </Table>
+                )}
               </CardContent>
             </Card>
           ))}
@@ -259,6 +300,7 @@ const Taxation = () => {
                     <TableHead>Units</TableHead>
                     <TableHead>WAC</TableHead>
                     <TableHead>WAC FX</TableHead>
+                    <TableHead>Altbestand</TableHead>
                   </TableRow>
                 </TableHeader>
                 <TableBody>
@@ -270,6 +312,7 @@ const Taxation = () => {
                       <TableCell>{value.units}</TableCell>
                       <TableCell>{formatCurrency(parseFloat(value.average_cost))}</TableCell>
                       <TableCell>{formatCurrency(parseFloat(value.weighted_avg_fx_rate))}</TableCell>
+                      <TableCell>{value.old_units}</TableCell>
                     </TableRow>
                   ))}
                 </TableBody>
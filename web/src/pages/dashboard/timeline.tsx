@@ -98,6 +98,7 @@ export const Timeline = () => {
                 );
                 break;
               case EventType.ShareInterest:
+              case EventType.BondInterest:
                 eventComponent = (
                   <InterestCard
                     timelineEvent={timelineEvent}
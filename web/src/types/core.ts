@@ -23,6 +23,7 @@ export interface PositionWithName {
   isin: string;
   name: string;
   units: string;
+  short: boolean;
 }
 
 export interface PositionWithValueAndAllocation {
@@ -31,6 +32,7 @@ export interface PositionWithValueAndAllocation {
   value: string;
   units: string;
   share: string;
+  short: boolean;
 }
 
 export interface PortfolioEvent {
@@ -80,24 +82,49 @@ export interface PortfolioOverview {
 export interface AnnualTaxableAmounts {
   cash_interest: string;
   share_lending_interest: string;
+  bond_interest: string;
   capital_gains: string;
   capital_losses: string;
+  tax_free_gains: string;
+  derivative_gains: string;
+  derivative_losses: string;
   dividends: string;
   dividend_equivalents: string;
+  non_reporting_fund_income: string;
   fx_appreciation: string;
   withheld_tax_capital_gains: string;
   withheld_tax_dividends: string;
   withheld_tax_interest: string;
+  domestic_tax_dividends: string;
+  creditable_tax_dividends: string;
+  creditable_tax_interest: string;
   tax_optimization_adjustment: string;
 }
 
+export interface TaxSettlement {
+  gains: string;
+  other_income: string;
+  dividends: string;
+  losses: string;
+  offset_losses: string;
+  forfeited_losses: string;
+  cash_interest: string;
+  tax_due: string;
+  withheld_tax: string;
+  non_creditable_tax: string;
+  tax_optimization_adjustment: string;
+  balance: string;
+}
+
 export interface TaxationReport {
   created_at: Date;
   from_date: Date | null;
   until_date: Date | null;
   taxable_amounts: any;
   securities_wacs: any;
+  depot_wacs: any;
   currency_wacs: any;
+  settlements: any;
 }
 
 export interface FxWac {
@@ -110,16 +137,87 @@ export interface SecWac {
   average_cost: string;
   weighted_avg_fx_rate: string;
   name: string;
+  currency: string;
+  old_units: string;
+  old_average_cost: string;
+}
+
+export interface HarvestingCandidate {
+  broker: string;
+  isin: string;
+  name: string;
+  units: string;
+  currency: string;
+  average_cost: string;
+  current_price: string;
+  price_gain_eur: string;
+  fx_appreciation_eur: string;
+  unrealised_loss_eur: string;
+  tax_saving_eur: string;
+}
+
+export interface SaleSimulation {
+  isin: string;
+  name: string;
+  broker: string;
+  units: string;
+  currency: string;
+  price: string;
+  proceeds_eur: string;
+  realised_gain_eur: string;
+  fx_appreciation_eur: string;
+  additional_tax_eur: string;
+  settlement: TaxSettlement;
+  new_wac: SecWac | null;
+  new_depot_wac: SecWac | null;
+}
+
+export interface HarvestingReport {
+  year: number;
+  settlement: TaxSettlement;
+  candidates: HarvestingCandidate[];
+}
+
+export interface ReclaimablePayment {
+  date: Date;
+  identifier: string;
+  name: string;
+  broker: string;
+  gross_amount: string;
+  currency: string;
+  withheld_tax: string;
+  withholding_tax_currency: string;
+  withholding_rate: string;
+  reclaimable_tax: string;
+  reclaimable_tax_eur: string;
+}
+
+export interface WithholdingTaxReclaim {
+  year: number;
+  country: string;
+  kind: WithholdingTaxKind;
+  withheld_tax_eur: string;
+  treaty_rate: string | null;
+  creditable_tax_eur: string;
+  reclaimable_tax_eur: string;
+  payments: ReclaimablePayment[];
+}
+
+export enum WithholdingTaxKind {
+  Dividend = "Dividend",
+  Interest = "Interest",
 }
 
 export enum TradeDirection {
   Buy = "Buy",
   Sell = "Sell",
+  TransferOut = "TransferOut",
 }
 
 export enum EventType {
   CashInterest = "CashInterest",
   ShareInterest = "ShareInterest",
+  BondInterest = "BondInterest",
   Dividend = "Dividend",
   Trade = "Trade",
   FxConversion = "FxConversion",